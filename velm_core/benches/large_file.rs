@@ -0,0 +1,69 @@
+// Opens a 10MB file and edits rows all over it, then rows near each other, before undoing and
+// redoing as many edits as the history holds, printing how long each step takes. Run it with
+// `cargo bench --features test-util`, then again with `--features gap-buffer` added to compare the
+// gap buffer against the default `Vec` of rows.
+
+use std::time::Instant;
+use velm_core::{ui::Position, Document};
+
+const FILE_SIZE: usize = 10 * 1024 * 1024;
+const LINE: &str = "The quick brown fox jumps over the lazy dog, 0123456789\n";
+const EDITS: usize = 10_000;
+
+fn main() {
+    let file_name = std::env::temp_dir()
+        .join(format!("velm-{}-bench.txt", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    std::fs::write(&file_name, LINE.repeat(FILE_SIZE / LINE.len())).unwrap();
+
+    let start = Instant::now();
+    let mut doc = Document::open(&file_name).unwrap();
+    report("open", start);
+
+    let start = Instant::now();
+    doc.load_all().unwrap();
+    report("load all", start);
+
+    // A xorshift generator keeps the edits the same from run to run without a dependency.
+    let mut seed = 0x2545_f491_usize;
+    let mut random = |bound: usize| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed % bound
+    };
+
+    for (name, distance) in &[("scattered edits", doc.len()), ("nearby edits", 100)] {
+        let start = Instant::now();
+
+        for _ in 0..EDITS {
+            let row = (doc.len() / 2 + random(*distance)).saturating_sub(distance / 2) % doc.len();
+            let at = Position::new(random(LINE.len()), row);
+
+            match random(3) {
+                0 => doc.insert_newline(&at),
+                1 => doc.insert(&at, 'x').unwrap(),
+                _ => {
+                    doc.join_line(row);
+                }
+            }
+        }
+
+        report(name, start);
+    }
+
+    let start = Instant::now();
+    while doc.undo().is_some() {}
+    report("undo all", start);
+
+    let start = Instant::now();
+    while doc.redo().is_some() {}
+    report("redo all", start);
+
+    std::fs::remove_file(&file_name).unwrap();
+}
+
+fn report(name: &str, start: Instant) {
+    println!("{:<16}{:>12.1?}", name, start.elapsed());
+}
@@ -1,5 +1,12 @@
+use crate::component::QuickfixEntry;
+use crate::grep::Search;
 use crate::mode::Mode;
+use crate::ui::{Position, Rect};
 use std::fmt;
+use std::time::Duration;
+
+/// How long a `Message::Flash` is shown before it is cleared.
+pub const FLASH_DURATION: Duration = Duration::from_secs(3);
 
 /// `Message` is an enum that captures all messages that the `Editor` and its `Component`s
 /// understand.
@@ -8,27 +15,226 @@ pub enum Message {
     AbortCommandLineInput,
     EndCommandLineInput,
     ParseCommandLineInput(String),
+    HistoryPrevious,
+    HistoryNext,
 
     EnterMode(Mode),
 
+    Resize(Rect),
+    /// Sent periodically by the `Editor` to drive animations and timers.
+    Tick,
+
     InsertChar(char),
     InsertLineBreak,
+    /// Insert a block of text, such as one pasted into the terminal, exactly as it is given.
+    InsertText(String),
+    /// Insert a tab, or spaces up to the next tab stop when tabs are expanded.
+    InsertTab,
+    /// Overwrite the character under the cursor, or append it at the end of the line.
+    ReplaceChar(char),
+    /// Put back the character overwritten by the last `ReplaceChar`.
+    RestoreReplacedChar,
+    OpenLineBelow,
+    OpenLineAbove,
+    JoinLines(usize),
     DeleteCharForward,
     DeleteCharBackward,
+    DeleteCharsUnderCursor(usize),
+    DeleteCharsBeforeCursor(usize),
+    DeleteSelection,
+    /// Delete up to the start of the given number of words ahead, like `dw`.
+    DeleteWordForward(usize),
+    /// Delete back to the start of the given number of words behind, like `db`.
+    DeleteWordBackward(usize),
+    /// Delete through the end of the given number of words ahead, like `de`.
+    DeleteToWordEnd(usize),
+    /// Delete the given number of words from the one under the cursor along with the whitespace
+    /// that follows them, like `daw`.
+    DeleteAWord(usize),
+    /// Delete the text replaced by `cw`, which is like `de` except that the word under the cursor
+    /// is always the first to go.
+    ChangeWord(usize),
+    /// Delete the text replaced by `cc` from the given number of lines, keeping the indent of the
+    /// first line when auto-indent is on.
+    ChangeLine(usize),
+    /// Delete from the cursor to the end of the line, like `C`.
+    ChangeToLineEnd,
+    /// Swap the case of the given number of characters from the cursor onwards.
+    ToggleCase(usize),
+    /// Indent the given number of lines from the cursor's line by one level.
+    IndentLine(usize),
+    /// Remove up to one level of indentation from the given number of lines from the cursor's
+    /// line.
+    DedentLine(usize),
+    YankSelection,
+    Paste,
+    Undo,
+    Redo,
+    /// Repeat the last change the given number of times.
+    RepeatLastChange(usize),
 
     MoveCursorUp(usize),
     MoveCursorDown(usize),
     MoveCursorLeft(usize),
     MoveCursorRight(usize),
+    MoveCursorWordForward(usize),
+    MoveCursorWordBackward(usize),
+    MoveCursorWordEnd(usize),
+    MoveCursorAfterChar,
     MoveCursorLineStart,
+    /// Move to the start of the line or, with smart home, alternate between the first non-blank
+    /// character and the start of the line.
+    MoveCursorHome,
+    MoveCursorLineFirstNonBlank,
     MoveCursorLineEnd,
     MoveCursorPageUp,
     MoveCursorPageDown,
+    /// Scroll the view and move the cursor down by half the height of the view.
+    ScrollHalfPageDown,
+    /// Scroll the view and move the cursor up by half the height of the view.
+    ScrollHalfPageUp,
+    /// Scroll the view down by a row, only moving the cursor if it would leave the view.
+    ScrollLineDown,
+    /// Scroll the view up by a row, only moving the cursor if it would leave the view.
+    ScrollLineUp,
+    MoveCursorDocumentStart,
+    MoveCursorDocumentEnd,
+    GotoPosition(Position),
+    GoToLine(usize),
+    MouseClick(Position),
+    GotoLastEdit,
+
+    OpenFile(String),
+    /// Open the file read-only, so that it can be looked at without risk of changing it.
+    ViewFile(String),
+    /// The number of rows in a large file with the file name, counted while the rows of its
+    /// document are read as they are needed.
+    RowsCounted {
+        file_name: String,
+        rows: usize,
+    },
+    NextBuffer,
+    PrevBuffer,
+    /// Switch to the buffer with the given number, counting from 1.
+    GoToBuffer(usize),
+    Split(Option<String>),
+    FocusNextPane,
+    /// Change an option while the `Editor` is running, such as `:set nowrap` or `:set tabstop=4`.
+    /// The value is only given for options that take one.
+    SetOption {
+        key: String,
+        value: Option<String>,
+    },
+
+    Search(String),
+    SearchNext,
+    SearchPrevious,
+    /// Stop highlighting the matches of the last search until the next search.
+    ClearSearchHighlight,
+    StatusInfo(String),
+    /// Report the number of words, lines and characters in the document.
+    WordCount,
+    /// Report an error that the user can recover from, such as a failed save, without stopping
+    /// the `Editor`.
+    StatusError(String),
+    /// Show a message that is cleared automatically once `FLASH_DURATION` has passed.
+    Flash(String),
+    ClearFlash,
+
+    GlobalSearch(String),
+    Grep(String, Option<String>),
+    /// A batch of matches from a running grep, the batch is empty once the search is done.
+    GrepProgress(Search, Vec<QuickfixEntry>),
+    SetQuickfixList(Vec<QuickfixEntry>),
+    AppendQuickfixEntries(Vec<QuickfixEntry>),
+    QuickfixNext,
+    QuickfixPrevious,
+    QuickfixOpen,
+    QuickfixClose,
 
     Save,
     SaveAs(String),
+    /// Save the document, optionally as the given file name, and then quit. When only saving if
+    /// modified, as with `:x`, the document is only written if it has changes or a file name is
+    /// given, while `:wq` always writes it.
+    SaveQuit {
+        file_name: Option<String>,
+        only_if_modified: bool,
+    },
+    /// The lines of the document with the id were written to disk under the file name, as they
+    /// were at the given revision of the document.
+    Saved {
+        document: usize,
+        file_name: String,
+        lines: usize,
+        revision: usize,
+    },
+    /// Replace the document with the unsaved changes in the swap file left behind by an earlier
+    /// session.
+    Recover,
+    /// The unsaved changes to the document with the id were written to its swap file.
+    SwapWritten(usize),
 
     Quit,
+    ForceQuit,
+
+    /// Allows a single input to have multiple effects, such as mutating the document and then
+    /// switching mode. The `Message`s are dispatched by the `Editor` in the given order.
+    Batch(Vec<Message>),
+}
+
+impl Message {
+    /// Returns `true` if the message changes the document. Changes, unlike motions, are what
+    /// `RepeatLastChange` repeats.
+    #[must_use]
+    pub fn is_change(&self) -> bool {
+        match self {
+            Self::InsertChar(_)
+            | Self::InsertLineBreak
+            | Self::InsertText(_)
+            | Self::InsertTab
+            | Self::ReplaceChar(_)
+            | Self::RestoreReplacedChar
+            | Self::OpenLineBelow
+            | Self::OpenLineAbove
+            | Self::JoinLines(_)
+            | Self::DeleteCharForward
+            | Self::DeleteCharBackward
+            | Self::DeleteCharsUnderCursor(_)
+            | Self::DeleteCharsBeforeCursor(_)
+            | Self::DeleteSelection
+            | Self::DeleteWordForward(_)
+            | Self::DeleteWordBackward(_)
+            | Self::DeleteToWordEnd(_)
+            | Self::DeleteAWord(_)
+            | Self::ChangeWord(_)
+            | Self::ChangeLine(_)
+            | Self::ChangeToLineEnd
+            | Self::ToggleCase(_)
+            | Self::IndentLine(_)
+            | Self::DedentLine(_)
+            | Self::Paste => true,
+            Self::Batch(msgs) => msgs.iter().any(Self::is_change),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the `Command` that results from handling the message runs in the
+    /// background, so that input carries on being handled while it runs rather than waiting for
+    /// it, such as writing a file with `:w`, counting the lines of a large file once it has been
+    /// opened or waiting for the first results of a grep.
+    #[must_use]
+    pub fn runs_in_background(&self) -> bool {
+        matches!(
+            self,
+            Self::Save
+                | Self::SaveAs(_)
+                | Self::Grep(..)
+                | Self::OpenFile(_)
+                | Self::ViewFile(_)
+                | Self::Split(_)
+        )
+    }
 }
 
 /// This trait is just a wrapper for our `Command` closer so that we can implement `std::fmt::Debug` on it.
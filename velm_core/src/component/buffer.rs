@@ -1,27 +1,65 @@
 use crate::{
-    communication::{Command, Message},
+    communication::{self, Command, Message},
     component::Component,
-    document::Document,
+    config::Config,
+    document::{Document, LineEnding, Snapshot},
+    mode::Mode,
     render::View,
-    ui::{Color, Position, Rect},
+    ui::{LineNumberStyle, Position, Rect, Theme},
+    Row,
 };
 use anyhow::Result;
+use std::cmp;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Reported when a change or save is attempted on a read-only `Document`.
+const READ_ONLY_ERROR: &str = "E45: 'readonly' option is set";
+
+/// Reported the first time a `Document` that was not valid UTF-8 is saved over its file.
+const LOSSY_SAVE_WARNING: &str =
+    "W: File was not valid UTF-8, saving replaces the invalid bytes. Save again to write anyway";
+
+/// Reported when entering a mode for typing into a read-only `Document`.
+const READ_ONLY_WARNING: &str = "W10: Warning: Changing a readonly file";
 
 pub struct Buffer {
+    /// The options that the `Buffer` was created with, as changed by `:set` since.
+    config: Config,
     cursor_position: Position,
     document: Document,
     focused: bool,
+    /// Whether the search matches are highlighted, they are hidden by `:noh` until the next search.
+    highlight_search: bool,
+    /// Whether saving a document that was not valid UTF-8 over its file has been warned about.
+    lossy_save_warned: bool,
     offset: Position,
+    register: Option<String>,
+    /// The graphemes overwritten in Replace mode, in order, so that they can be put back. `None`
+    /// marks a character that was appended to the end of a line.
+    replaced: Vec<Option<String>>,
+    search_query: Option<String>,
+    /// Every match of the search query, kept up to date as the document changes so that they can
+    /// be highlighted.
+    search_matches: Vec<Position>,
+    selection_anchor: Option<Position>,
     viewport: Rect,
 }
 
 impl Buffer {
-    pub fn new(viewport: Rect, document: Document) -> Self {
+    pub fn new(viewport: Rect, document: Document, config: &Config) -> Self {
         Self {
+            config: config.clone(),
             cursor_position: Position::default(),
             document,
             focused: false,
+            highlight_search: true,
+            lossy_save_warned: false,
             offset: Position::default(),
+            register: None,
+            replaced: Vec::new(),
+            search_query: None,
+            search_matches: Vec::new(),
+            selection_anchor: None,
             viewport,
         }
     }
@@ -33,111 +71,582 @@ impl Buffer {
             .clone()
     }
 
+    /// The position of the cursor on screen, taking the position of the viewport into account.
     pub fn cursor_position(&self) -> Position {
+        let in_view = self
+            .view_position(self.cursor_position.row, self.cursor_column())
+            .unwrap_or_default();
+
         Position::new(
-            self.cursor_position.col.saturating_sub(self.offset.col),
-            self.cursor_position.row.saturating_sub(self.offset.row),
+            self.viewport.left() + self.gutter_width() + in_view.col,
+            self.viewport.top() + in_view.row,
+        )
+    }
+
+    /// Highlight the line that the cursor is on.
+    pub fn set_cursor_line(&mut self, cursor_line: bool) {
+        self.config.cursor_line = cursor_line;
+    }
+
+    /// Show line numbers in the gutter in the given style, `None` hides the gutter.
+    pub fn set_line_numbers(&mut self, line_numbers: Option<LineNumberStyle>) {
+        self.config.line_numbers = line_numbers;
+        self.scroll();
+    }
+
+    /// The number of columns between each tab stop when rendering tabs.
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.config.tab_width = tab_width;
+        self.scroll();
+    }
+
+    /// Make Home alternate between the first non-blank character of the line and its start.
+    pub fn set_smart_home(&mut self, smart_home: bool) {
+        self.config.smart_home = smart_home;
+    }
+
+    /// Show tabs and trailing spaces as indicators. The document itself is unchanged.
+    pub fn set_list(&mut self, list: bool) {
+        self.config.list = list;
+    }
+
+    /// Keep the given number of lines in view above and below the cursor when scrolling.
+    pub fn set_scroll_off(&mut self, scroll_off: usize) {
+        self.config.scroll_off = scroll_off;
+        self.scroll();
+    }
+
+    /// Start new lines with the leading whitespace of the line they are split from.
+    pub fn set_auto_indent(&mut self, auto_indent: bool) {
+        self.config.auto_indent = auto_indent;
+    }
+
+    /// Insert spaces up to the next tab stop in place of tabs.
+    pub fn set_expand_tab(&mut self, expand_tab: bool) {
+        self.config.expand_tab = expand_tab;
+    }
+
+    /// Change the line ending that the document is written with on the next save.
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.document.set_line_ending(line_ending);
+    }
+
+    /// Wrap long lines onto the following screen rows rather than scrolling horizontally.
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.config.wrap = wrap;
+        self.offset.col = 0;
+        self.scroll();
+    }
+
+    /// The number of screen rows that the document row takes up. This is always 1 unless
+    /// wrapping, in which case a row takes up enough screen rows to hold its text and the cursor
+    /// after its last character.
+    fn screen_rows_for(&self, row: usize) -> usize {
+        if !self.config.wrap {
+            return 1;
+        }
+
+        self.document.row(row).map_or(1, |r| {
+            r.width(self.config.tab_width) / cmp::max(1, self.text_width()) + 1
+        })
+    }
+
+    /// The position, relative to the top left of the text area, that the display column of the
+    /// document row is drawn at. Returns `None` when the position is not in view.
+    fn view_position(&self, row: usize, col: usize) -> Option<Position> {
+        let width = cmp::max(1, self.text_width());
+
+        if row < self.offset.row {
+            return None;
+        }
+
+        let (view_col, view_row) = if self.config.wrap {
+            let rows_above: usize = (self.offset.row..row)
+                .map(|r| self.screen_rows_for(r))
+                .sum();
+
+            (col % width, rows_above + col / width)
+        } else {
+            (col.checked_sub(self.offset.col)?, row - self.offset.row)
+        };
+
+        if view_col < width && view_row < self.viewport.height {
+            Some(Position::new(view_col, view_row))
+        } else {
+            None
+        }
+    }
+
+    /// The document row and display column drawn at the position relative to the top left of
+    /// the text area. This is the inverse of `view_position`.
+    fn document_position_at(&self, view_position: Position) -> (usize, usize) {
+        if !self.config.wrap {
+            return (
+                self.offset.row + view_position.row,
+                self.offset.col + view_position.col,
+            );
+        }
+
+        let width = cmp::max(1, self.text_width());
+        let mut row = self.offset.row;
+        let mut view_row = view_position.row;
+
+        while row < self.document.len() && view_row >= self.screen_rows_for(row) {
+            view_row -= self.screen_rows_for(row);
+            row += 1;
+        }
+
+        (
+            row,
+            view_row * width + cmp::min(view_position.col, width - 1),
         )
     }
 
+    /// The display column of the cursor within its row, accounting for wide characters.
+    fn cursor_column(&self) -> usize {
+        let Position { col, row } = self.cursor_position;
+        self.document
+            .row(row)
+            .map_or(col, |row| row.width_to(col, self.config.tab_width))
+    }
+
+    /// The position of the cursor within the document, as a grapheme index and row.
+    pub fn document_cursor_position(&self) -> Position {
+        self.cursor_position
+    }
+
+    /// The number of lines of the document that fit in the viewport.
+    pub fn visible_lines(&self) -> usize {
+        self.viewport.height
+    }
+
+    /// The width of the line number gutter, including the space separating it from the text.
+    /// The gutter grows with the number of lines in the document.
+    fn gutter_width(&self) -> usize {
+        if self.config.line_numbers.is_none() {
+            return 0;
+        }
+
+        cmp::max(3, self.document_len().to_string().len()) + 1
+    }
+
+    /// The width of the viewport that is left for the document text.
+    fn text_width(&self) -> usize {
+        self.viewport.width.saturating_sub(self.gutter_width())
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.document.is_modified()
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.document.is_read_only()
+    }
+
+    /// Reject changes to, and saving of, the document while it is read-only.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.document.set_read_only(read_only);
+    }
+
+    /// Give the number of rows in the file of a large document that is read as it is needed.
+    pub fn set_row_count(&mut self, rows: usize) {
+        self.document.set_row_count(rows);
+    }
+
+    /// Read the rows of a large document up to the given number of rows, if they haven't been.
+    pub fn load(&mut self, rows: usize) -> Result<(), std::io::Error> {
+        self.document.load(rows)
+    }
+
+    /// Read the rest of a large document, for commands that look at every row.
+    pub fn load_all(&mut self) -> Result<(), std::io::Error> {
+        self.document.load_all()
+    }
+
+    /// The number of lines in the whole document, or only those read so far while the length of
+    /// a large file is still being counted.
     pub fn lines_in_document(&self) -> usize {
-        self.document.len()
+        self.document_len()
+    }
+
+    fn document_len(&self) -> usize {
+        self.document
+            .total_len()
+            .unwrap_or_else(|| self.document.len())
+    }
+
+    /// The number of characters in the selection, counting each line break as one character.
+    pub fn selected_char_count(&self) -> Option<usize> {
+        let (start, end) = self.selection()?;
+
+        Some(
+            self.document
+                .text_range(&start, &end)
+                .graphemes(true)
+                .count(),
+        )
+    }
+
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+
+    /// Take the unsaved changes to be written to the swap file, see `Document::swap_snapshot`.
+    pub fn swap_snapshot(&mut self) -> Result<Option<Snapshot>, std::io::Error> {
+        self.document.swap_snapshot()
+    }
+
+    pub fn remove_swap(&mut self) {
+        self.document.remove_swap();
+    }
+
+    pub fn resize(&mut self, viewport: Rect) {
+        self.viewport = viewport;
+        self.scroll();
+    }
+
+    /// The number of rows to keep in view above and below the cursor. Fewer are kept below the
+    /// cursor near the end of the document, and no more than half the viewport is given up to
+    /// them so that the cursor can still move between them.
+    fn scroll_off_rows(&self) -> (usize, usize) {
+        let row = self.cursor_position.row;
+        let scroll_off = cmp::min(
+            self.config.scroll_off,
+            self.viewport.height.saturating_sub(1) / 2,
+        );
+
+        (
+            scroll_off,
+            cmp::min(scroll_off, self.document.len().saturating_sub(row + 1)),
+        )
     }
 
+    /// Update the offset so that the cursor remains within the viewport, along with the rows
+    /// around it kept in view by `scroll_off`.
     pub fn scroll(&mut self) {
-        let Position { col, row } = self.cursor_position;
-        let width = self.viewport.width;
-        let height = self.viewport.height - 2;
+        if self.config.wrap {
+            self.scroll_wrapped();
+            return;
+        }
 
-        let offset = if row < self.offset.row {
-            (self.offset.col, row)
-        } else if col >= self.offset.col.saturating_add(height) {
+        let Position { col, row } = self.cursor_position;
+        let width = self.text_width();
+        let height = self.viewport.height;
+        let (col, col_end) = self.document.row(row).map_or((col, col + 1), |r| {
             (
-                self.offset.row,
-                col.saturating_sub(height).saturating_add(1),
+                r.width_to(col, self.config.tab_width),
+                cmp::max(
+                    r.width_to(col + 1, self.config.tab_width),
+                    r.width_to(col, self.config.tab_width) + 1,
+                ),
             )
+        });
+
+        let (above, below) = self.scroll_off_rows();
+
+        if row < self.offset.row.saturating_add(above) {
+            self.offset.row = row.saturating_sub(above);
+        } else if row.saturating_add(below) >= self.offset.row.saturating_add(height) {
+            self.offset.row = row.saturating_add(below + 1).saturating_sub(height);
+        }
+
+        if col < self.offset.col {
+            self.offset.col = col;
+        } else if col_end > self.offset.col.saturating_add(width) {
+            self.offset.col = col_end.saturating_sub(width);
+        }
+    }
+
+    /// Move the view by a row without moving the cursor, unless the cursor would leave the view
+    /// in which case it is moved to the nearest row still in view. The view can be scrolled
+    /// down until the last row of the document is at the top.
+    fn scroll_view(&mut self, down: bool) {
+        let height = self.viewport.height;
+        let scroll_off = cmp::min(self.config.scroll_off, height.saturating_sub(1) / 2);
+        let Position { col, row } = self.cursor_position;
+
+        if down {
+            self.offset.row = cmp::min(self.offset.row + 1, self.document.len().saturating_sub(1));
+        } else {
+            self.offset.row = self.offset.row.saturating_sub(1);
+        }
+
+        let top = self.offset.row + scroll_off;
+        let bottom = (self.offset.row + height)
+            .saturating_sub(scroll_off + 1)
+            .max(top);
+
+        let row = if row < top {
+            top
+        } else if row > bottom {
+            bottom
+        } else {
+            return;
+        };
+
+        self.move_cursor(Message::GotoPosition(Position::new(col, row)));
+    }
+
+    /// Update the row offset so that the screen row that the cursor is on is within the viewport
+    /// when wrapping. The first row in view is always shown from its start.
+    fn scroll_wrapped(&mut self) {
+        let row = self.cursor_position.row;
+        let height = self.viewport.height;
+        let cursor_row_in_line = self.cursor_column() / cmp::max(1, self.text_width());
+
+        let (above, below) = self.scroll_off_rows();
+
+        self.offset.col = 0;
+
+        if row < self.offset.row.saturating_add(above) {
+            self.offset.row = row.saturating_sub(above);
+            return;
+        }
+
+        // Every row takes up at least one screen row, so only the rows just above the cursor
+        // could still be in view.
+        if row.saturating_add(below) >= self.offset.row.saturating_add(height) {
+            self.offset.row = row.saturating_add(below + 1).saturating_sub(height);
+        }
+
+        // The rows kept in view below the cursor are counted as a single screen row each.
+        while self.offset.row < row
+            && (self.offset.row..row)
+                .map(|r| self.screen_rows_for(r))
+                .sum::<usize>()
+                + cursor_row_in_line
+                + below
+                >= height
+        {
+            self.offset.row += 1;
+        }
+    }
+
+    /// The selected range from the anchor to the cursor, ordered so that the start comes first.
+    /// The end is exclusive and has been moved past the character under the cursor, which is
+    /// the newline when the cursor is past the end of the row.
+    fn selection(&self) -> Option<(Position, Position)> {
+        let anchor = self.selection_anchor?;
+        let cursor = self.cursor_position;
+
+        let (start, end) = if (anchor.row, anchor.col) <= (cursor.row, cursor.col) {
+            (anchor, cursor)
         } else {
-            (self.offset.col, self.offset.row)
+            (cursor, anchor)
         };
 
-        let offset = if col < self.offset.col {
-            (col, offset.1)
-        } else if col >= self.offset.col.saturating_add(width) {
-            (col.saturating_add(width).saturating_add(1), offset.1)
+        let end = if end.col < self.document.row(end.row).map_or(0, Row::len) {
+            Position::new(end.col + 1, end.row)
         } else {
-            (self.offset.col, offset.1)
+            Position::new(0, end.row + 1)
         };
 
-        self.offset = Position::from(offset);
+        Some((start, end))
     }
 
-    fn move_cursor(&mut self, msg: Message) {
-        use crate::Row;
+    /// Move from the position by the motion n times. The motion stops early once it no longer
+    /// moves, such as at the end of the document, so that a large count returns straight away.
+    fn repeat_motion(
+        &self,
+        from: Position,
+        n: usize,
+        motion: impl Fn(&Document, &Position) -> Position,
+    ) -> Position {
+        let mut at = from;
 
-        let terminal_height = self.viewport.height - 2;
-        let Position { col, row } = self.cursor_position;
-        let height = self.document.len();
-        let width = self.document.row(row).map_or(0, Row::len);
+        for _ in 0..n {
+            let next = motion(&self.document, &at);
+            if next == at {
+                break;
+            }
 
-        let (col, row) = match msg {
-            Message::MoveCursorUp(n) => (col, row.saturating_sub(n)),
-            Message::MoveCursorDown(n) => {
-                if row < height {
-                    (col, row.saturating_add(n))
+            at = next;
+        }
+
+        at
+    }
+
+    /// The text covered by a word deletion from the cursor, from the start up to, but not
+    /// including, the end.
+    fn word_range(&self, msg: &Message) -> Option<(Position, Position)> {
+        let at = self.cursor_position;
+        let row_len = |row| self.document.row(row).map_or(0, Row::len);
+
+        let (from, to) = match *msg {
+            Message::DeleteWordForward(n) => {
+                let to = self.repeat_motion(at, n, Document::next_word_start);
+
+                // Like vim, a deletion that would end on a later line stops at the end of the
+                // line before it, unless the cursor is already past the end of its own line so
+                // that there is nothing left to delete but the line break.
+                if to.row > at.row && at.col < row_len(at.row) {
+                    (at, Position::new(row_len(to.row - 1), to.row - 1))
                 } else {
-                    (col, row)
+                    (at, to)
                 }
             }
-            Message::MoveCursorLeft(n) => {
-                if col > 0 {
-                    (col - n, row)
-                } else if row > 0 {
-                    self.document
-                        .row(row)
-                        .map_or((0, row - n), |r| (r.len(), row - n))
+            Message::DeleteWordBackward(n) => {
+                (self.repeat_motion(at, n, Document::previous_word_start), at)
+            }
+            Message::DeleteToWordEnd(n) | Message::ChangeWord(n) => {
+                let in_word = self.document.row(at.row).and_then(|row| {
+                    row.words()
+                        .into_iter()
+                        .find(|(start, end)| (*start..=*end).contains(&at.col))
+                });
+
+                let end = match (msg, in_word) {
+                    (Message::ChangeWord(_), Some((_, end))) => self.repeat_motion(
+                        Position::new(end, at.row),
+                        n.saturating_sub(1),
+                        Document::word_end,
+                    ),
+                    _ => self.repeat_motion(at, n, Document::word_end),
+                };
+                (
+                    at,
+                    Position::new(cmp::min(end.col + 1, row_len(end.row)), end.row),
+                )
+            }
+            Message::DeleteAWord(n) => {
+                let row = self.document.row(at.row)?;
+                let words = row.words();
+                let first = words.iter().position(|(_, end)| *end >= at.col)?;
+                let on_word = words[first].0 <= at.col;
+                let (_, end) = words[cmp::min(first.saturating_add(n), words.len()) - 1];
+
+                // The whitespace after the words is deleted along with them, or the whitespace
+                // before them when there is none after. Starting on whitespace deletes it along
+                // with the words that follow.
+                let trailing_end = words
+                    .get(first.saturating_add(n))
+                    .map_or_else(|| row.len(), |(start, _)| *start);
+
+                if !on_word {
+                    (at, Position::new(end + 1, at.row))
+                } else if trailing_end > end + 1 {
+                    (
+                        Position::new(words[first].0, at.row),
+                        Position::new(trailing_end, at.row),
+                    )
                 } else {
-                    (col, row)
+                    let start = first
+                        .checked_sub(1)
+                        .map_or(0, |previous| words[previous].1 + 1);
+                    (Position::new(start, at.row), Position::new(end + 1, at.row))
                 }
             }
-            Message::MoveCursorRight(n) => {
-                if col < width {
-                    (col + n, row)
-                } else if row < height {
-                    (0, row + n)
-                } else {
-                    (col, row)
+            _ => return None,
+        };
+
+        if (from.row, from.col) < (to.row, to.col) {
+            Some((from, to))
+        } else {
+            None
+        }
+    }
+
+    /// Insert the text after the cursor as a single undo step, leaving the cursor on the last
+    /// inserted character.
+    fn paste(&mut self, text: &str) -> Result<()> {
+        let Position { col, row } = self.cursor_position;
+        let width = self.document.row(row).map_or(0, Row::len);
+
+        let at = self.insert_text(Position::new(cmp::min(col + 1, width), row), text)?;
+
+        self.move_cursor(Message::GotoPosition(Position::new(
+            at.col.saturating_sub(1),
+            at.row,
+        )));
+
+        Ok(())
+    }
+
+    /// Insert the text at the given position as a single undoable change, starting a new row at
+    /// each newline. The position just after the inserted text is returned.
+    fn insert_text(&mut self, at: Position, text: &str) -> Result<Position> {
+        use anyhow::Context;
+
+        self.document
+            .insert_str(&at, text)
+            .context("unable to paste text in document")
+    }
+
+    /// Make the change to the document that the message describes, see `Message::is_change`.
+    fn edit(&mut self, msg: Message) -> Result<()> {
+        match msg {
+            Message::InsertChar(_)
+            | Message::InsertLineBreak
+            | Message::OpenLineBelow
+            | Message::OpenLineAbove
+            | Message::InsertText(_)
+            | Message::InsertTab
+            | Message::Paste => return self.insert(msg),
+            Message::ReplaceChar(ch) => {
+                let replaced = self
+                    .document
+                    .replace(&self.cursor_position, &ch.to_string());
+
+                self.replaced.push(replaced);
+                self.move_cursor(Message::MoveCursorRight(1));
+            }
+            Message::RestoreReplacedChar => {
+                let Position { col, row } = self.cursor_position;
+
+                if col > 0 {
+                    let at = Position::new(col - 1, row);
+
+                    // Backspace only moves the cursor back over text that wasn't replaced.
+                    match self.replaced.pop() {
+                        Some(Some(original)) => {
+                            self.document.replace(&at, &original);
+                        }
+                        Some(None) => self.document.delete(&at),
+                        None => (),
+                    }
+
+                    self.move_cursor(Message::GotoPosition(at));
                 }
             }
-            Message::MoveCursorPageUp => {
-                if row > terminal_height {
-                    (col, row - terminal_height)
-                } else {
-                    (col, 0)
+            Message::JoinLines(n) => {
+                let row = self.cursor_position.row;
+
+                self.document.begin_undo_group();
+                for _ in 0..n {
+                    match self.document.join_line(row) {
+                        Some(at) => self.move_cursor(Message::GotoPosition(at)),
+                        None => break,
+                    }
                 }
+                self.document.end_undo_group();
             }
-            Message::MoveCursorPageDown => {
-                if row.saturating_add(terminal_height) < height {
-                    (col, row + terminal_height)
-                } else {
-                    (col, height)
+            Message::ToggleCase(n) => {
+                let Position { col, row } = self.cursor_position;
+                let toggled = self.document.toggle_case(&self.cursor_position, n);
+
+                if toggled > 0 {
+                    self.move_cursor(Message::GotoPosition(Position::new(col + toggled, row)));
                 }
             }
-            Message::MoveCursorLineStart => (0, row),
-            Message::MoveCursorLineEnd => (width, row),
-            _ => (col, row),
-        };
+            Message::IndentLine(n) | Message::DedentLine(n) => {
+                let row = self.cursor_position.row;
 
-        let new_width = self.document.row(row).map_or(0, Row::len);
+                if let Message::IndentLine(_) = msg {
+                    self.document.indent(row, n, self.config.tab_width);
+                } else {
+                    self.document.dedent(row, n, self.config.tab_width);
+                }
 
-        self.cursor_position = Position {
-            col: if col > new_width { new_width } else { col },
-            row,
-        };
+                self.move_cursor(Message::MoveCursorLineFirstNonBlank);
+            }
+            _ => self.delete(&msg),
+        }
+
+        Ok(())
     }
-}
 
-impl Component for Buffer {
-    fn update(&mut self, msg: Message) -> Result<Option<Command>> {
+    /// Insert the text that the message describes at the cursor.
+    fn insert(&mut self, msg: Message) -> Result<()> {
         use anyhow::Context;
 
         match msg {
@@ -149,52 +658,1635 @@ impl Component for Buffer {
                 self.move_cursor(Message::MoveCursorRight(1));
             }
             Message::InsertLineBreak => {
+                let Position { row, .. } = self.cursor_position;
+                let indent = match self.document.row(row) {
+                    Some(r) if self.config.auto_indent => r.slice(0, r.first_non_blank()),
+                    _ => String::new(),
+                };
+
                 self.document.insert_newline(&self.cursor_position);
-                self.move_cursor(Message::MoveCursorDown(1));
-                self.move_cursor(Message::MoveCursorLineStart);
+
+                let mut at = Position::new(0, row + 1);
+                if !indent.is_empty() {
+                    at = self
+                        .document
+                        .insert_str(&at, &indent)
+                        .context("unable to indent new line in document")?;
+                }
+
+                self.move_cursor(Message::GotoPosition(at));
             }
-            Message::DeleteCharForward => self.document.delete(&self.cursor_position),
-            Message::DeleteCharBackward => {
-                if self.cursor_position.col > 0 || self.cursor_position.row > 0 {
-                    self.move_cursor(Message::MoveCursorLeft(1));
-                    self.document.delete(&self.cursor_position);
+            Message::OpenLineBelow => {
+                let row = self.cursor_position.row;
+                let end_of_row = self.document.row(row).map_or(0, Row::len);
+
+                self.document
+                    .insert_newline(&Position::new(end_of_row, row));
+                self.move_cursor(Message::GotoPosition(Position::new(0, row + 1)));
+            }
+            Message::OpenLineAbove => {
+                let row = self.cursor_position.row;
+
+                self.document.insert_newline(&Position::new(0, row));
+                self.move_cursor(Message::GotoPosition(Position::new(0, row)));
+            }
+            Message::Paste => {
+                if let Some(text) = self.register.clone() {
+                    self.paste(&text)?;
                 }
             }
+            Message::InsertText(text) => {
+                let at = self.insert_text(self.cursor_position, &text)?;
 
-            Message::Save => self
-                .document
-                .save(None)
-                .context("unable to save document")?,
-            Message::SaveAs(filename) => self
-                .document
-                .save(Some(&filename))
-                .context("unable to save document")?,
-            _ => {
-                self.move_cursor(msg);
+                self.move_cursor(Message::GotoPosition(at));
             }
-        };
+            Message::InsertTab if self.config.expand_tab => {
+                let col = self
+                    .document
+                    .row(self.cursor_position.row)
+                    .map_or(0, |row| {
+                        row.width_to(self.cursor_position.col, self.config.tab_width)
+                    });
+                let tab_width = self.config.tab_width.max(1);
+                let spaces = " ".repeat(tab_width - col % tab_width);
+                let at = self.insert_text(self.cursor_position, &spaces)?;
 
-        self.scroll();
+                self.move_cursor(Message::GotoPosition(at));
+            }
+            Message::InsertTab => {
+                self.document
+                    .insert(&self.cursor_position, '\t')
+                    .context("unable to insert tab in document")?;
 
-        Ok(None)
+                self.move_cursor(Message::MoveCursorRight(1));
+            }
+            _ => (),
+        }
+
+        Ok(())
     }
-}
 
-impl View for Buffer {
-    fn render_to(&self, frame: &mut crate::render::Frame) {
-        if self.focused {
-            frame.set_cursor_position(self.cursor_position);
-        }
+    /// Delete the text that the message describes, keeping it in the register when it is
+    /// changed rather than just deleted.
+    fn delete(&mut self, msg: &Message) {
+        match *msg {
+            Message::DeleteCharForward => self.document.delete(&self.cursor_position),
+            // Unlike `DeleteCharForward` and `DeleteCharBackward`, these never join rows.
+            Message::DeleteCharsUnderCursor(n) => {
+                let Position { col, row } = self.cursor_position;
+                let end = cmp::min(
+                    col.saturating_add(n),
+                    self.document.row(row).map_or(0, Row::len),
+                );
 
-        for row_in_view in 0..self.viewport.height {
-            if let Some(row) = self.document.row(row_in_view as usize + self.offset.row) {
-                let start = self.offset.col;
-                let end = self.offset.col + self.viewport.width;
-                let row = row.to_string(start, end);
-                frame.write_line(row_in_view, &row, Color::default(), Color::default());
-            } else {
-                frame.write_line(row_in_view, "~", Color::Gray, Color::default());
+                if col < end {
+                    self.document
+                        .delete_range(&self.cursor_position, &Position::new(end, row));
+                }
             }
-        }
+            Message::DeleteCharsBeforeCursor(n) => {
+                let Position { col, row } = self.cursor_position;
+                let start = Position::new(col.saturating_sub(n), row);
+
+                if start.col < col {
+                    let at = self.document.delete_range(&start, &self.cursor_position);
+                    self.move_cursor(Message::GotoPosition(at));
+                }
+            }
+            Message::DeleteCharBackward
+                if self.cursor_position.col > 0 || self.cursor_position.row > 0 =>
+            {
+                let Position { col, row } = self.cursor_position;
+                let previous = if col > 0 {
+                    Position::new(col - 1, row)
+                } else {
+                    Position::new(self.document.row(row - 1).map_or(0, Row::len), row - 1)
+                };
+                self.move_cursor(Message::GotoPosition(previous));
+                self.document.delete(&self.cursor_position);
+            }
+            Message::DeleteSelection => {
+                if let Some((start, end)) = self.selection() {
+                    self.register = Some(self.document.text_range(&start, &end));
+                    let at = self.document.delete_range(&start, &end);
+                    self.move_cursor(Message::GotoPosition(at));
+                }
+            }
+            Message::DeleteWordForward(_)
+            | Message::DeleteWordBackward(_)
+            | Message::DeleteToWordEnd(_)
+            | Message::DeleteAWord(_)
+            | Message::ChangeWord(_) => {
+                if let Some((start, end)) = self.word_range(msg) {
+                    self.register = Some(self.document.text_range(&start, &end));
+                    let at = self.document.delete_range(&start, &end);
+                    self.move_cursor(Message::GotoPosition(at));
+                }
+            }
+            Message::ChangeLine(n) => {
+                let row = self.cursor_position.row;
+                let last_row = cmp::min(
+                    row.saturating_add(n.max(1) - 1),
+                    self.document.len().saturating_sub(1),
+                );
+                let indent = match self.document.row(row) {
+                    Some(r) if self.config.auto_indent => r.first_non_blank(),
+                    _ => 0,
+                };
+                let start = Position::new(indent, row);
+                let end = Position::new(self.document.row(last_row).map_or(0, Row::len), last_row);
+
+                self.register = Some(self.document.text_range(&start, &end));
+                let at = self.document.delete_range(&start, &end);
+                self.move_cursor(Message::GotoPosition(at));
+            }
+            Message::ChangeToLineEnd => {
+                let Position { row, .. } = self.cursor_position;
+                let end = Position::new(self.document.row(row).map_or(0, Row::len), row);
+
+                self.register = Some(self.document.text_range(&self.cursor_position, &end));
+                self.document.delete_range(&self.cursor_position, &end);
+            }
+            _ => (),
+        }
+    }
+
+    /// Start or end the undo group and selection for the mode. Entering a mode for typing into a
+    /// read-only document is warned about.
+    fn enter_mode(&mut self, mode: &Mode) -> Option<Command> {
+        self.selection_anchor = None;
+        self.replaced.clear();
+
+        match mode {
+            Mode::Insert(_) | Mode::Replace(_) => {
+                self.document.begin_undo_group();
+
+                if self.document.is_read_only() {
+                    return Some(communication::wrap(Message::StatusError(
+                        READ_ONLY_WARNING.into(),
+                    )));
+                }
+            }
+            Mode::Visual(_) => {
+                self.document.end_undo_group();
+                self.selection_anchor = Some(self.cursor_position);
+            }
+            _ => self.document.end_undo_group(),
+        }
+
+        None
+    }
+
+    /// Move the cursor to the character that was clicked on, if it is within the viewport.
+    fn click(&mut self, position: Position) {
+        if self.viewport.contains(&position) {
+            let (row, col) = self.document_position_at(Position::new(
+                (position.col - self.viewport.left()).saturating_sub(self.gutter_width()),
+                position.row - self.viewport.top(),
+            ));
+            let col = self
+                .document
+                .row(row)
+                .map_or(col, |r| r.index_at_width(col, self.config.tab_width));
+
+            self.move_cursor(Message::GotoPosition(Position::new(col, row)));
+        }
+    }
+
+    /// Scroll the view by half a screen. The cursor moves along with it so that it stays on the
+    /// same screen row, unless the view can't move any further.
+    fn scroll_half_page(&mut self, down: bool) {
+        let half = cmp::max(1, self.viewport.height / 2);
+        let Position { col, row } = self.cursor_position;
+
+        if down {
+            let last_offset = self.document.len().saturating_sub(self.viewport.height);
+
+            self.offset.row = cmp::max(
+                self.offset.row,
+                cmp::min(self.offset.row + half, last_offset),
+            );
+            self.move_cursor(Message::GotoPosition(Position::new(col, row + half)));
+        } else {
+            self.offset.row = self.offset.row.saturating_sub(half);
+            self.move_cursor(Message::GotoPosition(Position::new(
+                col,
+                row.saturating_sub(half),
+            )));
+        }
+    }
+
+    /// Save the document, or record that it has been saved once the file has been written.
+    fn save_message(&mut self, msg: Message) -> Result<Option<Command>> {
+        match msg {
+            Message::Save => self.save(None, false),
+            Message::SaveAs(filename) => self.save(Some(&filename), false),
+            // Quitting waits for the file to be written, a failed save reports the error instead
+            // so that the changes aren't lost. Quit still refuses while other buffers have
+            // unsaved changes.
+            Message::SaveQuit {
+                file_name,
+                only_if_modified,
+            } => {
+                if !only_if_modified || file_name.is_some() || self.document.is_modified() {
+                    return self.save(file_name.as_deref(), true);
+                }
+
+                Ok(Some(communication::wrap(Message::Quit)))
+            }
+            // The document may have been saved while the swap file was being written.
+            Message::SwapWritten(_) => {
+                if !self.document.is_modified() {
+                    self.document.remove_swap();
+                }
+
+                Ok(None)
+            }
+            Message::Saved {
+                file_name,
+                lines,
+                revision,
+                ..
+            } => {
+                self.document.mark_saved(revision);
+
+                Ok(Some(communication::wrap(Message::Flash(format!(
+                    "\"{file_name}\" {lines}L written"
+                )))))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns `true` if handling the message would change the document or write it to disk.
+    /// Quitting with `:x` only writes when there are changes to save.
+    fn writes_document(&self, msg: &Message) -> bool {
+        match msg {
+            Message::Save | Message::SaveAs(_) | Message::Recover => true,
+            Message::SaveQuit {
+                file_name,
+                only_if_modified,
+            } => !only_if_modified || file_name.is_some() || self.document.is_modified(),
+            _ => msg.is_change(),
+        }
+    }
+
+    /// The number of rows of a large document that need to have been read before the message is
+    /// handled, or `None` if it needs the whole document, such as searches and jumps to the end.
+    /// Edits and moving around near the cursor only need the rows that they can reach.
+    fn rows_needed(&self, msg: &Message) -> Option<usize> {
+        let row = self.cursor_position.row;
+
+        match msg {
+            Message::Search(_)
+            | Message::SearchNext
+            | Message::SearchPrevious
+            | Message::GlobalSearch(_)
+            | Message::WordCount
+            | Message::Save
+            | Message::SaveAs(_)
+            | Message::SaveQuit { .. }
+            | Message::Recover
+            | Message::MoveCursorDocumentEnd => None,
+            Message::GoToLine(line) => Some(*line),
+            Message::GotoPosition(position) => Some(position.row.saturating_add(1)),
+            Message::MoveCursorDown(n)
+            | Message::MoveCursorWordForward(n)
+            | Message::MoveCursorWordEnd(n) => Some(row.saturating_add(*n)),
+            Message::MoveCursorPageDown | Message::ScrollHalfPageDown | Message::ScrollLineDown => {
+                Some(row.saturating_add(self.viewport.height))
+            }
+            Message::JoinLines(n)
+            | Message::ChangeLine(n)
+            | Message::IndentLine(n)
+            | Message::DedentLine(n)
+            | Message::DeleteWordForward(n)
+            | Message::DeleteToWordEnd(n)
+            | Message::DeleteAWord(n) => Some(row.saturating_add(*n).saturating_add(1)),
+            // Other edits are made on the cursor row, though they may join the next row onto it.
+            _ if msg.is_change() => Some(row.saturating_add(2)),
+            _ => Some(row),
+        }
+    }
+
+    /// Save the document, and then quit if asked to. The file is written by the returned
+    /// `Command` so that the `Editor` isn't held up by a slow file system, and it reports back
+    /// with `Message::Saved`, or the error if the file could not be written.
+    fn save(&mut self, file_name: Option<&str>, quit: bool) -> Result<Option<Command>> {
+        use anyhow::Context;
+
+        // Writing back a file that was not valid UTF-8 loses the bytes that were replaced, so the
+        // first attempt fails with a warning. Quitting with `:wq` is refused along with it.
+        let overwrites = file_name.is_none_or(|file_name| {
+            self.document.file_name().map(String::as_str) == Some(file_name)
+        });
+        if overwrites && self.document.is_lossy() && !self.lossy_save_warned {
+            self.lossy_save_warned = true;
+            anyhow::bail!(LOSSY_SAVE_WARNING);
+        }
+
+        let snapshot = self
+            .document
+            .snapshot(file_name)
+            .context("unable to save document")?;
+
+        let snapshot = match snapshot {
+            Some(snapshot) => snapshot,
+            None if quit => return Ok(Some(communication::wrap(Message::Quit))),
+            None => return Ok(None),
+        };
+
+        let write: Command = Box::new(move || match snapshot.write() {
+            Ok(()) => {
+                let saved = Message::Saved {
+                    document: snapshot.document(),
+                    file_name: snapshot.file_name().into(),
+                    lines: snapshot.lines(),
+                    revision: snapshot.revision(),
+                };
+
+                if quit {
+                    Message::Batch(vec![saved, Message::Quit])
+                } else {
+                    saved
+                }
+            }
+            Err(e) => Message::StatusError(format!(
+                "{:#}",
+                anyhow::Error::new(e).context("unable to save document")
+            )),
+        });
+
+        Ok(Some(write))
+    }
+
+    /// Find every match of the search query again, such as after the document has changed.
+    fn refresh_search_matches(&mut self) {
+        self.search_matches = match &self.search_query {
+            Some(query) if self.highlight_search => self.document.find_all(query),
+            _ => Vec::new(),
+        };
+    }
+
+    /// Move the cursor to the next, or previous, match of the last search query. If there is no
+    /// match then a `Command` to report it is returned.
+    fn search(&mut self, forward: bool) -> Option<Command> {
+        let Some(query) = self.search_query.clone() else {
+            return Some(communication::wrap(Message::Flash(
+                "No previous search pattern".into(),
+            )));
+        };
+
+        let found = if forward {
+            self.document.find(&query, &self.cursor_position)
+        } else {
+            self.document.rfind(&query, &self.cursor_position)
+        };
+
+        if let Some(position) = found {
+            self.move_cursor(Message::GotoPosition(position));
+            self.scroll();
+            return None;
+        }
+
+        Some(communication::wrap(Message::Flash(format!(
+            "Pattern not found: {query}"
+        ))))
+    }
+
+    fn move_cursor(&mut self, msg: Message) {
+        let terminal_height = self.viewport.height;
+        let Position { col, row } = self.cursor_position;
+        let height = self.document.len();
+        let width = self.document.row(row).map_or(0, Row::len);
+
+        let (col, row) = match msg {
+            Message::MoveCursorUp(n) => (col, row.saturating_sub(n)),
+            Message::MoveCursorDown(n) => {
+                if row < height {
+                    (col, row.saturating_add(n))
+                } else {
+                    (col, row)
+                }
+            }
+            Message::MoveCursorLeft(n) => (col.saturating_sub(n), row),
+            Message::MoveCursorRight(n) => {
+                if col < width {
+                    (col.saturating_add(n), row)
+                } else if row < height {
+                    (0, row.saturating_add(n))
+                } else {
+                    (col, row)
+                }
+            }
+            Message::MoveCursorPageUp => {
+                if row > terminal_height {
+                    (col, row - terminal_height)
+                } else {
+                    (col, 0)
+                }
+            }
+            Message::MoveCursorPageDown => {
+                if row.saturating_add(terminal_height) < height {
+                    (col, row + terminal_height)
+                } else {
+                    (col, height.saturating_sub(1))
+                }
+            }
+            Message::MoveCursorDocumentStart => (0, 0),
+            Message::MoveCursorDocumentEnd => {
+                let last = height.saturating_sub(1);
+                (self.document.row(last).map_or(0, Row::len), last)
+            }
+            Message::MoveCursorAfterChar => (cmp::min(col + 1, width), row),
+            Message::MoveCursorLineStart => (0, row),
+            Message::MoveCursorHome => {
+                let first_non_blank = self.document.row(row).map_or(0, Row::first_non_blank);
+
+                if self.config.smart_home && col != first_non_blank {
+                    (first_non_blank, row)
+                } else {
+                    (0, row)
+                }
+            }
+            Message::MoveCursorLineFirstNonBlank => {
+                (self.document.row(row).map_or(0, Row::first_non_blank), row)
+            }
+            Message::MoveCursorLineEnd => (width, row),
+            Message::MoveCursorWordForward(n) => {
+                let position =
+                    self.repeat_motion(self.cursor_position, n, Document::next_word_start);
+                (position.col, position.row)
+            }
+            Message::MoveCursorWordBackward(n) => {
+                let position =
+                    self.repeat_motion(self.cursor_position, n, Document::previous_word_start);
+                (position.col, position.row)
+            }
+            Message::MoveCursorWordEnd(n) => {
+                let position = self.repeat_motion(self.cursor_position, n, Document::word_end);
+                (position.col, position.row)
+            }
+            Message::GoToLine(line) => (
+                0,
+                cmp::min(line.saturating_sub(1), height.saturating_sub(1)),
+            ),
+            Message::GotoPosition(position) => (
+                position.col,
+                cmp::min(position.row, height.saturating_sub(1)),
+            ),
+            _ => (col, row),
+        };
+
+        let new_width = self.document.row(row).map_or(0, Row::len);
+
+        self.cursor_position = Position {
+            col: if col > new_width { new_width } else { col },
+            row,
+        };
+    }
+
+    /// Render the rows of the document that are in view with their line numbers, or a `~` for
+    /// each screen row past the end of the document.
+    fn render_rows(&self, frame: &mut crate::render::Frame, theme: &Theme) {
+        let gutter_width = self.gutter_width();
+        let left = self.viewport.left();
+        let top = self.viewport.top();
+
+        let text_width = self.text_width();
+        let mut idx = self.offset.row;
+        let mut row_in_line = 0;
+
+        for row_in_view in 0..self.viewport.height {
+            if let Some(row) = self.document.row(idx) {
+                let start = if self.config.wrap {
+                    row_in_line * text_width
+                } else {
+                    self.offset.col
+                };
+                let end = start + text_width;
+
+                // Only the first screen row of a wrapped line is numbered.
+                let gutter = self.config.line_numbers.map_or(String::new(), |style| {
+                    if row_in_line == 0 {
+                        format!(
+                            "{:>width$} ",
+                            style.number_for(idx, self.cursor_position.row),
+                            width = gutter_width - 1
+                        )
+                    } else {
+                        " ".repeat(gutter_width)
+                    }
+                });
+                let text = if self.config.list {
+                    row.to_list_string(start, end, self.config.tab_width)
+                } else {
+                    row.to_string(start, end, self.config.tab_width)
+                };
+
+                // The view can be taller than the frame while a resize is being caught up with,
+                // the rows below the frame are skipped.
+                let written = frame.write_line(
+                    top + row_in_view,
+                    &format!("{gutter}{text}"),
+                    theme.foreground,
+                    theme.background,
+                );
+                if written.is_err() {
+                    break;
+                }
+
+                if self.config.list {
+                    for col in row.whitespace_columns(self.config.tab_width) {
+                        if (start..end).contains(&col) {
+                            frame.set_foreground(
+                                &Position::new(
+                                    left + gutter_width + col - start,
+                                    top + row_in_view,
+                                ),
+                                theme.whitespace,
+                            );
+                        }
+                    }
+                }
+
+                for col in 0..gutter_width {
+                    frame.set_foreground(
+                        &Position::new(left + col, top + row_in_view),
+                        theme.line_number,
+                    );
+                }
+
+                // The selection is highlighted afterwards so that it takes precedence.
+                if self.config.cursor_line && idx == self.cursor_position.row {
+                    for col in gutter_width..self.viewport.width {
+                        frame.set_background(
+                            &Position::new(left + col, top + row_in_view),
+                            theme.current_line,
+                        );
+                    }
+                }
+
+                row_in_line += 1;
+                if row_in_line >= self.screen_rows_for(idx) {
+                    idx += 1;
+                    row_in_line = 0;
+                }
+            } else if frame
+                .write_line(top + row_in_view, "~", theme.tilde, theme.background)
+                .is_err()
+            {
+                break;
+            }
+        }
+    }
+}
+
+impl Component for Buffer {
+    fn update(&mut self, msg: Message) -> Result<Option<Command>> {
+        use anyhow::Context;
+
+        if self.document.is_read_only() && self.writes_document(&msg) {
+            return Ok(Some(communication::wrap(Message::StatusError(
+                READ_ONLY_ERROR.into(),
+            ))));
+        }
+
+        if !self.document.is_loaded() {
+            // Read a screen beyond the rows that are needed, so that the view can be filled.
+            let ahead = 2 * self.viewport.height;
+            let loaded = match self.rows_needed(&msg) {
+                Some(rows) => self.document.load(rows.saturating_add(ahead)),
+                None => self.document.load_all(),
+            };
+            loaded.context("unable to read the rest of the document")?;
+        }
+
+        // Changes fall through to the end of the match, where the search matches are found again.
+        let changes_document =
+            msg.is_change() || matches!(msg, Message::Undo | Message::Redo | Message::Recover);
+
+        match msg {
+            msg if msg.is_change() => self.edit(msg)?,
+            Message::Undo => {
+                if let Some(position) = self.document.undo() {
+                    self.move_cursor(Message::GotoPosition(position));
+                }
+            }
+            Message::Redo => {
+                if let Some(position) = self.document.redo() {
+                    self.move_cursor(Message::GotoPosition(position));
+                }
+            }
+            Message::EnterMode(mode) => return Ok(self.enter_mode(&mode)),
+            Message::YankSelection => {
+                if let Some((start, end)) = self.selection() {
+                    self.register = Some(self.document.text_range(&start, &end));
+                    self.move_cursor(Message::GotoPosition(start));
+                }
+            }
+            Message::MouseClick(position) => self.click(position),
+            Message::Search(query) => {
+                self.search_query = Some(query);
+                self.highlight_search = true;
+                self.refresh_search_matches();
+                return Ok(self.search(true));
+            }
+            Message::ClearSearchHighlight => {
+                self.highlight_search = false;
+                self.refresh_search_matches();
+            }
+            Message::WordCount => {
+                let count = self.document.word_count();
+
+                return Ok(Some(communication::wrap(Message::Flash(format!(
+                    "{} words, {} lines, {} characters",
+                    count.words, count.lines, count.chars
+                )))));
+            }
+            Message::SearchNext | Message::SearchPrevious => {
+                self.highlight_search = true;
+                self.refresh_search_matches();
+                return Ok(self.search(msg == Message::SearchNext));
+            }
+            Message::GotoLastEdit => {
+                if let Some(position) = self.document.last_edit() {
+                    self.move_cursor(Message::GotoPosition(position));
+                }
+            }
+            Message::Save
+            | Message::SaveAs(_)
+            | Message::SaveQuit { .. }
+            | Message::SwapWritten(_)
+            | Message::Saved { .. } => return self.save_message(msg),
+            Message::Recover => {
+                self.document
+                    .recover()
+                    .context("unable to recover document")?;
+
+                self.move_cursor(Message::MoveCursorDocumentStart);
+            }
+            Message::ScrollHalfPageDown => self.scroll_half_page(true),
+            Message::ScrollHalfPageUp => self.scroll_half_page(false),
+            Message::ScrollLineDown => self.scroll_view(true),
+            Message::ScrollLineUp => self.scroll_view(false),
+            _ => {
+                self.move_cursor(msg);
+            }
+        };
+
+        if changes_document {
+            self.refresh_search_matches();
+        }
+
+        self.scroll();
+
+        Ok(None)
+    }
+}
+
+impl View for Buffer {
+    fn render_to(&self, frame: &mut crate::render::Frame, theme: &Theme) {
+        if self.focused {
+            frame.set_cursor_position(self.cursor_position());
+        }
+
+        let gutter_width = self.gutter_width();
+        let left = self.viewport.left();
+        let top = self.viewport.top();
+
+        self.render_rows(frame, theme);
+
+        if let Some(matching) = self.document.matching_bracket(&self.cursor_position) {
+            for position in &[self.cursor_position, matching] {
+                let col = self
+                    .document
+                    .row(position.row)
+                    .map_or(0, |r| r.width_to(position.col, self.config.tab_width));
+
+                if let Some(in_view) = self.view_position(position.row, col) {
+                    frame.set_background(
+                        &Position::new(left + gutter_width + in_view.col, top + in_view.row),
+                        theme.matching_bracket,
+                    );
+                }
+            }
+        }
+
+        // Search matches are highlighted before the selection so that the selection takes
+        // precedence.
+        let match_len = self
+            .search_query
+            .as_ref()
+            .map_or(0, |query| query.graphemes(true).count());
+        let last_row_in_view = self.offset.row + self.viewport.height;
+
+        for position in self
+            .search_matches
+            .iter()
+            .filter(|position| (self.offset.row..=last_row_in_view).contains(&position.row))
+        {
+            if let Some(row) = self.document.row(position.row) {
+                let first = row.width_to(position.col, self.config.tab_width);
+                let last = row.width_to(position.col + match_len, self.config.tab_width);
+
+                for col in first..last {
+                    if let Some(in_view) = self.view_position(position.row, col) {
+                        let at =
+                            Position::new(left + gutter_width + in_view.col, top + in_view.row);
+                        frame.set_foreground(&at, theme.search_match_fg);
+                        frame.set_background(&at, theme.search_match_bg);
+                    }
+                }
+            }
+        }
+
+        if let Some((start, end)) = self.selection() {
+            for row in cmp::max(start.row, self.offset.row)..=cmp::min(end.row, last_row_in_view) {
+                let document_row = self.document.row(row);
+                let width_to = |idx| {
+                    document_row.map_or(idx, |r: &Row| r.width_to(idx, self.config.tab_width))
+                };
+
+                let first = if row == start.row {
+                    width_to(start.col)
+                } else {
+                    0
+                };
+                // Selections that continue past the end of a row include its newline.
+                let last = if row == end.row {
+                    width_to(end.col)
+                } else {
+                    document_row.map_or(0, |r| r.width(self.config.tab_width)) + 1
+                };
+
+                for col in first..last {
+                    if let Some(in_view) = self.view_position(row, col) {
+                        frame.set_background(
+                            &Position::new(left + gutter_width + in_view.col, top + in_view.row),
+                            theme.selection,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Buffer, LOSSY_SAVE_WARNING, READ_ONLY_ERROR};
+    use crate::communication::Message;
+    use crate::component::Component;
+    use crate::config::Config;
+    use crate::document::Document;
+    use crate::mode::{Mode, Replace, Visual};
+    use crate::render::Viewport;
+    use crate::testing::{TempFile, TestCanvas};
+    use crate::ui::{Color, LineNumberStyle, Position, Rect, Theme};
+
+    fn buffer_with_line(viewport: Rect, line: &str) -> Buffer {
+        let mut document = Document::default();
+        for (col, ch) in line.chars().enumerate() {
+            document.insert(&Position::new(col, 0), ch).unwrap();
+        }
+
+        Buffer::new(
+            viewport,
+            document,
+            &Config {
+                line_numbers: None,
+                ..Config::default()
+            },
+        )
+    }
+
+    #[test]
+    fn scroll_keeps_cursor_visible_when_moving_right_past_the_edge() {
+        let mut buffer = buffer_with_line(Rect::new(5, 10), "Hello World!");
+
+        buffer.update(Message::MoveCursorRight(4)).unwrap();
+        assert_eq!(Position::new(4, 0), buffer.cursor_position());
+
+        buffer.update(Message::MoveCursorRight(3)).unwrap();
+        assert_eq!(Position::new(4, 0), buffer.cursor_position());
+        assert_eq!(3, buffer.offset.col);
+
+        buffer.update(Message::MoveCursorLineEnd).unwrap();
+        assert_eq!(Position::new(4, 0), buffer.cursor_position());
+        assert_eq!(8, buffer.offset.col);
+    }
+
+    #[test]
+    fn wrapped_lines_continue_on_the_next_screen_row() {
+        let mut buffer = buffer_with_line(Rect::new(5, 10), "Hello World!");
+        buffer.set_wrap(true);
+
+        buffer.update(Message::MoveCursorLineEnd).unwrap();
+        assert_eq!(Position::new(2, 2), buffer.cursor_position());
+        assert_eq!(0, buffer.offset.col);
+
+        buffer
+            .update(Message::MouseClick(Position::new(1, 1)))
+            .unwrap();
+        assert_eq!(Position::new(6, 0), buffer.cursor_position);
+    }
+
+    #[test]
+    fn wrapped_lines_scroll_by_whole_document_rows() {
+        let mut buffer = buffer_with_line(Rect::new(5, 3), "Hello World!");
+        buffer.set_wrap(true);
+        buffer.update(Message::MoveCursorLineEnd).unwrap();
+        buffer.update(Message::InsertLineBreak).unwrap();
+
+        assert_eq!(1, buffer.offset.row);
+        assert_eq!(Position::new(0, 0), buffer.cursor_position());
+    }
+
+    #[test]
+    fn current_line_is_highlighted_beneath_the_selection() {
+        let mut buffer = buffer_with_line(Rect::new(5, 2), "Hello");
+        buffer.set_cursor_line(true);
+        buffer
+            .update(Message::EnterMode(Mode::Visual(Visual::default())))
+            .unwrap();
+        buffer.update(Message::MoveCursorRight(1)).unwrap();
+
+        let theme = Theme::default();
+        let mut canvas = TestCanvas::new(5, 2);
+        {
+            let mut viewport = Viewport::new(&mut canvas).unwrap();
+            viewport.render(&buffer, &theme).unwrap();
+        }
+
+        let background = |col, row| canvas.cell(col, row).unwrap().background();
+        assert_eq!(theme.selection, background(0, 0));
+        assert_eq!(theme.selection, background(1, 0));
+        assert_eq!(theme.current_line, background(2, 0));
+        assert_eq!(Color::Reset, background(2, 1));
+    }
+
+    #[test]
+    fn every_search_match_is_highlighted_as_the_document_changes() {
+        let mut buffer = buffer_with_line(Rect::new(10, 1), "Hello");
+        buffer.update(Message::Search("l".into())).unwrap();
+        buffer.update(Message::MoveCursorLineStart).unwrap();
+        buffer.update(Message::InsertChar('l')).unwrap();
+
+        let theme = Theme::default();
+        let mut canvas = TestCanvas::new(10, 1);
+        {
+            let mut viewport = Viewport::new(&mut canvas).unwrap();
+            viewport.render(&buffer, &theme).unwrap();
+        }
+
+        let background = |col| canvas.cell(col, 0).unwrap().background();
+        assert_eq!(theme.search_match_bg, background(0));
+        assert_eq!(Color::Reset, background(1));
+        assert_eq!(theme.search_match_bg, background(3));
+        assert_eq!(theme.search_match_bg, background(4));
+        assert_eq!(Color::Reset, background(5));
+    }
+
+    #[test]
+    fn search_highlights_are_cleared_until_the_next_search() {
+        let mut buffer = buffer_with_line(Rect::new(10, 1), "Hello");
+        buffer.update(Message::Search("l".into())).unwrap();
+        assert_eq!(2, buffer.search_matches.len());
+
+        buffer.update(Message::ClearSearchHighlight).unwrap();
+        buffer.update(Message::InsertChar('l')).unwrap();
+        assert!(buffer.search_matches.is_empty());
+
+        buffer.update(Message::SearchNext).unwrap();
+        assert_eq!(3, buffer.search_matches.len());
+    }
+
+    #[test]
+    fn brackets_are_highlighted_with_their_pair() {
+        let mut buffer = buffer_with_line(Rect::new(10, 1), "f(a[0])");
+        buffer.update(Message::MoveCursorRight(1)).unwrap();
+
+        let theme = Theme::default();
+        let mut canvas = TestCanvas::new(10, 1);
+        {
+            let mut viewport = Viewport::new(&mut canvas).unwrap();
+            viewport.render(&buffer, &theme).unwrap();
+        }
+
+        let background = |col| canvas.cell(col, 0).unwrap().background();
+        assert_eq!(theme.matching_bracket, background(1));
+        assert_eq!(theme.matching_bracket, background(6));
+        assert_eq!(Color::Reset, background(3));
+        assert_eq!(Color::Reset, background(5));
+    }
+
+    #[test]
+    fn scroll_keeps_cursor_visible_when_moving_back_left() {
+        let mut buffer = buffer_with_line(Rect::new(5, 10), "Hello World!");
+
+        buffer.update(Message::MoveCursorLineEnd).unwrap();
+        buffer.update(Message::MoveCursorLeft(2)).unwrap();
+        assert_eq!(Position::new(2, 0), buffer.cursor_position());
+        assert_eq!(8, buffer.offset.col);
+
+        buffer.update(Message::MoveCursorLeft(5)).unwrap();
+        assert_eq!(Position::new(0, 0), buffer.cursor_position());
+        assert_eq!(5, buffer.offset.col);
+
+        buffer.update(Message::MoveCursorLineStart).unwrap();
+        assert_eq!(Position::new(0, 0), buffer.cursor_position());
+        assert_eq!(0, buffer.offset.col);
+    }
+
+    #[test]
+    fn goto_last_edit_follows_a_change_to_an_earlier_row() {
+        let mut buffer = buffer_with_line(Rect::new(20, 5), "Hello");
+        buffer.update(Message::MoveCursorLineEnd).unwrap();
+        buffer.update(Message::InsertLineBreak).unwrap();
+        buffer.update(Message::InsertChar('!')).unwrap();
+
+        buffer.update(Message::MoveCursorUp(1)).unwrap();
+        buffer.update(Message::OpenLineAbove).unwrap();
+        buffer.update(Message::MoveCursorDocumentEnd).unwrap();
+        buffer.update(Message::GotoLastEdit).unwrap();
+        assert_eq!(Position::new(0, 0), buffer.cursor_position);
+
+        buffer.update(Message::Undo).unwrap();
+        buffer.update(Message::MoveCursorDocumentEnd).unwrap();
+        buffer.update(Message::GotoLastEdit).unwrap();
+        assert_eq!(Position::new(0, 0), buffer.cursor_position);
+
+        buffer.update(Message::Undo).unwrap();
+        buffer.update(Message::MoveCursorDocumentStart).unwrap();
+        buffer.update(Message::GotoLastEdit).unwrap();
+        assert_eq!(Position::new(0, 1), buffer.cursor_position);
+    }
+
+    #[test]
+    fn move_cursor_left_stops_at_the_start_of_the_row() {
+        let mut buffer = buffer_with_line(Rect::new(20, 5), "Hello");
+        buffer.update(Message::MoveCursorLineEnd).unwrap();
+        buffer.update(Message::InsertLineBreak).unwrap();
+
+        buffer.update(Message::MoveCursorLeft(5)).unwrap();
+        assert_eq!(Position::new(0, 1), buffer.cursor_position);
+    }
+
+    #[test]
+    fn delete_char_backward_at_the_start_of_a_row_joins_it_to_the_previous_row() {
+        let mut buffer = buffer_with_line(Rect::new(20, 5), "Hello");
+        buffer.update(Message::MoveCursorLineEnd).unwrap();
+        buffer.update(Message::InsertLineBreak).unwrap();
+        buffer.update(Message::InsertChar('!')).unwrap();
+        buffer.update(Message::MoveCursorLineStart).unwrap();
+
+        buffer.update(Message::DeleteCharBackward).unwrap();
+        assert_eq!(Some("Hello!".to_string()), buffer.document().line(0));
+        assert_eq!(Position::new(5, 0), buffer.cursor_position);
+    }
+
+    #[test]
+    fn scroll_keeps_cursor_visible_when_moving_down_past_the_bottom() {
+        let mut buffer = buffer_with_line(Rect::new(5, 3), "");
+        for _ in 0..10 {
+            buffer.update(Message::InsertLineBreak).unwrap();
+        }
+
+        assert_eq!(10, buffer.cursor_position.row);
+        assert_eq!(Position::new(0, 2), buffer.cursor_position());
+        assert_eq!(8, buffer.offset.row);
+
+        buffer.update(Message::GoToLine(1)).unwrap();
+        assert_eq!(Position::new(0, 0), buffer.cursor_position());
+        assert_eq!(0, buffer.offset.row);
+    }
+
+    #[test]
+    fn page_motions_and_scrolling_work_in_tiny_viewports() {
+        for height in 0..=1 {
+            let mut buffer = buffer_with_line(Rect::new(5, height), "");
+            for _ in 0..10 {
+                buffer.update(Message::InsertLineBreak).unwrap();
+            }
+            buffer.update(Message::GoToLine(1)).unwrap();
+
+            for msg in vec![
+                Message::MoveCursorPageDown,
+                Message::ScrollHalfPageDown,
+                Message::ScrollLineDown,
+                Message::ScrollLineUp,
+                Message::ScrollHalfPageUp,
+                Message::MoveCursorPageUp,
+                Message::MoveCursorPageDown,
+                Message::MoveCursorDocumentEnd,
+                Message::MoveCursorPageDown,
+            ] {
+                buffer.update(msg).unwrap();
+                assert!(buffer.cursor_position.row < buffer.document.len());
+            }
+        }
+
+        let mut buffer = buffer_with_line(Rect::new(5, 1), "");
+        for _ in 0..10 {
+            buffer.update(Message::InsertLineBreak).unwrap();
+        }
+        buffer.update(Message::GoToLine(1)).unwrap();
+
+        buffer.update(Message::MoveCursorPageDown).unwrap();
+        assert_eq!(1, buffer.cursor_position.row);
+        assert_eq!(1, buffer.offset.row);
+    }
+
+    #[test]
+    fn half_page_scrolls_move_the_view_and_cursor_together() {
+        let mut buffer = buffer_with_line(Rect::new(5, 4), "");
+        for _ in 0..10 {
+            buffer.update(Message::InsertLineBreak).unwrap();
+        }
+        buffer.update(Message::GoToLine(1)).unwrap();
+
+        buffer.update(Message::ScrollHalfPageDown).unwrap();
+        assert_eq!(2, buffer.cursor_position.row);
+        assert_eq!(2, buffer.offset.row);
+
+        // The view stops once the last line is at the bottom, the cursor carries on to the end.
+        for _ in 0..5 {
+            buffer.update(Message::ScrollHalfPageDown).unwrap();
+        }
+        assert_eq!(10, buffer.cursor_position.row);
+        assert_eq!(7, buffer.offset.row);
+
+        buffer.update(Message::ScrollHalfPageUp).unwrap();
+        assert_eq!(8, buffer.cursor_position.row);
+        assert_eq!(5, buffer.offset.row);
+
+        for _ in 0..5 {
+            buffer.update(Message::ScrollHalfPageUp).unwrap();
+        }
+        assert_eq!(0, buffer.cursor_position.row);
+        assert_eq!(0, buffer.offset.row);
+    }
+
+    #[test]
+    fn scrolling_a_line_only_moves_the_cursor_when_it_would_leave_the_view() {
+        let mut buffer = buffer_with_line(Rect::new(5, 4), "");
+        for _ in 0..10 {
+            buffer.update(Message::InsertLineBreak).unwrap();
+        }
+        buffer.update(Message::GoToLine(1)).unwrap();
+        buffer.update(Message::GoToLine(3)).unwrap();
+        assert_eq!(0, buffer.offset.row);
+
+        buffer.update(Message::ScrollLineDown).unwrap();
+        buffer.update(Message::ScrollLineDown).unwrap();
+        assert_eq!(2, buffer.offset.row);
+        assert_eq!(2, buffer.cursor_position.row);
+
+        buffer.update(Message::ScrollLineDown).unwrap();
+        assert_eq!(3, buffer.offset.row);
+        assert_eq!(3, buffer.cursor_position.row);
+
+        for _ in 0..4 {
+            buffer.update(Message::ScrollLineUp).unwrap();
+        }
+        assert_eq!(0, buffer.offset.row);
+        assert_eq!(3, buffer.cursor_position.row);
+
+        for _ in 0..20 {
+            buffer.update(Message::ScrollLineDown).unwrap();
+        }
+        assert_eq!(10, buffer.offset.row);
+        assert_eq!(10, buffer.cursor_position.row);
+    }
+
+    #[test]
+    fn scroll_off_keeps_lines_in_view_around_the_cursor() {
+        let mut buffer = buffer_with_line(Rect::new(5, 10), "");
+        for _ in 0..19 {
+            buffer.update(Message::InsertLineBreak).unwrap();
+        }
+        buffer.update(Message::GoToLine(1)).unwrap();
+        buffer.set_scroll_off(3);
+
+        buffer.update(Message::MoveCursorDown(6)).unwrap();
+        assert_eq!(0, buffer.offset.row);
+
+        buffer.update(Message::MoveCursorDown(1)).unwrap();
+        assert_eq!(1, buffer.offset.row);
+
+        buffer.update(Message::MoveCursorUp(3)).unwrap();
+        assert_eq!(1, buffer.offset.row);
+
+        buffer.update(Message::MoveCursorUp(1)).unwrap();
+        assert_eq!(0, buffer.offset.row);
+
+        // The last line is shown at the bottom rather than scrolling past the document end.
+        buffer.update(Message::GoToLine(20)).unwrap();
+        assert_eq!(10, buffer.offset.row);
+    }
+
+    #[test]
+    fn cursor_moves_to_the_document_start_and_end() {
+        let mut buffer = buffer_with_line(Rect::new(5, 3), "");
+        for _ in 0..10 {
+            buffer.update(Message::InsertLineBreak).unwrap();
+        }
+        buffer.update(Message::InsertText("end".into())).unwrap();
+
+        buffer.update(Message::MoveCursorDocumentStart).unwrap();
+        assert_eq!(Position::new(0, 0), buffer.cursor_position());
+        assert_eq!(0, buffer.offset.row);
+
+        buffer.update(Message::MoveCursorDocumentEnd).unwrap();
+        assert_eq!(Position::new(3, 10), buffer.cursor_position);
+        assert_eq!(8, buffer.offset.row);
+    }
+
+    #[test]
+    fn replaced_characters_are_restored_in_reverse_order() {
+        let mut buffer = buffer_with_line(Rect::new(20, 10), "abc");
+        buffer
+            .update(Message::EnterMode(Mode::Replace(Replace)))
+            .unwrap();
+        buffer.update(Message::MoveCursorRight(1)).unwrap();
+
+        for ch in "XYZ".chars() {
+            buffer.update(Message::ReplaceChar(ch)).unwrap();
+        }
+        assert_eq!("aXYZ", buffer.document.row(0).unwrap().contents());
+        assert_eq!(Position::new(4, 0), buffer.cursor_position());
+
+        buffer.update(Message::RestoreReplacedChar).unwrap();
+        buffer.update(Message::RestoreReplacedChar).unwrap();
+        assert_eq!("aXc", buffer.document.row(0).unwrap().contents());
+        assert_eq!(Position::new(2, 0), buffer.cursor_position());
+    }
+
+    #[test]
+    fn read_only_documents_are_not_changed_or_saved() {
+        let mut buffer = buffer_with_line(Rect::new(10, 5), "Hello");
+        buffer.set_read_only(true);
+        let modified = buffer.is_modified();
+
+        for msg in [
+            Message::InsertChar('x'),
+            Message::InsertLineBreak,
+            Message::DeleteCharForward,
+            Message::DeleteCharsUnderCursor(2),
+            Message::ToggleCase(1),
+            Message::Save,
+            Message::SaveAs("hello.txt".into()),
+        ] {
+            let error = buffer.update(msg).unwrap().map(|cmd| cmd());
+            assert_eq!(Some(Message::StatusError(READ_ONLY_ERROR.into())), error);
+        }
+
+        assert_eq!(1, buffer.lines_in_document());
+        assert_eq!("Hello", buffer.document().row(0).unwrap().contents());
+        assert_eq!(modified, buffer.is_modified());
+    }
+
+    #[test]
+    fn indenting_moves_the_cursor_to_the_first_non_blank_character() {
+        let mut buffer = buffer_with_line(Rect::new(20, 5), "Hello");
+        buffer.update(Message::MoveCursorLineEnd).unwrap();
+
+        buffer.update(Message::IndentLine(1)).unwrap();
+        assert_eq!("    Hello", buffer.document().row(0).unwrap().contents());
+        assert_eq!(Position::new(4, 0), buffer.cursor_position);
+
+        buffer.update(Message::DedentLine(1)).unwrap();
+        buffer.update(Message::DedentLine(1)).unwrap();
+        assert_eq!("Hello", buffer.document().row(0).unwrap().contents());
+        assert_eq!(Position::new(0, 0), buffer.cursor_position);
+    }
+
+    #[test]
+    fn new_lines_inherit_the_indent_of_the_line_they_are_split_from() {
+        let mut buffer = buffer_with_line(Rect::new(20, 5), "    Hello");
+        buffer.update(Message::MoveCursorLineEnd).unwrap();
+        buffer.update(Message::InsertLineBreak).unwrap();
+
+        assert_eq!("    ", buffer.document().row(1).unwrap().contents());
+        assert_eq!(Position::new(4, 1), buffer.cursor_position);
+
+        buffer.config.auto_indent = false;
+        buffer.update(Message::InsertLineBreak).unwrap();
+
+        assert_eq!("", buffer.document().row(2).unwrap().contents());
+        assert_eq!(Position::new(0, 2), buffer.cursor_position);
+    }
+
+    #[test]
+    fn tab_inserts_a_tab_character_by_default() {
+        let mut buffer = buffer_with_line(Rect::new(20, 5), "ab");
+        buffer.update(Message::MoveCursorRight(1)).unwrap();
+
+        buffer.update(Message::InsertTab).unwrap();
+
+        assert_eq!(Some("a\tb".to_string()), buffer.document().line(0));
+        assert_eq!(Position::new(2, 0), buffer.cursor_position);
+    }
+
+    #[test]
+    fn tab_inserts_spaces_to_the_next_tab_stop_when_expanded() {
+        let mut buffer = buffer_with_line(Rect::new(20, 5), "ab");
+        buffer.set_expand_tab(true);
+        buffer.update(Message::MoveCursorRight(1)).unwrap();
+
+        buffer.update(Message::InsertTab).unwrap();
+        assert_eq!("a   b", buffer.document().row(0).unwrap().contents());
+        assert_eq!(Position::new(4, 0), buffer.cursor_position);
+
+        buffer.update(Message::InsertTab).unwrap();
+        assert_eq!("a       b", buffer.document().row(0).unwrap().contents());
+        assert_eq!(Position::new(8, 0), buffer.cursor_position);
+    }
+
+    #[test]
+    fn words_are_deleted_up_to_the_word_motion() {
+        let mut buffer = buffer_with_line(Rect::new(20, 5), "foo bar baz");
+        buffer.update(Message::MoveCursorRight(4)).unwrap();
+
+        buffer.update(Message::DeleteWordForward(1)).unwrap();
+        assert_eq!("foo baz", buffer.document().row(0).unwrap().contents());
+        assert_eq!(Position::new(4, 0), buffer.cursor_position);
+
+        buffer.update(Message::DeleteWordBackward(1)).unwrap();
+        assert_eq!("baz", buffer.document().row(0).unwrap().contents());
+        assert_eq!(Position::new(0, 0), buffer.cursor_position);
+
+        buffer.update(Message::DeleteToWordEnd(1)).unwrap();
+        assert_eq!("", buffer.document().row(0).unwrap().contents());
+    }
+
+    #[test]
+    fn word_motions_with_a_large_count_stop_at_the_end_of_the_document() {
+        let mut buffer = buffer_with_line(Rect::new(20, 5), "foo bar baz");
+
+        buffer
+            .update(Message::MoveCursorWordForward(usize::MAX))
+            .unwrap();
+        assert_eq!(Position::new(11, 0), buffer.cursor_position);
+
+        buffer
+            .update(Message::MoveCursorWordBackward(usize::MAX))
+            .unwrap();
+        assert_eq!(Position::new(0, 0), buffer.cursor_position);
+
+        buffer
+            .update(Message::MoveCursorWordEnd(usize::MAX))
+            .unwrap();
+        assert_eq!(Position::new(11, 0), buffer.cursor_position);
+
+        buffer.update(Message::MoveCursorLineStart).unwrap();
+        buffer.update(Message::DeleteAWord(usize::MAX)).unwrap();
+        buffer
+            .update(Message::DeleteWordForward(usize::MAX))
+            .unwrap();
+        assert_eq!("", buffer.document().row(0).unwrap().contents());
+    }
+
+    #[test]
+    fn deleting_a_word_takes_its_surrounding_whitespace() {
+        let mut buffer = buffer_with_line(Rect::new(20, 5), "foo bar baz");
+        buffer.update(Message::MoveCursorRight(5)).unwrap();
+
+        buffer.update(Message::DeleteAWord(1)).unwrap();
+        assert_eq!("foo baz", buffer.document().row(0).unwrap().contents());
+
+        buffer.update(Message::DeleteAWord(1)).unwrap();
+        assert_eq!("foo", buffer.document().row(0).unwrap().contents());
+    }
+
+    #[test]
+    fn deleting_a_word_stops_at_the_end_of_the_line() {
+        let mut buffer = buffer_with_line(Rect::new(20, 5), "foobar");
+        buffer.document.insert_newline(&Position::new(3, 0));
+        buffer.document.insert_newline(&Position::new(0, 1));
+
+        buffer.update(Message::DeleteWordForward(1)).unwrap();
+        assert_eq!(3, buffer.document().len());
+        assert_eq!("", buffer.document().row(0).unwrap().contents());
+
+        // With nothing left on the line, the line break is deleted instead.
+        buffer.update(Message::DeleteWordForward(1)).unwrap();
+        assert_eq!(2, buffer.document().len());
+        assert_eq!("", buffer.document().row(0).unwrap().contents());
+        assert_eq!("bar", buffer.document().row(1).unwrap().contents());
+    }
+
+    #[test]
+    fn smart_home_alternates_between_the_first_non_blank_and_line_start() {
+        let mut buffer = buffer_with_line(Rect::new(20, 5), "    Hello");
+        buffer.update(Message::MoveCursorLineEnd).unwrap();
+
+        buffer.update(Message::MoveCursorHome).unwrap();
+        assert_eq!(0, buffer.cursor_position.col);
+
+        buffer.set_smart_home(true);
+        buffer.update(Message::MoveCursorLineEnd).unwrap();
+
+        buffer.update(Message::MoveCursorHome).unwrap();
+        assert_eq!(4, buffer.cursor_position.col);
+
+        buffer.update(Message::MoveCursorHome).unwrap();
+        assert_eq!(0, buffer.cursor_position.col);
+
+        buffer.update(Message::MoveCursorHome).unwrap();
+        assert_eq!(4, buffer.cursor_position.col);
+    }
+
+    #[test]
+    fn toggle_case_advances_the_cursor_past_the_toggled_characters() {
+        let mut buffer = buffer_with_line(Rect::new(20, 10), "hello");
+
+        buffer.update(Message::ToggleCase(2)).unwrap();
+        assert_eq!("HEllo", buffer.document.row(0).unwrap().contents());
+        assert_eq!(Position::new(2, 0), buffer.cursor_position());
+
+        buffer.update(Message::ToggleCase(10)).unwrap();
+        assert_eq!("HELLO", buffer.document.row(0).unwrap().contents());
+        assert_eq!(Position::new(5, 0), buffer.cursor_position());
+    }
+
+    #[test]
+    fn cursor_position_accounts_for_wide_characters() {
+        let mut buffer = buffer_with_line(Rect::new(20, 10), "a\u{1f980}\u{4e16}b");
+
+        buffer.update(Message::MoveCursorRight(1)).unwrap();
+        assert_eq!(Position::new(1, 0), buffer.cursor_position());
+
+        buffer.update(Message::MoveCursorRight(1)).unwrap();
+        assert_eq!(Position::new(3, 0), buffer.cursor_position());
+
+        buffer.update(Message::MoveCursorRight(1)).unwrap();
+        assert_eq!(Position::new(5, 0), buffer.cursor_position());
+    }
+
+    #[test]
+    fn cursor_position_accounts_for_tab_stops() {
+        let mut buffer = buffer_with_line(Rect::new(20, 10), "a\tb");
+
+        buffer.update(Message::MoveCursorRight(1)).unwrap();
+        assert_eq!(Position::new(1, 0), buffer.cursor_position());
+
+        buffer.update(Message::MoveCursorRight(1)).unwrap();
+        assert_eq!(Position::new(4, 0), buffer.cursor_position());
+    }
+
+    #[test]
+    fn listed_tabs_keep_the_cursor_in_place() {
+        let mut buffer = buffer_with_line(Rect::new(10, 1), "\tx ");
+        buffer.set_list(true);
+        buffer.update(Message::MoveCursorRight(1)).unwrap();
+        assert_eq!(Position::new(4, 0), buffer.cursor_position());
+
+        let theme = Theme::default();
+        let mut canvas = TestCanvas::new(10, 1);
+        {
+            let mut viewport = Viewport::new(&mut canvas).unwrap();
+            viewport.render(&buffer, &theme).unwrap();
+        }
+
+        assert!(canvas.row(0).starts_with("→   x·"));
+        assert_eq!(theme.whitespace, canvas.cell(0, 0).unwrap().foreground());
+        assert_eq!(theme.whitespace, canvas.cell(5, 0).unwrap().foreground());
+        assert_eq!(theme.foreground, canvas.cell(4, 0).unwrap().foreground());
+    }
+
+    #[test]
+    fn scroll_keeps_wide_characters_fully_visible() {
+        let mut buffer = buffer_with_line(Rect::new(4, 10), "abc\u{1f980}");
+
+        buffer.update(Message::MoveCursorRight(3)).unwrap();
+        assert_eq!(1, buffer.offset.col);
+        assert_eq!(Position::new(2, 0), buffer.cursor_position());
+    }
+
+    #[test]
+    fn mouse_click_on_a_wide_character_selects_it() {
+        let mut buffer = buffer_with_line(Rect::new(20, 10), "a\u{1f980}b");
+
+        buffer
+            .update(Message::MouseClick(Position::new(2, 0)))
+            .unwrap();
+        assert_eq!(Position::new(1, 0), buffer.cursor_position);
+
+        buffer
+            .update(Message::MouseClick(Position::new(3, 0)))
+            .unwrap();
+        assert_eq!(Position::new(2, 0), buffer.cursor_position);
+    }
+
+    #[test]
+    fn cursor_position_and_mouse_clicks_account_for_the_viewport_position() {
+        let mut buffer = buffer_with_line(Rect::positioned(20, 5, 0, 6), "Hello");
+
+        buffer.update(Message::MoveCursorRight(2)).unwrap();
+        assert_eq!(Position::new(2, 6), buffer.cursor_position());
+
+        buffer
+            .update(Message::MouseClick(Position::new(4, 6)))
+            .unwrap();
+        assert_eq!(Position::new(4, 0), buffer.cursor_position);
+
+        buffer
+            .update(Message::MouseClick(Position::new(1, 2)))
+            .unwrap();
+        assert_eq!(Position::new(4, 0), buffer.cursor_position);
+    }
+
+    #[test]
+    fn delete_chars_under_and_before_cursor_stay_on_the_line() {
+        let mut buffer = buffer_with_line(Rect::new(20, 10), "Hello World");
+
+        buffer.update(Message::MoveCursorRight(5)).unwrap();
+        buffer.update(Message::DeleteCharsUnderCursor(100)).unwrap();
+        assert_eq!("Hello", buffer.document.row(0).unwrap().contents());
+
+        buffer.update(Message::DeleteCharsBeforeCursor(2)).unwrap();
+        assert_eq!("Hel", buffer.document.row(0).unwrap().contents());
+        assert_eq!(Position::new(3, 0), buffer.cursor_position);
+
+        buffer.update(Message::InsertLineBreak).unwrap();
+        buffer
+            .update(Message::GotoPosition(Position::new(3, 0)))
+            .unwrap();
+        buffer.update(Message::DeleteCharsUnderCursor(1)).unwrap();
+        assert_eq!(2, buffer.document.len());
+    }
+
+    #[test]
+    fn gutter_offsets_the_cursor_and_scales_with_line_count() {
+        let mut buffer = buffer_with_line(Rect::new(20, 10), "Hello");
+        buffer.config.line_numbers = Some(LineNumberStyle::Absolute);
+
+        assert_eq!(4, buffer.gutter_width());
+        assert_eq!(16, buffer.text_width());
+
+        buffer.update(Message::MoveCursorRight(2)).unwrap();
+        assert_eq!(Position::new(6, 0), buffer.cursor_position());
+
+        for _ in 0..1000 {
+            buffer.update(Message::InsertLineBreak).unwrap();
+        }
+
+        assert_eq!(5, buffer.gutter_width());
+    }
+
+    #[test]
+    fn large_documents_are_read_as_the_cursor_moves_through_them() {
+        let file_name = TempFile::new("buffer-large.txt");
+        std::fs::write(&file_name, "line\n".repeat(1_000_000)).unwrap();
+
+        let mut buffer = Buffer::new(
+            Rect::new(20, 10),
+            Document::open(&file_name).unwrap(),
+            &Config::default(),
+        );
+        let loaded = buffer.document.len();
+
+        buffer.update(Message::MoveCursorDown(loaded)).unwrap();
+        assert!(!buffer.document.is_loaded());
+        assert_eq!(loaded, buffer.cursor_position.row);
+        assert_eq!(loaded + 20, buffer.document.len());
+
+        buffer.update(Message::MoveCursorDocumentEnd).unwrap();
+        assert!(buffer.document.is_loaded());
+        assert_eq!(999_999, buffer.cursor_position.row);
+    }
+
+    #[test]
+    fn editing_a_large_document_only_reads_the_rows_near_the_edit() {
+        let file_name = TempFile::new("buffer-large-edit.txt");
+        std::fs::write(&file_name, "line\n".repeat(1_000_000)).unwrap();
+
+        let mut buffer = Buffer::new(
+            Rect::new(20, 10),
+            Document::open(&file_name).unwrap(),
+            &Config::default(),
+        );
+        let loaded = buffer.document.len();
+
+        buffer.update(Message::InsertChar('x')).unwrap();
+        buffer.update(Message::JoinLines(1)).unwrap();
+        buffer.update(Message::Undo).unwrap();
+        assert!(!buffer.document.is_loaded());
+        assert_eq!(loaded, buffer.document.len());
+        assert_eq!(5, buffer.gutter_width());
+
+        buffer.set_row_count(Document::count_rows(&file_name).unwrap());
+        assert_eq!(1_000_000, buffer.lines_in_document());
+        assert_eq!(8, buffer.gutter_width());
+
+        buffer.update(Message::GoToLine(5000)).unwrap();
+        assert!(!buffer.document.is_loaded());
+        assert_eq!(4999, buffer.cursor_position.row);
+    }
+
+    #[test]
+    fn saving_over_a_file_that_was_not_utf8_warns_first() {
+        let file_name = TempFile::new("buffer-latin1.txt");
+        std::fs::write(&file_name, b"caf\xe9\n").unwrap();
+
+        let mut buffer = Buffer::new(
+            Rect::new(20, 5),
+            Document::open(&file_name).unwrap(),
+            &Config::default(),
+        );
+
+        let error = buffer.update(Message::Save).unwrap_err();
+        assert_eq!(LOSSY_SAVE_WARNING, error.to_string());
+        assert_eq!(b"caf\xe9\n".to_vec(), std::fs::read(&file_name).unwrap());
+
+        buffer.update(Message::Save).unwrap().unwrap()();
+        assert_eq!(
+            "caf\u{fffd}\n",
+            std::fs::read_to_string(&file_name).unwrap()
+        );
+    }
+
+    #[test]
+    fn documents_are_only_unmodified_once_the_save_has_been_written() {
+        let file_name = TempFile::new("buffer-save.txt");
+        let mut buffer = buffer_with_line(Rect::new(20, 5), "hello");
+
+        let write = buffer
+            .update(Message::SaveAs(file_name.to_string()))
+            .unwrap();
+        assert!(buffer.is_modified());
+        assert!(!std::path::Path::new(&*file_name).exists());
+
+        let saved = write.unwrap()();
+        assert!(matches!(&saved, Message::Saved { lines: 1, .. }));
+        assert_eq!("hello\n", std::fs::read_to_string(&file_name).unwrap());
+
+        buffer.update(saved).unwrap();
+        assert!(!buffer.is_modified());
+
+        // Changes made while the file is being written still need saving.
+        let write = buffer.update(Message::Save).unwrap().unwrap();
+        buffer.update(Message::InsertChar('!')).unwrap();
+        buffer.update(write()).unwrap();
+        assert!(buffer.is_modified());
+    }
+
+    #[test]
+    fn failed_saves_are_reported_without_quitting() {
+        let dir = std::env::temp_dir().to_string_lossy().into_owned();
+        let mut buffer = buffer_with_line(Rect::new(20, 5), "hello");
+
+        match buffer
+            .update(Message::SaveQuit {
+                file_name: Some(dir),
+                only_if_modified: false,
+            })
+            .unwrap()
+            .unwrap()()
+        {
+            Message::StatusError(error) => assert!(error.starts_with("unable to save document")),
+            msg => panic!("expected the save to fail, got {:?}", msg),
+        }
+        assert!(buffer.is_modified());
+    }
+
+    #[test]
+    fn wq_writes_unmodified_documents_while_x_does_not() {
+        let file_name = TempFile::new("buffer-wq.txt");
+        std::fs::write(&file_name, "hello\n").unwrap();
+
+        let mut buffer = Buffer::new(
+            Rect::new(20, 5),
+            Document::open(&file_name).unwrap(),
+            &Config::default(),
+        );
+        let save_quit = |only_if_modified| Message::SaveQuit {
+            file_name: None,
+            only_if_modified,
+        };
+
+        // The file is written again even though the document has no changes.
+        std::fs::remove_file(&file_name).unwrap();
+        let write = buffer.update(save_quit(false)).unwrap().unwrap();
+        assert_eq!(
+            Message::Batch(vec![
+                Message::Saved {
+                    document: buffer.document().id(),
+                    file_name: file_name.to_string(),
+                    lines: 1,
+                    revision: 0,
+                },
+                Message::Quit,
+            ]),
+            write()
+        );
+        assert_eq!("hello\n", std::fs::read_to_string(&file_name).unwrap());
+
+        std::fs::remove_file(&file_name).unwrap();
+        let quit = buffer.update(save_quit(true)).unwrap().unwrap();
+        assert_eq!(Message::Quit, quit());
+        assert!(!std::path::Path::new(&*file_name).exists());
     }
 }
@@ -2,12 +2,15 @@ use crate::communication::{Command, Message};
 use anyhow::Result;
 
 mod buffer;
+mod quickfix;
 mod status_bar;
 mod text_input;
 mod welcome;
 mod window;
 
 use buffer::Buffer;
+use quickfix::Quickfix;
+pub use quickfix::QuickfixEntry;
 use status_bar::StatusBar;
 use text_input::TextInput;
 use welcome::Welcome;
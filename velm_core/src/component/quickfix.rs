@@ -0,0 +1,111 @@
+use crate::communication::{Command, Message};
+use crate::component::Component;
+use crate::render::{Frame, View};
+use crate::ui::{Position, Rect, Theme};
+use anyhow::Result;
+
+/// A single location held in the `Quickfix` list.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct QuickfixEntry {
+    pub file_name: Option<String>,
+    pub position: Position,
+    pub text: String,
+}
+
+impl QuickfixEntry {
+    pub fn new(file_name: Option<String>, position: Position, text: &str) -> Self {
+        Self {
+            file_name,
+            position,
+            text: String::from(text),
+        }
+    }
+}
+
+/// `Quickfix` holds a navigable list of locations, such as search results, and renders them as a
+/// pane with the currently selected entry highlighted.
+pub struct Quickfix {
+    area: Rect,
+    entries: Vec<QuickfixEntry>,
+    selected: usize,
+}
+
+impl Quickfix {
+    pub fn new(area: Rect) -> Self {
+        Self {
+            area,
+            entries: Vec::default(),
+            selected: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn resize(&mut self, area: Rect) {
+        self.area = area;
+    }
+
+    /// The currently selected entry, if the list is not empty.
+    pub fn selected(&self) -> Option<&QuickfixEntry> {
+        self.entries.get(self.selected)
+    }
+}
+
+impl Component for Quickfix {
+    fn update(&mut self, msg: Message) -> Result<Option<Command>> {
+        match msg {
+            Message::SetQuickfixList(entries) => {
+                self.entries = entries;
+                self.selected = 0;
+            }
+            Message::AppendQuickfixEntries(entries) => self.entries.extend(entries),
+            Message::QuickfixNext if self.selected + 1 < self.entries.len() => {
+                self.selected += 1;
+            }
+            Message::QuickfixPrevious => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            _ => (),
+        }
+
+        Ok(None)
+    }
+}
+
+impl View for Quickfix {
+    fn render_to(&self, frame: &mut Frame, theme: &Theme) {
+        let first_in_view = (self.selected + 1).saturating_sub(self.area.height);
+
+        for row_in_view in 0..self.area.height {
+            let row = self.area.top() + row_in_view;
+            let idx = first_in_view + row_in_view;
+
+            let written = if let Some(entry) = self.entries.get(idx) {
+                let line: String = format!(
+                    "{}|{}| {}",
+                    entry.file_name.as_deref().unwrap_or("[No Name]"),
+                    entry.position.row + 1,
+                    entry.text
+                )
+                .chars()
+                .take(self.area.width)
+                .collect();
+
+                if idx == self.selected {
+                    frame.write_line(row, &line, theme.highlight_fg, theme.highlight_bg)
+                } else {
+                    frame.write_line(row, &line, theme.foreground, theme.background)
+                }
+            } else {
+                frame.write_line(row, "", theme.foreground, theme.background)
+            };
+
+            // The rows below one that is outside of the frame are too.
+            if written.is_err() {
+                break;
+            }
+        }
+    }
+}
@@ -1,38 +1,207 @@
+use crate::mode::Mode;
 use crate::render::{Frame, View};
-use crate::ui::{Color, Position, Rect};
+use crate::ui::{Color, Position, Rect, Theme};
+use std::cmp;
 
 pub struct StatusBar {
     pub area: Rect,
-    pub mode: String,
+    pub mode: Mode,
     pub line_count: usize,
+    /// Whether only some of the lines of a large file have been read and the rest haven't been
+    /// counted yet, in which case the line count is marked as a lower bound.
+    pub partially_loaded: bool,
+    /// The number of lines that fit on screen, used to tell when the whole document is visible.
+    pub visible_lines: usize,
+    /// The position of the cursor within the document.
     pub cursor_position: Position,
     pub file_name: String,
+    pub modified: bool,
+    pub read_only: bool,
+    pub buffer_number: usize,
+    pub buffer_count: usize,
+    /// The number of characters in the document.
+    pub char_count: Option<usize>,
+    /// The number of characters in the selection while in Visual mode.
+    pub selected_char_count: Option<usize>,
+}
+
+impl StatusBar {
+    /// The foreground and background colors of the bar for the current mode.
+    fn colors(&self, theme: &Theme) -> (Color, Color) {
+        match self.mode {
+            Mode::Execute(_) | Mode::Search(_) => {
+                (theme.command_status_fg, theme.command_status_bg)
+            }
+            Mode::Insert(_) | Mode::Replace(_) => (theme.insert_status_fg, theme.insert_status_bg),
+            Mode::Normal(_) => (theme.status_fg, theme.status_bg),
+            Mode::Visual(_) => (theme.visual_status_fg, theme.visual_status_bg),
+        }
+    }
+
+    /// How far through the document the cursor is, like vim's ruler. `All` when the whole
+    /// document fits on screen, `Top` and `Bot` on the first and last lines, and a percentage
+    /// in between.
+    fn ruler(&self) -> String {
+        let row = self.cursor_position.row;
+        let last_row = self.line_count.saturating_sub(1);
+
+        if self.line_count <= cmp::max(1, self.visible_lines) {
+            "All".into()
+        } else if row == 0 {
+            "Top".into()
+        } else if row >= last_row {
+            "Bot".into()
+        } else {
+            format!("{}%", row * 100 / last_row)
+        }
+    }
 }
 
 impl View for StatusBar {
-    fn render_to(&self, frame: &mut Frame) {
-        let mut status = format!("Mode: [{}]    File: {}", self.mode, self.file_name);
-        let line_indicator = format!(
-            "L: {}/{} C: {}",
-            self.cursor_position.row,
+    fn render_to(&self, frame: &mut Frame, theme: &Theme) {
+        let buffer = if self.buffer_count > 1 {
+            format!("    Buffer: {}/{}", self.buffer_number, self.buffer_count)
+        } else {
+            String::new()
+        };
+        let mut status = format!(
+            "Mode: [{}]    File: {}{}{}{}",
+            self.mode,
+            self.file_name,
+            if self.modified { " [+]" } else { "" },
+            if self.read_only { " [RO]" } else { "" },
+            buffer
+        );
+
+        let chars = match (self.selected_char_count, self.char_count) {
+            (Some(selected), Some(total)) => format!("{selected}/{total} chars    "),
+            (None, Some(total)) => format!("{total} chars    "),
+            _ => String::new(),
+        };
+        let indicator = format!(
+            "{}L: {}/{}{} C: {}    {}",
+            chars,
+            self.cursor_position.row + 1,
             self.line_count,
-            self.cursor_position.col + 1
+            if self.partially_loaded { "+" } else { "" },
+            self.cursor_position.col + 1,
+            self.ruler()
         );
 
-        let len = status.len() + line_indicator.len();
+        // The indicators keep their place on the right, the rest of the status gives way to them
+        // when the terminal is narrow.
+        let status_width = self
+            .area
+            .width
+            .saturating_sub(indicator.chars().count() + 1);
+
+        if status.chars().count() > status_width {
+            status = status.chars().take(status_width).collect();
+        }
+
+        let padding = self
+            .area
+            .width
+            .saturating_sub(status.chars().count() + indicator.chars().count());
+        let status = format!("{}{}{}", status, " ".repeat(padding), indicator);
+
+        let (foreground, background) = self.colors(theme);
+
+        // A bar outside of the frame, such as while the terminal is resized, is not drawn.
+        let _ = frame.write_line(self.area.top(), &status, foreground, background);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatusBar;
+    use crate::mode::{Mode, Normal};
+    use crate::render::Viewport;
+    use crate::testing::TestCanvas;
+    use crate::ui::{Position, Rect, Theme};
+
+    fn render(status_bar: &StatusBar) -> String {
+        let mut canvas = TestCanvas::new(status_bar.area.width, 1);
+        {
+            let mut viewport = Viewport::new(&mut canvas).unwrap();
+            viewport.render(status_bar, &Theme::default()).unwrap();
+        }
+
+        canvas.row(0)
+    }
 
-        if self.area.width > len {
-            status.push_str(&" ".repeat(self.area.width - len));
+    fn status_bar(width: usize) -> StatusBar {
+        StatusBar {
+            area: Rect::new(width, 1),
+            mode: Mode::Normal(Normal::default()),
+            line_count: 3,
+            partially_loaded: false,
+            visible_lines: 10,
+            cursor_position: Position::new(0, 1),
+            file_name: "notes.txt".into(),
+            modified: false,
+            read_only: false,
+            buffer_number: 1,
+            buffer_count: 1,
+            char_count: Some(120),
+            selected_char_count: None,
         }
+    }
+
+    #[test]
+    fn counts_are_shown_on_the_right() {
+        let rendered = render(&StatusBar {
+            selected_char_count: Some(7),
+            ..status_bar(80)
+        });
+
+        assert!(rendered.starts_with("Mode: [NORMAL]    File: notes.txt "));
+        assert!(rendered.ends_with("7/120 chars    L: 2/3 C: 1    All"));
+    }
 
-        status = format!("{}{}", status, line_indicator);
-        status.truncate(self.area.width);
+    #[test]
+    fn partially_loaded_line_counts_are_marked() {
+        let rendered = render(&StatusBar {
+            partially_loaded: true,
+            ..status_bar(80)
+        });
 
-        frame.write_line(
-            self.area.top(),
-            &status,
-            Color::Rgb(63, 63, 63),
-            Color::Rgb(239, 239, 239),
+        assert!(rendered.ends_with("L: 2/3+ C: 1    All"));
+    }
+
+    #[test]
+    fn read_only_files_are_marked() {
+        let rendered = render(&StatusBar {
+            read_only: true,
+            ..status_bar(80)
+        });
+
+        assert!(rendered.starts_with("Mode: [NORMAL]    File: notes.txt [RO] "));
+    }
+
+    #[test]
+    fn counts_are_kept_when_the_status_is_too_wide() {
+        assert_eq!(
+            "Mode: [NO 120 chars    L: 2/3 C: 1    All",
+            render(&status_bar(41))
         );
     }
+
+    #[test]
+    fn ruler_shows_how_far_through_the_document_the_cursor_is() {
+        let ruler = |row, line_count| {
+            StatusBar {
+                line_count,
+                cursor_position: Position::new(0, row),
+                ..status_bar(80)
+            }
+            .ruler()
+        };
+
+        assert_eq!("All", ruler(0, 1));
+        assert_eq!("All", ruler(5, 10));
+        assert_eq!("Top", ruler(0, 101));
+        assert_eq!("25%", ruler(25, 101));
+        assert_eq!("Bot", ruler(100, 101));
+    }
 }
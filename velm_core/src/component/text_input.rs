@@ -2,13 +2,19 @@ use crate::communication::{self, Command, Message};
 use crate::component::Component;
 use crate::mode::{Mode, Normal};
 use crate::render::{Frame, View};
-use crate::ui::{Color, Position};
+use crate::ui::{Position, Theme};
 use crate::Row;
 use anyhow::Result;
+use std::cmp;
 
 pub struct TextInput {
+    /// The index of the grapheme within `value` that the cursor is on, starting at 0. The prompt
+    /// is only accounted for when rendering.
     cursor_position: usize,
+    draft: Option<Row>,
     focused: bool,
+    history: Vec<String>,
+    history_idx: Option<usize>,
     place_holder: String,
     position: Position,
     prompt: String,
@@ -19,7 +25,10 @@ impl TextInput {
     pub fn new(prompt: &str, place_holder: &str, position: Position) -> Self {
         Self {
             cursor_position: 0,
+            draft: None,
             focused: false,
+            history: Vec::default(),
+            history_idx: None,
             place_holder: String::from(place_holder),
             position,
             prompt: String::from(prompt),
@@ -38,9 +47,57 @@ impl TextInput {
         self.focused = false;
     }
 
+    pub fn set_position(&mut self, position: Position) {
+        self.position = position;
+    }
+
     fn reset(&mut self) {
         self.value = Row::default();
         self.cursor_position = 0;
+        self.draft = None;
+        self.history_idx = None;
+    }
+
+    /// Clear the input and return to Normal mode.
+    fn abort(&mut self) -> Command {
+        self.reset();
+
+        communication::wrap(Message::EnterMode(Mode::Normal(Normal::default())))
+    }
+
+    /// Record a submitted value so that it can be recalled later. Empty values and repeats of
+    /// the most recent entry are skipped.
+    fn push_history(&mut self, value: String) {
+        if !value.is_empty() && self.history.last() != Some(&value) {
+            self.history.push(value);
+        }
+    }
+
+    /// Move through the history, replacing the current value with the recalled entry. The value
+    /// that was being typed is restored when moving forward past the most recent entry.
+    fn recall_history(&mut self, backward: bool) {
+        let idx = match (self.history_idx, backward) {
+            (None, true) => self.history.len().checked_sub(1),
+            (None, false) => return,
+            (Some(idx), true) => Some(idx.saturating_sub(1)),
+            (Some(idx), false) if idx + 1 < self.history.len() => Some(idx + 1),
+            (Some(_), false) => None,
+        };
+
+        if self.history_idx.is_none() {
+            if idx.is_none() {
+                return;
+            }
+
+            self.draft = Some(self.value.clone());
+        }
+
+        self.value = match idx {
+            Some(idx) => Row::from(&self.history[idx][..]),
+            None => self.draft.take().unwrap_or_default(),
+        };
+        self.cursor_position = self.value.len();
+        self.history_idx = idx;
     }
 }
 
@@ -54,37 +111,40 @@ impl Component for TextInput {
                 None
             }
             Message::EndCommandLineInput => {
+                let value = self.value.contents();
                 let cmd = Some(communication::wrap(Message::ParseCommandLineInput(
-                    self.value.contents(),
+                    value.clone(),
                 )));
 
+                self.push_history(value);
                 self.reset();
 
                 cmd
             }
-            Message::AbortCommandLineInput => {
-                self.reset();
+            Message::AbortCommandLineInput => Some(self.abort()),
+            Message::HistoryPrevious => {
+                self.recall_history(true);
 
-                Some(communication::wrap(Message::EnterMode(Mode::Normal(
-                    Normal::default(),
-                ))))
+                None
+            }
+            Message::HistoryNext => {
+                self.recall_history(false);
+
+                None
             }
             Message::MoveCursorLeft(n) => {
-                if self.cursor_position > 1 {
-                    self.cursor_position = self.cursor_position.saturating_sub(n);
-                }
+                self.cursor_position = self.cursor_position.saturating_sub(n);
 
                 None
             }
             Message::MoveCursorRight(n) => {
-                if self.cursor_position != self.value.len() {
-                    self.cursor_position = self.cursor_position.saturating_add(n);
-                }
+                self.cursor_position =
+                    cmp::min(self.cursor_position.saturating_add(n), self.value.len());
 
                 None
             }
             Message::MoveCursorLineStart => {
-                self.cursor_position = 1;
+                self.cursor_position = 0;
 
                 None
             }
@@ -93,30 +153,19 @@ impl Component for TextInput {
 
                 None
             }
+            // Like vim, deleting from an input that is already empty leaves it.
+            Message::DeleteCharForward | Message::DeleteCharBackward if self.value.is_empty() => {
+                Some(self.abort())
+            }
             Message::DeleteCharForward => {
                 self.value.delete(self.cursor_position);
 
-                // TODO: revmove duplication here.
-                if self.value.len() <= 1 {
-                    self.reset();
-
-                    return Ok(Some(communication::wrap(Message::EnterMode(Mode::Normal(
-                        Normal::default(),
-                    )))));
-                }
-
                 None
             }
             Message::DeleteCharBackward => {
-                self.cursor_position = self.cursor_position.saturating_sub(1);
-                self.value.delete(self.cursor_position);
-
-                if self.value.len() <= 1 {
-                    self.reset();
-
-                    return Ok(Some(communication::wrap(Message::EnterMode(Mode::Normal(
-                        Normal::default(),
-                    )))));
+                if self.cursor_position > 0 {
+                    self.cursor_position -= 1;
+                    self.value.delete(self.cursor_position);
                 }
 
                 None
@@ -127,13 +176,14 @@ impl Component for TextInput {
 }
 
 impl View for TextInput {
-    fn render_to(&self, frame: &mut Frame) {
+    fn render_to(&self, frame: &mut Frame, theme: &Theme) {
+        // A prompt that is outside of the frame, such as while the terminal is resized, is skipped.
         if self.value.is_empty() && !self.place_holder.is_empty() && !self.focused {
-            frame.write_line(
+            let _ = frame.write_line(
                 self.position.row,
                 &self.place_holder,
-                Color::default(),
-                Color::default(),
+                theme.foreground,
+                theme.background,
             );
 
             return;
@@ -141,18 +191,126 @@ impl View for TextInput {
 
         let value = format!("{}{}", self.prompt, &self.value.contents());
 
-        frame.write_line(
+        let _ = frame.write_line(
             self.position.row,
             &value,
-            Color::default(),
-            Color::default(),
+            theme.foreground,
+            theme.background,
         );
 
         if self.focused {
             frame.set_cursor_position(Position::new(
-                self.cursor_position + self.prompt.len(),
+                self.prompt.len() + self.value.width_to(self.cursor_position, 1),
                 self.position.row,
             ));
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::TextInput;
+    use crate::communication::Message;
+    use crate::component::Component;
+    use crate::mode::{Mode, Normal};
+    use crate::render::{Frame, View};
+    use crate::ui::{Position, Rect, Theme};
+
+    fn submit(input: &mut TextInput, value: &str) {
+        for ch in value.chars() {
+            input.update(Message::InsertChar(ch)).unwrap();
+        }
+
+        input.update(Message::EndCommandLineInput).unwrap();
+    }
+
+    fn rendered_cursor_col(input: &TextInput) -> usize {
+        let mut frame = Frame::empty(Rect::new(20, 1));
+        input.render_to(&mut frame, &Theme::default());
+
+        frame.cursor_position().col
+    }
+
+    #[test]
+    fn cursor_moves_to_the_start_and_end_of_the_value_after_the_prompt() {
+        let mut input = TextInput::new(">>>", "", Position::default());
+        input.focus();
+        for ch in "abc".chars() {
+            input.update(Message::InsertChar(ch)).unwrap();
+        }
+        assert_eq!(6, rendered_cursor_col(&input));
+
+        input.update(Message::MoveCursorLineStart).unwrap();
+        assert_eq!(0, input.cursor_position);
+        assert_eq!(3, rendered_cursor_col(&input));
+
+        input.update(Message::MoveCursorLeft(1)).unwrap();
+        assert_eq!(3, rendered_cursor_col(&input));
+
+        input.update(Message::MoveCursorRight(1)).unwrap();
+        assert_eq!(4, rendered_cursor_col(&input));
+
+        input.update(Message::MoveCursorLineEnd).unwrap();
+        assert_eq!(3, input.cursor_position);
+        assert_eq!(6, rendered_cursor_col(&input));
+
+        input.update(Message::MoveCursorRight(1)).unwrap();
+        assert_eq!(6, rendered_cursor_col(&input));
+    }
+
+    #[test]
+    fn deleting_only_leaves_the_input_once_it_is_empty() {
+        let mut input = TextInput::new(":", "", Position::default());
+        input.update(Message::InsertChar('w')).unwrap();
+        input.update(Message::InsertChar('q')).unwrap();
+
+        assert!(input.update(Message::DeleteCharBackward).unwrap().is_none());
+        assert_eq!("w", input.value.contents());
+
+        input.update(Message::MoveCursorLineStart).unwrap();
+        assert!(input.update(Message::DeleteCharBackward).unwrap().is_none());
+        assert_eq!("w", input.value.contents());
+
+        assert!(input.update(Message::DeleteCharForward).unwrap().is_none());
+        assert_eq!("", input.value.contents());
+
+        let cmd = input.update(Message::DeleteCharBackward).unwrap().unwrap();
+        assert_eq!(Message::EnterMode(Mode::Normal(Normal::default())), cmd());
+    }
+
+    #[test]
+    fn history_recalls_submitted_values() {
+        let mut input = TextInput::new(":", "", Position::default());
+        submit(&mut input, "w");
+        submit(&mut input, "q");
+        submit(&mut input, "q");
+
+        input.update(Message::InsertChar('e')).unwrap();
+
+        input.update(Message::HistoryPrevious).unwrap();
+        assert_eq!("q", input.value.contents());
+        assert_eq!(1, input.cursor_position);
+
+        input.update(Message::HistoryPrevious).unwrap();
+        assert_eq!("w", input.value.contents());
+
+        input.update(Message::HistoryPrevious).unwrap();
+        assert_eq!("w", input.value.contents());
+
+        input.update(Message::HistoryNext).unwrap();
+        assert_eq!("q", input.value.contents());
+
+        input.update(Message::HistoryNext).unwrap();
+        assert_eq!("e", input.value.contents());
+    }
+
+    #[test]
+    fn history_does_nothing_when_empty() {
+        let mut input = TextInput::new(":", "", Position::default());
+        input.update(Message::InsertChar('e')).unwrap();
+
+        input.update(Message::HistoryPrevious).unwrap();
+        input.update(Message::HistoryNext).unwrap();
+        assert_eq!("e", input.value.contents());
+    }
+}
@@ -1,28 +1,124 @@
 use crate::{
     render::View,
-    ui::{Color, Rect},
+    ui::{Rect, Theme},
 };
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Hints for getting started, shown below the version.
+const HINTS: &[&str] = &[
+    "type  i             to start editing",
+    "type  :e file<CR>   to open a file  ",
+    "type  :q<CR>        to quit         ",
+];
+
 pub struct Welcome {
     pub size: Rect,
 }
 
-impl View for Welcome {
-    fn render_to(&self, frame: &mut crate::render::Frame) {
-        let mut message = format!("Velm editor -- version {}", VERSION);
-        let padding = self.size.width.saturating_sub(message.len()) / 2;
+impl Welcome {
+    /// Center the text within the width of the screen after the leading tilde, cutting it off
+    /// if the screen is too narrow.
+    fn centered(&self, text: &str) -> String {
+        let padding = self.size.width.saturating_sub(text.len()) / 2;
         let spaces = " ".repeat(padding.saturating_sub(1));
-        message = format!("~{}{}", spaces, message);
-        message.truncate(self.size.width);
-        for row in 0..self.size.height - 1 {
-            if row == self.size.height / 3 {
-                frame.write_line(row, &message, Color::default(), Color::default());
-                continue;
+        let mut line = format!("~{spaces}{text}");
+        line.truncate(self.size.width);
+        line
+    }
+}
+
+impl View for Welcome {
+    fn render_to(&self, frame: &mut crate::render::Frame, theme: &Theme) {
+        let version = format!("Velm editor -- version {VERSION}");
+        let lines: Vec<&str> = [version.as_str(), ""]
+            .iter()
+            .chain(HINTS)
+            .copied()
+            .collect();
+        // Nothing fits on a screen without any cells, which can happen part way through a resize.
+        if self.size.width == 0 || self.size.height == 0 {
+            return;
+        }
+
+        // The block of lines is centered, starting from the top row when the screen is too short
+        // to fit all of it.
+        let top = self.size.height.saturating_sub(lines.len()) / 2;
+
+        for row in 0..self.size.height {
+            let written = match row.checked_sub(top).and_then(|idx| lines.get(idx)) {
+                Some(line) if !line.is_empty() => frame.write_line(
+                    row,
+                    &self.centered(line),
+                    theme.foreground,
+                    theme.background,
+                ),
+                _ => frame.write_line(row, "~", theme.tilde, theme.background),
+            };
+
+            // The rows below one that is outside of the frame are too.
+            if written.is_err() {
+                break;
             }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Welcome;
+    use crate::render::{Frame, View, Viewport};
+    use crate::testing::TestCanvas;
+    use crate::ui::{Rect, Theme};
 
-            frame.write_line(row, "~", Color::Gray, Color::default());
+    #[test]
+    fn tiny_screens_render_without_panicking() {
+        for area in [
+            Rect::new(10, 1),
+            Rect::new(1, 10),
+            Rect::new(0, 0),
+            Rect::new(0, 3),
+        ] {
+            let mut frame = Frame::empty(area);
+            Welcome { size: area }.render_to(&mut frame, &Theme::default());
         }
     }
+
+    #[test]
+    fn version_is_shown_on_a_single_row_screen() {
+        let mut canvas = TestCanvas::new(40, 1);
+        let mut viewport = Viewport::new(&mut canvas).unwrap();
+
+        viewport
+            .render(
+                &Welcome {
+                    size: Rect::new(40, 1),
+                },
+                &Theme::default(),
+            )
+            .unwrap();
+        drop(viewport);
+
+        assert!(canvas.row(0).contains("Velm editor"));
+    }
+
+    #[test]
+    fn lines_are_centered_vertically() {
+        let mut canvas = TestCanvas::new(40, 9);
+        let mut viewport = Viewport::new(&mut canvas).unwrap();
+
+        viewport
+            .render(
+                &Welcome {
+                    size: Rect::new(40, 9),
+                },
+                &Theme::default(),
+            )
+            .unwrap();
+        drop(viewport);
+
+        assert!(canvas.row(2).contains("Velm editor"));
+        assert!(canvas.row(6).contains(":q"));
+        assert_eq!("~", canvas.row(7).trim_end());
+    }
 }
@@ -1,106 +1,1212 @@
-use crate::communication::{Command, Message};
-use crate::component::{Buffer, Component, StatusBar, TextInput, Welcome};
-use crate::document::Document;
-use crate::mode::Mode;
+use crate::communication::{self, Command, Message, FLASH_DURATION};
+use crate::component::{Buffer, Component, Quickfix, QuickfixEntry, StatusBar, TextInput, Welcome};
+use crate::config::Config;
+use crate::document::{Document, LineEnding};
+use crate::grep;
+use crate::mode::{Mode, Normal};
 use crate::render::{Frame, View};
-use crate::ui::{Position, Rect};
+use crate::ui::{LineNumberStyle, Position, Rect, Theme};
+use crate::Row;
 use anyhow::Result;
+use regex::Regex;
+use std::cmp;
+use std::time::{Duration, Instant};
+
+/// How often unsaved changes are written to swap files, like vim's `updatetime`.
+const SWAP_INTERVAL: Duration = Duration::from_secs(4);
+
+/// The number of rows taken up by the quickfix pane when it is open.
+const QUICKFIX_HEIGHT: usize = 6;
+
+/// A message shown on the bottom row of the `Window` until the next command line is entered.
+enum Status {
+    Info(String),
+    Error(String),
+    /// A message that is cleared once the given time has passed.
+    Flash(String, Instant),
+}
 
 /// `Window` is the default root component for the `Editor`.
 pub struct Window {
-    active_buffer_idx: usize,
+    active_pane: usize,
     buffers: Vec<Buffer>,
     command_prompt: TextInput,
+    config: Config,
+    /// The grep whose results are being added to the quickfix list.
+    grep_search: Option<grep::Search>,
     mode: Mode,
+    panes: Vec<usize>,
+    quickfix: Quickfix,
+    quickfix_open: bool,
+    search_prompt: TextInput,
     size: Rect,
+    status: Option<Status>,
+    /// When unsaved changes were last written to swap files.
+    swapped_at: Instant,
 }
 
 impl Window {
-    pub fn new(size: Rect, mode: Mode) -> Self {
+    pub fn new(size: Rect, mode: Mode, config: Config) -> Self {
         let mut command_prompt = TextInput::new(
             ":",
             " Press : to enter a command...",
-            Position::new(0, size.bottom()),
+            Position::new(0, Self::command_line_row(size)),
         );
 
-        if let Mode::Execute(_) = mode {
-            command_prompt.focus();
+        let mut search_prompt =
+            TextInput::new("/", "", Position::new(0, Self::command_line_row(size)));
+
+        match mode {
+            Mode::Execute(_) => command_prompt.focus(),
+            Mode::Search(_) => search_prompt.focus(),
+            _ => (),
         }
 
         Self {
-            active_buffer_idx: 0,
+            active_pane: 0,
             buffers: Vec::default(),
             command_prompt,
+            config,
+            grep_search: None,
             mode,
+            panes: Vec::default(),
+            quickfix: Quickfix::new(Self::quickfix_space_for(size)),
+            quickfix_open: false,
+            search_prompt,
             size,
+            status: None,
+            swapped_at: Instant::now(),
         }
     }
 
+    /// The area above the status bar and command line, and the quickfix pane when it is open,
+    /// that is shared between the panes.
     fn buffer_space(&self) -> Rect {
+        let reserved = if self.quickfix_open {
+            QUICKFIX_HEIGHT + Self::reserved_rows(self.size)
+        } else {
+            Self::reserved_rows(self.size)
+        };
+
+        self.size
+            .split_horizontal(self.size.height.saturating_sub(reserved))
+            .0
+    }
+
+    /// Stack the panes on top of each other within the `buffer_space`, each separated by a
+    /// divider row. Any rows that don't divide evenly go to the last pane.
+    fn pane_spaces(&self) -> Vec<Rect> {
+        let space = self.buffer_space();
+        let count = cmp::max(1, self.panes.len());
+        let available = space.height.saturating_sub(count - 1);
+        let height = available / count;
+
+        (0..count)
+            .map(|idx| {
+                let pane_height = if idx == count - 1 {
+                    available - height * (count - 1)
+                } else {
+                    height
+                };
+
+                Rect::positioned(
+                    space.width,
+                    pane_height,
+                    space.left(),
+                    space.top() + idx * (height + 1),
+                )
+            })
+            .collect()
+    }
+
+    /// The index of the buffer shown in the focused pane.
+    fn active_buffer_idx(&self) -> usize {
+        self.panes.get(self.active_pane).copied().unwrap_or(0)
+    }
+
+    /// The buffer shown in the focused pane, `None` before any file has been opened.
+    pub fn active_buffer(&self) -> Option<&Buffer> {
+        self.buffers.get(self.active_buffer_idx())
+    }
+
+    /// The buffer shown in the focused pane, to be changed from outside of a `Message`.
+    pub fn active_buffer_mut(&mut self) -> Option<&mut Buffer> {
+        let idx = self.active_buffer_idx();
+        self.buffers.get_mut(idx)
+    }
+
+    /// Show the buffer at the given index in the focused pane. If the buffer is already shown in
+    /// another pane then that pane is focused instead.
+    fn set_active_buffer(&mut self, idx: usize) {
+        if let Some(pane) = self.panes.iter().position(|&shown| shown == idx) {
+            self.active_pane = pane;
+        } else if self.panes.is_empty() {
+            self.panes.push(idx);
+            self.active_pane = 0;
+        } else {
+            self.panes[self.active_pane] = idx;
+        }
+
+        self.resize_buffers();
+    }
+
+    /// The number of rows at the bottom taken up by the status bar and command line. The status
+    /// bar is hidden when there isn't room for it along with a row of the buffers.
+    fn reserved_rows(size: Rect) -> usize {
+        if Self::shows_status_bar(size) {
+            2
+        } else {
+            cmp::min(size.height, 1)
+        }
+    }
+
+    fn shows_status_bar(size: Rect) -> bool {
+        size.height >= 3
+    }
+
+    /// The row of the command line, which is the last row of the `Window`.
+    fn command_line_row(size: Rect) -> usize {
+        size.top() + size.height.saturating_sub(1)
+    }
+
+    fn quickfix_space_for(size: Rect) -> Rect {
         Rect::positioned(
-            self.size.width,
-            self.size.height,
-            self.size.left(),
-            self.size.bottom() - 2,
+            size.width,
+            QUICKFIX_HEIGHT,
+            size.left(),
+            size.height.saturating_sub(QUICKFIX_HEIGHT + 2),
         )
     }
+
+    fn set_quickfix_open(&mut self, open: bool) {
+        self.quickfix_open = open;
+        self.resize_buffers();
+    }
+
+    fn resize(&mut self, size: Rect) {
+        self.size = size;
+        self.command_prompt
+            .set_position(Position::new(0, Self::command_line_row(size)));
+        self.search_prompt
+            .set_position(Position::new(0, Self::command_line_row(size)));
+        self.quickfix.resize(Self::quickfix_space_for(size));
+        self.resize_buffers();
+    }
+
+    fn resize_buffers(&mut self) {
+        let buffer_space = self.buffer_space();
+        for buffer in &mut self.buffers {
+            buffer.resize(buffer_space);
+        }
+
+        for (&idx, space) in self.panes.iter().zip(self.pane_spaces()) {
+            self.buffers[idx].resize(space);
+        }
+    }
+
+    /// Switch to the buffer holding the given file, opening it if it is not already loaded.
+    fn open_file(&mut self, file_name: &str) -> Result<Option<Command>> {
+        use anyhow::Context;
+
+        if let Some(idx) = self.buffer_with_file(file_name) {
+            self.set_active_buffer(idx);
+            return Ok(None);
+        }
+
+        let document = Document::open_or_create(file_name)
+            .with_context(|| format!("unable to open file {file_name}"))?;
+
+        if document.has_found_swap() {
+            self.status = Some(Status::Error(format!(
+                "E325: ATTENTION Found a swap file {}, use :recover to restore the unsaved changes",
+                document.swap_file_name().unwrap_or_default()
+            )));
+        }
+
+        let cmd = Self::count_rows(&document);
+
+        self.buffers
+            .push(Buffer::new(self.buffer_space(), document, &self.config));
+        self.set_active_buffer(self.buffers.len() - 1);
+
+        Ok(cmd)
+    }
+
+    /// The rows of a large file are read as they are needed, so the returned `Command` counts
+    /// them to give the length of the whole document without holding up editing.
+    fn count_rows(document: &Document) -> Option<Command> {
+        if document.is_loaded() {
+            return None;
+        }
+
+        let file_name = document.file_name()?.clone();
+
+        Some(Box::new(move || match Document::count_rows(&file_name) {
+            Ok(rows) => Message::RowsCounted { file_name, rows },
+            Err(e) => {
+                Message::StatusError(format!("unable to count the lines of {file_name}: {e}"))
+            }
+        }))
+    }
+
+    /// Write the unsaved changes of each buffer to its swap file, once `SWAP_INTERVAL` has passed
+    /// since they were last written. The files are written by the returned `Command` so that
+    /// editing isn't held up.
+    fn write_swap_files(&mut self) -> Option<Command> {
+        if self.swapped_at.elapsed() < SWAP_INTERVAL {
+            return None;
+        }
+
+        self.swapped_at = Instant::now();
+
+        let mut snapshots = Vec::new();
+
+        for buffer in &mut self.buffers {
+            let Some(file_name) = buffer.document().file_name().cloned() else {
+                continue;
+            };
+
+            match buffer.swap_snapshot() {
+                Ok(Some(snapshot)) => snapshots.push(snapshot),
+                Ok(None) => (),
+                Err(e) => {
+                    self.status = Some(Status::Error(format!(
+                        "E303: Unable to write swap file for {file_name}: {e}"
+                    )));
+                }
+            }
+        }
+
+        if snapshots.is_empty() {
+            return None;
+        }
+
+        Some(Box::new(move || {
+            Message::Batch(
+                snapshots
+                    .into_iter()
+                    .map(|snapshot| match snapshot.write() {
+                        Ok(()) => Message::SwapWritten(snapshot.document()),
+                        Err(e) => Message::StatusError(format!(
+                            "E303: Unable to write swap file {}: {}",
+                            snapshot.file_name(),
+                            e
+                        )),
+                    })
+                    .collect(),
+            )
+        }))
+    }
+
+    /// Switch to the buffer with the (1 based) number, as shown in the status bar.
+    fn go_to_buffer(&mut self, number: usize) {
+        if (1..=self.buffers.len()).contains(&number) {
+            self.set_active_buffer(number - 1);
+        } else {
+            self.status = Some(Status::Error(format!("Buffer {number} does not exist")));
+        }
+    }
+
+    /// Switch to the next, or previous, buffer wrapping around either end of the buffer list.
+    fn cycle_buffer(&mut self, forward: bool) {
+        let count = self.buffers.len();
+
+        if count > 0 {
+            let idx = self.active_buffer_idx();
+            self.set_active_buffer(if forward {
+                (idx + 1) % count
+            } else {
+                (idx + count - 1) % count
+            });
+        }
+    }
+
+    /// The index of the buffer holding the given file, if it has been opened.
+    fn buffer_with_file(&self, file_name: &str) -> Option<usize> {
+        self.buffers
+            .iter()
+            .position(|b| b.document().file_name().map(String::as_str) == Some(file_name))
+    }
+
+    /// Split the focused pane, showing the file, or an empty buffer, in a new pane below it. A
+    /// file that is already open is shown from the same buffer, so that there is only one
+    /// document of it to edit and save. A buffer is only shown in one pane, so the pane already
+    /// showing it is focused instead.
+    fn split(&mut self, file_name: Option<String>) -> Result<Option<Command>> {
+        use anyhow::Context;
+
+        let open = file_name.as_deref().and_then(|f| self.buffer_with_file(f));
+        if let Some(idx) = open.filter(|idx| self.panes.contains(idx)) {
+            self.set_active_buffer(idx);
+            return Ok(None);
+        }
+
+        if self.buffer_space().height / (self.panes.len() + 1) < 2 {
+            self.status = Some(Status::Error("Not enough room to split".into()));
+            return Ok(None);
+        }
+
+        let (idx, cmd) = if let Some(idx) = open {
+            (idx, None)
+        } else {
+            let document = match file_name {
+                Some(file_name) => Document::open_or_create(&file_name)
+                    .with_context(|| format!("unable to open file {file_name}"))?,
+                None => Document::default(),
+            };
+            let cmd = Self::count_rows(&document);
+
+            self.buffers
+                .push(Buffer::new(self.buffer_space(), document, &self.config));
+
+            (self.buffers.len() - 1, cmd)
+        };
+
+        if self.panes.is_empty() {
+            self.panes.push(idx);
+        } else {
+            self.panes.insert(self.active_pane + 1, idx);
+            self.active_pane += 1;
+        }
+
+        self.resize_buffers();
+
+        Ok(cmd)
+    }
+
+    /// Change an option, as with `:set`, for every buffer and any opened later. Options that are
+    /// turned on and off are turned off by prefixing their name with `no`, like vim. The file
+    /// format only applies to the active buffer as it belongs to the file.
+    fn set_option(&mut self, key: &str, value: Option<&str>) -> Result<()> {
+        let (name, on) = match key.strip_prefix("no") {
+            Some(name) => (name, false),
+            None => (key, true),
+        };
+
+        match (name, value) {
+            ("wrap", None) => {
+                self.config.wrap = on;
+
+                for buffer in &mut self.buffers {
+                    buffer.set_wrap(on);
+                }
+            }
+            ("cursorline" | "cul", None) => {
+                self.config.cursor_line = on;
+
+                for buffer in &mut self.buffers {
+                    buffer.set_cursor_line(on);
+                }
+            }
+            ("smarthome", None) => {
+                self.config.smart_home = on;
+
+                for buffer in &mut self.buffers {
+                    buffer.set_smart_home(on);
+                }
+            }
+            ("list", None) => {
+                self.config.list = on;
+
+                for buffer in &mut self.buffers {
+                    buffer.set_list(on);
+                }
+            }
+            ("expandtab" | "et", None) => {
+                self.config.expand_tab = on;
+
+                for buffer in &mut self.buffers {
+                    buffer.set_expand_tab(on);
+                }
+            }
+            ("autoindent" | "ai", None) => {
+                self.config.auto_indent = on;
+
+                for buffer in &mut self.buffers {
+                    buffer.set_auto_indent(on);
+                }
+            }
+            // Like vim, `number` and `relativenumber` are separate options that combine into the
+            // hybrid style when both are on.
+            ("number" | "nu" | "relativenumber" | "rnu", None) => {
+                let (mut number, mut relative) = match self.config.line_numbers {
+                    None => (false, false),
+                    Some(LineNumberStyle::Absolute) => (true, false),
+                    Some(LineNumberStyle::Relative) => (false, true),
+                    Some(LineNumberStyle::Hybrid) => (true, true),
+                };
+
+                if let "number" | "nu" = name {
+                    number = on;
+                } else {
+                    relative = on;
+                }
+
+                let line_numbers = match (number, relative) {
+                    (false, false) => None,
+                    (true, false) => Some(LineNumberStyle::Absolute),
+                    (false, true) => Some(LineNumberStyle::Relative),
+                    (true, true) => Some(LineNumberStyle::Hybrid),
+                };
+
+                self.config.line_numbers = line_numbers;
+
+                for buffer in &mut self.buffers {
+                    buffer.set_line_numbers(line_numbers);
+                }
+            }
+            ("tabstop" | "ts", Some(value)) if on => {
+                let tab_width = match value.parse::<usize>() {
+                    Ok(tab_width) if tab_width > 0 => tab_width,
+                    _ => anyhow::bail!("E487: Argument must be positive: {}={}", key, value),
+                };
+
+                self.config.tab_width = tab_width;
+
+                for buffer in &mut self.buffers {
+                    buffer.set_tab_width(tab_width);
+                }
+            }
+            ("scrolloff" | "so", Some(value)) if on => {
+                let scroll_off = value.parse::<usize>().map_err(|_| {
+                    anyhow::anyhow!("E521: Number required after =: {}={}", key, value)
+                })?;
+
+                self.config.scroll_off = scroll_off;
+
+                for buffer in &mut self.buffers {
+                    buffer.set_scroll_off(scroll_off);
+                }
+            }
+            ("fileformat" | "ff", Some(value)) if on => {
+                let line_ending = match value {
+                    "unix" => LineEnding::Lf,
+                    "dos" => LineEnding::CrLf,
+                    _ => anyhow::bail!("E474: Invalid argument: {}={}", key, value),
+                };
+
+                let idx = self.active_buffer_idx();
+                if let Some(buffer) = self.buffers.get_mut(idx) {
+                    buffer.set_line_ending(line_ending);
+                }
+            }
+            _ => anyhow::bail!("E518: Unknown option: {}", key),
+        }
+
+        Ok(())
+    }
+
+    /// Populate the quickfix list with every line in the active buffer that matches the pattern.
+    fn global_search(&mut self, pattern: &str) -> Result<Option<Command>> {
+        use anyhow::Context;
+
+        let regex = match Regex::new(pattern) {
+            Ok(regex) => regex,
+            Err(e) => {
+                return Ok(Some(communication::wrap(Message::StatusError(format!(
+                    "invalid search pattern {pattern}: {e}"
+                )))))
+            }
+        };
+
+        let idx = self.active_buffer_idx();
+        let loaded = match self.buffers.get_mut(idx) {
+            Some(buffer) => buffer.load_all(),
+            None => return Ok(None),
+        };
+        if let Err(e) = loaded.context("unable to read the rest of the document") {
+            return Ok(self.show_error(Err(e)));
+        }
+
+        let entries: Vec<QuickfixEntry> = match self.buffers.get(idx) {
+            Some(buffer) => {
+                let document = buffer.document();
+
+                // Like vim, each matching line is listed once however many matches it has.
+                (0..document.len())
+                    .filter_map(|row| {
+                        let contents = document.row(row).map_or(String::new(), Row::contents);
+
+                        grep::find(&regex, &contents).map(|col| {
+                            QuickfixEntry::new(
+                                document.file_name().cloned(),
+                                Position::new(col, row),
+                                &contents,
+                            )
+                        })
+                    })
+                    .collect()
+            }
+            None => return Ok(None),
+        };
+
+        self.quickfix.update(Message::SetQuickfixList(entries))?;
+        self.set_quickfix_open(true);
+        self.jump_to_quickfix_entry()
+    }
+
+    /// Clear the quickfix list and start searching the files under the path that match the glob.
+    /// The search runs as a chain of `Command`s, one per file, so results stream in without
+    /// blocking the `Editor`.
+    fn grep(&mut self, path: &str, pattern: &str, glob: Option<&str>) -> Result<Option<Command>> {
+        use anyhow::Context;
+
+        let regex =
+            Regex::new(pattern).with_context(|| format!("invalid search pattern {pattern}"))?;
+        let search = grep::Search::start_in(path, regex, glob)?;
+
+        self.quickfix.update(Message::SetQuickfixList(Vec::new()))?;
+        self.set_quickfix_open(true);
+        self.grep_search = Some(search.clone());
+
+        Ok(Some(Self::next_grep_batch(search)))
+    }
+
+    fn grep_progress(
+        &mut self,
+        search: grep::Search,
+        entries: Vec<QuickfixEntry>,
+    ) -> Result<Option<Command>> {
+        // The results of an earlier grep are dropped once another has started.
+        if self.grep_search.as_ref() != Some(&search) {
+            return Ok(None);
+        }
+
+        if entries.is_empty() {
+            self.grep_search = None;
+            return Ok(None);
+        }
+
+        let was_empty = self.quickfix.is_empty();
+
+        self.quickfix
+            .update(Message::AppendQuickfixEntries(entries))?;
+
+        if was_empty && !self.quickfix.is_empty() {
+            self.jump_to_quickfix_entry()?;
+        }
+
+        Ok(Some(Self::next_grep_batch(search)))
+    }
+
+    /// A command that waits for the next batch of matches from the search.
+    fn next_grep_batch(search: grep::Search) -> Command {
+        Box::new(move || {
+            let entries = search.next_batch();
+            Message::GrepProgress(search, entries)
+        })
+    }
+
+    fn jump_to_quickfix_entry(&mut self) -> Result<Option<Command>> {
+        let entry = match self.quickfix.selected() {
+            Some(entry) => entry.clone(),
+            None => return Ok(None),
+        };
+
+        if let Some(file_name) = entry.file_name {
+            self.update(Message::OpenFile(file_name))?;
+        }
+
+        self.update(Message::GotoPosition(entry.position))
+    }
+
+    /// Switch to the mode, focusing the prompt that it reads input from. Insert mode needs a
+    /// buffer to type into so one is created if there are none.
+    fn enter_mode(&mut self, mode: Mode) {
+        if let Mode::Insert(_) = mode {
+            if self.buffers.is_empty() {
+                self.buffers.push(Buffer::new(
+                    self.buffer_space(),
+                    Document::default(),
+                    &self.config,
+                ));
+                self.set_active_buffer(0);
+            }
+        }
+
+        self.command_prompt.unfocus();
+        self.search_prompt.unfocus();
+
+        match mode {
+            Mode::Execute(_) => {
+                self.status = None;
+                self.command_prompt.focus();
+            }
+            Mode::Search(_) => {
+                self.status = None;
+                self.search_prompt.focus();
+            }
+            _ => (),
+        }
+
+        self.mode = mode;
+    }
+
+    /// Pass the message to the prompt that the current mode reads input from.
+    fn update_prompt(&mut self, msg: Message) -> Result<Option<Command>> {
+        let prompt = if let Mode::Search(_) = self.mode {
+            &mut self.search_prompt
+        } else {
+            &mut self.command_prompt
+        };
+
+        // The `Editor` returns to Normal mode once the command line input has been parsed, so
+        // we follow suit here to ensure the resulting `Message` reaches the active buffer.
+        if let Message::EndCommandLineInput = msg {
+            prompt.unfocus();
+            self.mode = Mode::Normal(Normal::default());
+        }
+
+        prompt.update(msg)
+    }
+
+    /// Quit unless a buffer has unsaved changes, which are reported instead.
+    fn quit(&mut self) -> Option<Command> {
+        if self.buffers.iter().any(Buffer::is_modified) {
+            self.status = Some(Status::Error(
+                "No write since last change (add ! to override)".into(),
+            ));
+            return None;
+        }
+
+        Some(communication::wrap(Message::ForceQuit))
+    }
+
+    /// Pass the message about a file that has been written to the buffer that it was written
+    /// from. Another buffer may have become active while the file was being written, and more
+    /// than one may have a document of the same file, so the buffer is found by its document.
+    fn update_saved_buffer(&mut self, msg: Message) -> Result<Option<Command>> {
+        let saved = match msg {
+            Message::Saved { document, .. } | Message::SwapWritten(document) => self
+                .buffers
+                .iter_mut()
+                .find(|b| b.document().id() == document),
+            _ => None,
+        };
+
+        match saved {
+            Some(buffer) => buffer.update(msg),
+            None => Ok(None),
+        }
+    }
+
+    /// Show, or clear, the message on the status line.
+    fn show_status(&mut self, msg: Message) {
+        match msg {
+            Message::StatusInfo(status_message) => {
+                self.status = Some(Status::Info(status_message));
+            }
+            Message::StatusError(error) => self.status = Some(Status::Error(error)),
+            Message::Flash(text) => {
+                self.status = Some(Status::Flash(text, Instant::now() + FLASH_DURATION));
+            }
+            Message::ClearFlash => {
+                // A newer flash may have replaced the one that this clear was scheduled for.
+                if let Some(Status::Flash(_, expires_at)) = self.status {
+                    if Instant::now() >= expires_at {
+                        self.status = None;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Show the error on the status line rather than passing it up to the `Editor`, which would
+    /// stop the editor.
+    fn show_error(&mut self, result: Result<Option<Command>>) -> Option<Command> {
+        result.unwrap_or_else(|e| {
+            self.status = Some(Status::Error(format!("{e:#}")));
+            None
+        })
+    }
+
+    /// Make the pane that was clicked on the active pane.
+    fn focus_pane_at(&mut self, position: Position) {
+        if let Some(pane) = self
+            .pane_spaces()
+            .iter()
+            .position(|space| space.contains(&position))
+        {
+            if pane < self.panes.len() {
+                self.active_pane = pane;
+            }
+        }
+    }
+
+    /// Open, close or move through the quickfix list, jumping to the selected entry.
+    fn update_quickfix(&mut self, msg: Message) -> Result<Option<Command>> {
+        match msg {
+            Message::SetQuickfixList(_) => {
+                self.set_quickfix_open(true);
+                self.quickfix.update(msg)
+            }
+            Message::QuickfixNext | Message::QuickfixPrevious => {
+                self.quickfix.update(msg)?;
+                self.jump_to_quickfix_entry()
+            }
+            Message::QuickfixOpen => {
+                self.set_quickfix_open(true);
+                Ok(None)
+            }
+            Message::QuickfixClose => {
+                self.set_quickfix_open(false);
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
 }
 
 impl Component for Window {
     fn update(&mut self, msg: Message) -> Result<Option<Command>> {
-        if let Message::EnterMode(mode) = msg.clone() {
-            if let Mode::Insert(_) = mode {
-                if self.buffers.is_empty() {
-                    self.buffers
-                        .push(Buffer::new(self.buffer_space(), Document::default()));
+        if let Message::EnterMode(mode) = &msg {
+            self.enter_mode(mode.clone());
+        }
+
+        if let Message::Resize(size) = msg {
+            self.resize(size);
+            return Ok(None);
+        }
+
+        // Unsaved changes are written to swap files every so often, so that they can be recovered
+        // if the editor stops without saving them.
+        if let Message::Tick = msg {
+            return Ok(self.write_swap_files());
+        }
+
+        // Search results stream in asynchronously so they must be handled regardless of mode.
+        if let Message::GrepProgress(search, entries) = msg {
+            return self.grep_progress(search, entries);
+        }
+
+        if let Mode::Execute(_) | Mode::Search(_) = self.mode {
+            return self.update_prompt(msg);
+        }
+
+        match msg {
+            Message::StatusInfo(_)
+            | Message::StatusError(_)
+            | Message::Flash(_)
+            | Message::ClearFlash => {
+                self.show_status(msg);
+                return Ok(None);
+            }
+            Message::Quit => return Ok(self.quit()),
+            Message::OpenFile(file_name) => {
+                let opened = self.open_file(&file_name);
+                return Ok(self.show_error(opened));
+            }
+            Message::ViewFile(file_name) => {
+                let opened = self.open_file(&file_name).inspect(|_| {
+                    let idx = self.active_buffer_idx();
+                    self.buffers[idx].set_read_only(true);
+                });
+                return Ok(self.show_error(opened));
+            }
+            Message::RowsCounted { file_name, rows } => {
+                for buffer in &mut self.buffers {
+                    if buffer.document().file_name() == Some(&file_name) {
+                        buffer.set_row_count(rows);
+                    }
                 }
+
+                return Ok(None);
+            }
+            Message::NextBuffer | Message::PrevBuffer => {
+                self.cycle_buffer(msg == Message::NextBuffer);
+                return Ok(None);
+            }
+            Message::GoToBuffer(number) => {
+                self.go_to_buffer(number);
+                return Ok(None);
+            }
+            Message::Split(file_name) => {
+                let split = self.split(file_name);
+                return Ok(self.show_error(split));
             }
+            Message::SetOption { key, value } => {
+                let set = self.set_option(&key, value.as_deref()).map(|()| None);
+                return Ok(self.show_error(set));
+            }
+            // Like vim, the highlighting is hidden in every buffer rather than just the active one.
+            Message::ClearSearchHighlight => {
+                for buffer in &mut self.buffers {
+                    buffer.update(Message::ClearSearchHighlight)?;
+                }
 
-            if let Mode::Execute(_) = mode {
-                self.command_prompt.focus();
-            } else {
-                self.command_prompt.unfocus();
+                return Ok(None);
             }
+            Message::FocusNextPane => {
+                if !self.panes.is_empty() {
+                    self.active_pane = (self.active_pane + 1) % self.panes.len();
+                }
 
-            self.mode = mode;
-        }
+                return Ok(None);
+            }
+            Message::MouseClick(position) => self.focus_pane_at(position),
+            Message::GlobalSearch(pattern) => return self.global_search(&pattern),
+            Message::Grep(pattern, glob) => return self.grep(".", &pattern, glob.as_deref()),
+            Message::SetQuickfixList(_)
+            | Message::QuickfixNext
+            | Message::QuickfixPrevious
+            | Message::QuickfixOpen
+            | Message::QuickfixClose => return self.update_quickfix(msg),
+            Message::GotoPosition(_) if self.buffers.is_empty() => return Ok(None),
+            Message::SaveQuit { .. } if self.buffers.is_empty() => {
+                return Ok(Some(communication::wrap(Message::Quit)))
+            }
+            // Changes that are thrown away don't need recovering.
+            Message::ForceQuit => {
+                for buffer in &mut self.buffers {
+                    buffer.remove_swap();
+                }
 
-        if let Mode::Execute(_) = self.mode {
-            return self.command_prompt.update(msg);
+                return Ok(None);
+            }
+            Message::Saved { .. } | Message::SwapWritten(_) => {
+                return self.update_saved_buffer(msg)
+            }
+            _ => (),
         }
 
-        self.buffers[self.active_buffer_idx].update(msg)
+        let idx = self.active_buffer_idx();
+        match self.buffers.get_mut(idx) {
+            Some(buffer) => buffer.update(msg),
+            None => Ok(None),
+        }
     }
 }
 
 impl View for Window {
-    fn render_to(&self, frame: &mut Frame) {
+    fn render_to(&self, frame: &mut Frame, theme: &Theme) {
         if self.buffers.is_empty() {
             Welcome {
                 size: self.buffer_space(),
             }
-            .render_to(frame);
+            .render_to(frame, theme);
         } else {
-            self.buffers[self.active_buffer_idx].render_to(frame);
+            let pane_spaces = self.pane_spaces();
+
+            for (pane, (&idx, space)) in self.panes.iter().zip(&pane_spaces).enumerate() {
+                let buffer = &self.buffers[idx];
+                buffer.render_to(frame, theme);
+
+                // Every pane but the last is followed by a divider naming the buffer above it.
+                if pane + 1 < self.panes.len() {
+                    let divider = format!(
+                        " {}{}{}",
+                        buffer.document_name(),
+                        if buffer.is_modified() { " [+]" } else { "" },
+                        if buffer.is_read_only() { " [RO]" } else { "" }
+                    );
+                    let (foreground, background) = if pane == self.active_pane {
+                        (theme.active_divider_fg, theme.active_divider_bg)
+                    } else {
+                        (theme.divider_fg, theme.divider_bg)
+                    };
+
+                    let row = space.top() + space.height;
+                    let _ = frame.write_line(row, &divider, foreground, background);
+                }
+            }
+        }
+
+        if self.quickfix_open {
+            self.quickfix.render_to(frame, theme);
         }
 
-        if let Mode::Normal(_) | Mode::Insert(_) = self.mode {
+        if let Mode::Normal(_) | Mode::Insert(_) | Mode::Replace(_) | Mode::Visual(_) = self.mode {
             frame.set_cursor_position(if self.buffers.is_empty() {
                 Position::default()
             } else {
-                self.buffers[self.active_buffer_idx].cursor_position()
+                self.buffers[self.active_buffer_idx()].cursor_position()
             });
         }
 
-        StatusBar {
-            area: Rect::positioned(self.size.width, 1, self.size.left(), self.size.bottom() - 1),
-            mode: self.mode.to_string(),
-            line_count: 0,
-            cursor_position: frame.cursor_position(),
-            file_name: "".to_string(),
+        if Self::shows_status_bar(self.size) {
+            let active_buffer = self.buffers.get(self.active_buffer_idx());
+            let (file_name, line_count, modified, read_only) =
+                active_buffer.map_or(("[No Name]".to_string(), 0, false, false), |buffer| {
+                    (
+                        buffer.document_name(),
+                        buffer.lines_in_document(),
+                        buffer.is_modified(),
+                        buffer.is_read_only(),
+                    )
+                });
+            let (visible_lines, cursor_position) = active_buffer
+                .map_or((0, Position::default()), |buffer| {
+                    (buffer.visible_lines(), buffer.document_cursor_position())
+                });
+
+            StatusBar {
+                area: Rect::positioned(
+                    self.size.width,
+                    1,
+                    self.size.left(),
+                    self.size.bottom() - 1,
+                ),
+                mode: self.mode.clone(),
+                line_count,
+                partially_loaded: active_buffer
+                    .is_some_and(|buffer| buffer.document().total_len().is_none()),
+                visible_lines,
+                cursor_position,
+                file_name,
+                modified,
+                read_only,
+                buffer_number: self.active_buffer_idx() + 1,
+                buffer_count: self.buffers.len(),
+                char_count: active_buffer.map(|buffer| buffer.document().char_count()),
+                selected_char_count: active_buffer.and_then(Buffer::selected_char_count),
+            }
+            .render_to(frame, theme);
+        }
+
+        if let Mode::Search(_) = self.mode {
+            self.search_prompt.render_to(frame, theme);
+        } else {
+            self.command_prompt.render_to(frame, theme);
+        }
+
+        if let (
+            Some(status),
+            Mode::Normal(_) | Mode::Insert(_) | Mode::Replace(_) | Mode::Visual(_),
+        ) = (&self.status, &self.mode)
+        {
+            let (text, foreground, background) = match status {
+                Status::Info(text) | Status::Flash(text, _) => {
+                    (text, theme.foreground, theme.background)
+                }
+                Status::Error(text) => (text, theme.error_fg, theme.error_bg),
+            };
+
+            let row = Self::command_line_row(self.size);
+            let _ = frame.write_line(row, text, foreground, background);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Window;
+    use crate::communication::Message;
+    use crate::component::Component;
+    use crate::config::Config;
+    use crate::mode::{Mode, Normal};
+    use crate::render::Viewport;
+    use crate::testing::{TempFile, TestCanvas};
+    use crate::ui::{Rect, Theme};
+
+    fn render(window: &Window, canvas: &mut TestCanvas) {
+        let mut viewport = Viewport::new(canvas).unwrap();
+        viewport.render(window, &Theme::default()).unwrap();
+    }
+
+    #[test]
+    fn short_terminals_render_without_the_status_bar() {
+        let file_name = TempFile::new("window-missing.txt");
+
+        for height in 1..=2 {
+            let size = Rect::new(40, height);
+            let mut window = Window::new(size, Mode::Normal(Normal::default()), Config::default());
+            let mut canvas = TestCanvas::new(40, height);
+            render(&window, &mut canvas);
+
+            window
+                .update(Message::OpenFile(file_name.to_string()))
+                .unwrap();
+            render(&window, &mut canvas);
+
+            assert_eq!(height - 1, window.buffer_space().height);
+            assert!(canvas.row(height - 1).starts_with(" Press : to enter"));
+            assert!(!canvas.rows().iter().any(|row| row.contains("Mode:")));
+        }
+    }
+
+    #[test]
+    fn the_status_bar_is_shown_once_there_is_room_for_it() {
+        let window = Window::new(
+            Rect::new(40, 3),
+            Mode::Normal(Normal::default()),
+            Config::default(),
+        );
+        let mut canvas = TestCanvas::new(40, 3);
+        render(&window, &mut canvas);
+
+        assert_eq!(1, window.buffer_space().height);
+        assert!(canvas.row(1).starts_with("Mode: [NORMAL]"));
+    }
+
+    #[test]
+    fn global_search_lists_each_line_matching_the_pattern() {
+        let file_name = TempFile::new("window-global.txt");
+        std::fs::write(&file_name, "let a;\nfn main() {}\nfn  run() { main() }\n").unwrap();
+
+        let mut window = Window::new(
+            Rect::new(40, 10),
+            Mode::Normal(Normal::default()),
+            Config::default(),
+        );
+        window
+            .update(Message::OpenFile(file_name.to_string()))
+            .unwrap();
+        window
+            .update(Message::GlobalSearch(r"fn\s+\w+".into()))
+            .unwrap();
+
+        let mut rows = Vec::new();
+        for _ in 0..3 {
+            rows.push(window.quickfix.selected().unwrap().position.row);
+            window.quickfix.update(Message::QuickfixNext).unwrap();
+        }
+        assert_eq!(vec![1, 2, 2], rows);
+    }
+
+    #[test]
+    fn global_search_lists_matches_in_rows_of_a_large_file_that_have_not_been_read() {
+        let file_name = TempFile::new("window-global-large.txt");
+        std::fs::write(&file_name, "line\n".repeat(1_000_000) + "needle\n").unwrap();
+
+        let mut window = Window::new(
+            Rect::new(40, 10),
+            Mode::Normal(Normal::default()),
+            Config::default(),
+        );
+        window
+            .update(Message::OpenFile(file_name.to_string()))
+            .unwrap();
+        window
+            .update(Message::GlobalSearch("needle".into()))
+            .unwrap();
+
+        assert_eq!(1_000_000, window.quickfix.selected().unwrap().position.row);
+    }
+
+    #[test]
+    fn global_search_reports_invalid_patterns() {
+        let mut window = Window::new(
+            Rect::new(40, 10),
+            Mode::Normal(Normal::default()),
+            Config::default(),
+        );
+
+        match window
+            .update(Message::GlobalSearch("(".into()))
+            .unwrap()
+            .map(|cmd| cmd())
+        {
+            Some(Message::StatusError(error)) => {
+                assert!(error.starts_with("invalid search pattern ("));
+            }
+            msg => panic!("expected a status error, got {:?}", msg),
         }
-        .render_to(frame);
+    }
+
+    #[test]
+    fn results_from_an_earlier_grep_are_dropped() {
+        use crate::component::QuickfixEntry;
+        use crate::grep::Search;
+        use crate::ui::Position;
+        use regex::Regex;
+
+        let mut window = Window::new(
+            Rect::new(40, 10),
+            Mode::Normal(Normal::default()),
+            Config::default(),
+        );
+        let file_name = TempFile::new("window-grep.txt");
+        std::fs::write(&file_name, "a\nb\n").unwrap();
+
+        let earlier = Search::start_in(&file_name, Regex::new("a").unwrap(), None).unwrap();
+        window.grep(&file_name, "b", None).unwrap();
+
+        let entry = QuickfixEntry::new(None, Position::default(), "a");
+        let cmd = window
+            .update(Message::GrepProgress(earlier, vec![entry]))
+            .unwrap();
+
+        assert!(cmd.is_none());
+        assert!(window.quickfix.is_empty());
+    }
+
+    #[test]
+    fn the_lines_of_large_files_are_counted_once_they_are_opened() {
+        let file_name = TempFile::new("window-large.txt");
+        std::fs::write(&file_name, "line\n".repeat(1_000_000)).unwrap();
+
+        let mut window = Window::new(
+            Rect::new(40, 10),
+            Mode::Normal(Normal::default()),
+            Config::default(),
+        );
+        let count = window
+            .update(Message::OpenFile(file_name.to_string()))
+            .unwrap()
+            .unwrap();
+        window.update(count()).unwrap();
+
+        let buffer = &window.buffers[window.active_buffer_idx()];
+        assert!(!buffer.document().is_loaded());
+        assert_eq!(1_000_000, buffer.lines_in_document());
+    }
+
+    #[test]
+    fn saves_are_reported_to_the_buffer_that_wrote_them() {
+        let file_name = TempFile::new("window-saved.txt");
+        std::fs::write(&file_name, "first\n").unwrap();
+
+        let mut window = Window::new(
+            Rect::new(40, 10),
+            Mode::Normal(Normal::default()),
+            Config::default(),
+        );
+        window
+            .update(Message::OpenFile(file_name.to_string()))
+            .unwrap();
+        window.update(Message::InsertChar('!')).unwrap();
+
+        // The new buffer has a document of the same file once it is saved over it.
+        window.update(Message::Split(None)).unwrap();
+        window.update(Message::InsertChar('?')).unwrap();
+        let write = window
+            .update(Message::SaveAs(file_name.to_string()))
+            .unwrap()
+            .unwrap();
+        window.update(write()).unwrap();
+
+        assert!(window.buffers[0].is_modified());
+        assert!(!window.buffers[1].is_modified());
+    }
+
+    #[test]
+    fn splitting_an_open_file_shows_its_buffer() {
+        let first = TempFile::new("window-split-first.txt");
+        let second = TempFile::new("window-split-second.txt");
+
+        let mut window = Window::new(
+            Rect::new(40, 10),
+            Mode::Normal(Normal::default()),
+            Config::default(),
+        );
+        window.update(Message::OpenFile(first.to_string())).unwrap();
+        window
+            .update(Message::OpenFile(second.to_string()))
+            .unwrap();
+
+        window
+            .update(Message::Split(Some(first.to_string())))
+            .unwrap();
+        assert_eq!(2, window.buffers.len());
+        assert_eq!(vec![1, 0], window.panes);
+        assert_eq!(0, window.active_buffer_idx());
 
-        self.command_prompt.render_to(frame);
+        // A buffer that is already shown has its pane focused rather than being split again.
+        window
+            .update(Message::Split(Some(second.to_string())))
+            .unwrap();
+        assert_eq!(vec![1, 0], window.panes);
+        assert_eq!(1, window.active_buffer_idx());
     }
 }
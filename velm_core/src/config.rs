@@ -0,0 +1,219 @@
+use crate::ui::{LineNumberStyle, Theme};
+use anyhow::Result;
+use serde::{de, Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// `Config` holds the user's preferences for the `Editor`. It is read from `velm/config.toml`
+/// within the user's config directory, e.g. `~/.config/velm/config.toml`. Anything missing from
+/// the file keeps its default value.
+// Each bool is an option that is set on its own, like the options of vim's `:set`.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The number of columns between each tab stop when rendering tabs.
+    pub tab_width: usize,
+    /// How line numbers are displayed in the gutter, `None` hides the gutter. Written as
+    /// `"absolute"`, `"relative"`, `"hybrid"` or `"off"` in the config file.
+    #[serde(deserialize_with = "line_numbers")]
+    pub line_numbers: Option<LineNumberStyle>,
+    /// Wrap lines that are too long for the screen onto the following rows rather than
+    /// scrolling horizontally.
+    pub wrap: bool,
+    /// Highlight the line that the cursor is on.
+    pub cursor_line: bool,
+    /// Make Home alternate between the first non-blank character of the line and its start,
+    /// rather than always going to the start.
+    pub smart_home: bool,
+    /// Show tabs and trailing spaces, like vim's `list` option.
+    pub list: bool,
+    /// The number of lines to keep in view above and below the cursor when scrolling.
+    pub scroll_off: usize,
+    /// Insert spaces up to the next tab stop when Tab is pressed, rather than a tab.
+    pub expand_tab: bool,
+    /// Start each new line with the same leading whitespace as the line it was split from.
+    pub auto_indent: bool,
+    pub theme: Theme,
+    pub keymap: Mappings,
+}
+
+/// Key mappings for each mode, from the keys that are pressed to the keys that are handled in
+/// their place. Keys are written as they are typed with special keys in angle brackets, e.g.
+/// `jk = "<Esc>"` under `[keymap.insert]`. The `command` mappings apply to the command line.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Mappings {
+    pub normal: BTreeMap<String, String>,
+    pub insert: BTreeMap<String, String>,
+    pub visual: BTreeMap<String, String>,
+    pub command: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// Load the config file from the user's config directory. The default `Config` is used when
+    /// there is no config file.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` when the config file exists but can not be read or is invalid.
+    pub fn load() -> Result<Self> {
+        match Self::path() {
+            Some(path) => Self::from_file(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Load the config from the given file, the default `Config` is used when the file does not
+    /// exist.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` when the file can not be read or is invalid.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        use anyhow::Context;
+
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("unable to read config file {}", path.display()))
+            }
+        };
+
+        Self::parse(&contents).with_context(|| format!("invalid config file {}", path.display()))
+    }
+
+    /// Parse the config from the contents of a TOML file.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` when the contents are not valid TOML or contain an invalid setting.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let config: Self = toml::from_str(contents)?;
+
+        if config.tab_width == 0 {
+            anyhow::bail!("tab_width must be at least 1");
+        }
+
+        Ok(config)
+    }
+
+    /// The location of the config file, `$XDG_CONFIG_HOME/velm/config.toml` falling back to
+    /// `$HOME/.config/velm/config.toml`.
+    fn path() -> Option<PathBuf> {
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_dir.join("velm").join("config.toml"))
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tab_width: 4,
+            line_numbers: Some(LineNumberStyle::default()),
+            wrap: false,
+            cursor_line: false,
+            smart_home: false,
+            list: false,
+            scroll_off: 0,
+            expand_tab: false,
+            auto_indent: true,
+            theme: Theme::default(),
+            keymap: Mappings::default(),
+        }
+    }
+}
+
+fn line_numbers<'de, D>(deserializer: D) -> Result<Option<LineNumberStyle>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    const STYLES: &[&str] = &["absolute", "relative", "hybrid", "off"];
+
+    match String::deserialize(deserializer)?.as_str() {
+        "absolute" => Ok(Some(LineNumberStyle::Absolute)),
+        "relative" => Ok(Some(LineNumberStyle::Relative)),
+        "hybrid" => Ok(Some(LineNumberStyle::Hybrid)),
+        "off" => Ok(None),
+        other => Err(de::Error::unknown_variant(other, STYLES)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::ui::{Color, LineNumberStyle, Theme};
+
+    #[test]
+    fn empty_config_uses_the_defaults() {
+        assert_eq!(Config::default(), Config::parse("").unwrap());
+    }
+
+    #[test]
+    fn settings_are_parsed() {
+        let config = Config::parse(
+            r#"
+            tab_width = 8
+            line_numbers = "relative"
+            auto_indent = false
+
+            [theme]
+            foreground = "white"
+            line_number = "dark-gray"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Config {
+                tab_width: 8,
+                line_numbers: Some(LineNumberStyle::Relative),
+                auto_indent: false,
+                theme: Theme {
+                    foreground: Color::White,
+                    ..Theme::default()
+                },
+                ..Config::default()
+            },
+            config
+        );
+    }
+
+    #[test]
+    fn key_mappings_are_parsed() {
+        let config = Config::parse("[keymap.insert]\njk = \"<Esc>\"").unwrap();
+
+        assert_eq!(Some(&"<Esc>".to_string()), config.keymap.insert.get("jk"));
+    }
+
+    #[test]
+    fn line_numbers_can_be_turned_off() {
+        assert_eq!(
+            None,
+            Config::parse("line_numbers = \"off\"")
+                .unwrap()
+                .line_numbers
+        );
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        assert!(Config::parse("tab_width = 0").is_err());
+        assert!(Config::parse("line_numbers = \"sideways\"").is_err());
+        assert!(Config::parse("[theme]\nforeground = \"chartreuse\"").is_err());
+        assert!(Config::parse("unknown = true").is_err());
+    }
+
+    #[test]
+    fn missing_config_file_uses_the_defaults() {
+        let path = std::env::temp_dir().join(format!("velm-{}-missing.toml", std::process::id()));
+
+        assert_eq!(Config::default(), Config::from_file(&path).unwrap());
+    }
+}
@@ -1,27 +1,344 @@
 use crate::{row::Row, ui::Position};
 use anyhow::{Error, Result};
+use std::cmp;
+use std::collections::{BTreeMap, VecDeque};
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How the rows of a `Document` are stored, a gap buffer with the `gap-buffer` feature.
+#[cfg(not(feature = "gap-buffer"))]
+type Rows = Vec<Row>;
+#[cfg(feature = "gap-buffer")]
+type Rows = crate::gap_buffer::GapBuffer<Row>;
+
+/// Files larger than this many bytes are read a chunk of rows at a time, as the rows are needed,
+/// rather than all at once when they are opened.
+const LAZY_LOAD_SIZE: u64 = 4 * 1024 * 1024;
+
+/// The number of rows read when a large file is opened, enough to fill the first screen.
+const INITIAL_ROWS: usize = 1000;
+
+/// The most edits that can be undone, like vim's default `undolevels`. The oldest edit is
+/// forgotten once another is made.
+const MAX_UNDO_DEPTH: usize = 1000;
+
+/// The sequence of characters that terminates each row when the `Document` is written to disk.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// Detect the line ending used by the majority of lines in the contents, preferring `Lf` when
+    /// there are no line breaks or it is a tie.
+    fn detect(contents: &str) -> Self {
+        let crlf = contents.matches("\r\n").count();
+        let lf = contents.matches('\n').count() - crlf;
+
+        if crlf > lf {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+
+    fn as_bytes(self) -> &'static [u8] {
+        self.as_str().as_bytes()
+    }
+}
+
+/// The number of words, lines and characters in a `Document`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct WordCount {
+    pub words: usize,
+    pub lines: usize,
+    pub chars: usize,
+}
+
+/// Numbers each `Snapshot` in the order that they are taken, which is the order that they should
+/// be written in.
+static SNAPSHOTS_TAKEN: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts the `Document`s created, so that each one is given its own id.
+static DOCUMENTS_CREATED: AtomicUsize = AtomicUsize::new(0);
+
+/// The rows of a `Document` as they were when it was saved, so that they can be written to disk
+/// away from the `Editor` while the `Document` carries on being edited.
+pub struct Snapshot {
+    file_name: String,
+    rows: Rows,
+    line_ending: LineEnding,
+    trailing_newline: bool,
+    revision: usize,
+    number: usize,
+    last_written: LastWritten,
+    document: usize,
+}
+
+impl Snapshot {
+    fn new(document: &Document, file_name: String) -> Self {
+        Self {
+            file_name,
+            rows: document.rows.clone(),
+            line_ending: document.line_ending,
+            trailing_newline: document.trailing_newline,
+            revision: document.revision,
+            number: SNAPSHOTS_TAKEN.fetch_add(1, Ordering::Relaxed),
+            last_written: Arc::clone(&document.last_written),
+            document: document.id,
+        }
+    }
+
+    /// Write the rows to the file. The rows are written to a temporary file alongside the target
+    /// which is then renamed over it, so the original file is left untouched if anything goes
+    /// wrong part way through. Nothing is written if a later snapshot of the file has already
+    /// been written.
+    pub fn write(&self) -> Result<(), std::io::Error> {
+        if self.is_stale(&self.lock_last_written()) {
+            return Ok(());
+        }
+
+        let temp_path = write_temporary(
+            &self.file_name,
+            &self.rows,
+            self.line_ending,
+            self.trailing_newline,
+            self.number,
+        )?;
+
+        // Only the rename waits for the other snapshots of the document, so that a slow write
+        // doesn't hold them up.
+        let mut last_written = self.lock_last_written();
+
+        if self.is_stale(&last_written) {
+            let _ = std::fs::remove_file(&temp_path);
+            return Ok(());
+        }
+
+        replace(&temp_path, &self.file_name)?;
+        last_written.insert(self.file_name.clone(), self.number);
+
+        Ok(())
+    }
+
+    /// Whether a later snapshot of the file has already been written.
+    fn is_stale(&self, last_written: &BTreeMap<String, usize>) -> bool {
+        matches!(last_written.get(&self.file_name), Some(last) if *last > self.number)
+    }
+
+    /// The map is still usable if another write panicked while holding the lock.
+    fn lock_last_written(&self) -> MutexGuard<'_, BTreeMap<String, usize>> {
+        self.last_written
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    /// The number of lines that are written.
+    pub fn lines(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// The revision of the `Document` that the snapshot was taken at, to be passed to
+    /// `Document::mark_saved` once it has been written.
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+
+    /// The id of the `Document` that the snapshot was taken of, see `Document::id`.
+    pub fn document(&self) -> usize {
+        self.document
+    }
+}
+
+/// The rows that an edit replaced, so that it can be undone by putting them back.
+struct Change {
+    /// The index of the first row that was replaced.
+    row: usize,
+    /// The rows as they were before the edit.
+    removed: Vec<Row>,
+    /// The number of rows that the edit put in their place.
+    inserted: usize,
+}
+
+/// The changes made by an edit, or by a group of edits, so that they can be undone as one step.
+struct Revision {
+    cursor_position: Position,
+    changes: Vec<Change>,
+}
+
+impl Revision {
+    /// Undo each of the changes, the last one first, returning the `Revision` that redoes them.
+    fn revert(self, rows: &mut Rows) -> Self {
+        let changes = self
+            .changes
+            .into_iter()
+            .rev()
+            .map(|change| Change {
+                row: change.row,
+                inserted: change.removed.len(),
+                removed: rows
+                    .splice(change.row..change.row + change.inserted, change.removed)
+                    .collect(),
+            })
+            .collect();
+
+        Self {
+            cursor_position: self.cursor_position,
+            changes,
+        }
+    }
+}
+
+/// Whether edits are being grouped so that they are undone as a single step.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum UndoGroup {
+    Closed,
+    /// A group has been started but nothing has been edited in it yet.
+    Open,
+    /// The first edit in the group has been recorded, the edits that follow are added to it.
+    Recorded,
+}
+
+/// The number of the last `Snapshot` of a `Document` written to each file. Snapshots are written
+/// in the background, so one that was taken earlier can be ready to write after a later one of
+/// the same file. It is dropped rather than writing the older rows over the newer ones.
+type LastWritten = Arc<Mutex<BTreeMap<String, usize>>>;
+
+/// The swap file that unsaved changes are written to.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Swap {
+    /// No swap file has been written yet.
+    None,
+    /// Left behind by an earlier session that didn't save its changes. It is kept, rather than
+    /// written over, until it has been recovered or the file is saved.
+    Found,
+    /// The revision that was last taken to be written to the swap file.
+    Taken(usize),
+}
+
+impl Swap {
+    fn for_file(filename: &str) -> Self {
+        if swap_exists(filename) {
+            Swap::Found
+        } else {
+            Swap::None
+        }
+    }
+}
 
 pub struct Document {
+    /// Tells the `Document` apart from any other open on the same file, so that the reply to a
+    /// write reaches the `Document` that it was written from.
+    id: usize,
     file_name: Option<String>,
-    rows: Vec<Row>,
+    /// The revision that was last saved, the `Document` is modified if it has changed since.
+    saved_revision: usize,
+    line_ending: LineEnding,
+    /// Whether the file was not valid UTF-8, and the invalid bytes were replaced when it was read.
+    lossy: bool,
+    read_only: bool,
+    swap: Swap,
+    /// Counts every change to the rows, so that a save only marks the `Document` as unmodified
+    /// if nothing has changed since the rows were taken to be written.
+    revision: usize,
+    rows: Rows,
+    trailing_newline: bool,
+    redo_stack: Vec<Revision>,
+    undo_stack: VecDeque<Revision>,
+    undo_group: UndoGroup,
+    /// Where the last change was made, or undone or redone. Every change to the rows moves it,
+    /// so it never points at rows that have since been moved by an edit above them.
+    last_edit: Option<Position>,
+    /// The rest of a large file that has not been read into rows yet.
+    unloaded: Option<std::io::BufReader<std::fs::File>>,
+    /// The number of rows in a large file, once they have been counted, and how many of them
+    /// have been read. Together they give the length of the whole document before it is read.
+    file_rows: Option<usize>,
+    rows_read: usize,
+    /// Shared with the snapshots taken of the `Document`, so that they are written in order.
+    last_written: LastWritten,
 }
 
 impl Default for Document {
     fn default() -> Self {
         Self {
+            id: DOCUMENTS_CREATED.fetch_add(1, Ordering::Relaxed),
             file_name: None,
-            rows: vec![Row::default()],
+            saved_revision: 0,
+            line_ending: LineEnding::default(),
+            lossy: false,
+            read_only: false,
+            swap: Swap::None,
+            revision: 0,
+            rows: std::iter::once(Row::default()).collect(),
+            trailing_newline: true,
+            redo_stack: Vec::default(),
+            undo_stack: VecDeque::default(),
+            undo_group: UndoGroup::Closed,
+            last_edit: None,
+            unloaded: None,
+            file_rows: None,
+            rows_read: 0,
+            last_written: LastWritten::default(),
         }
     }
 }
 
 impl Document {
+    /// Open the file, reading only the first rows of a large file until more are needed.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the file can't be read.
     pub fn open(filename: &str) -> Result<Self> {
         use anyhow::Context;
         use std::fs;
 
-        let contents = fs::read_to_string(filename).context("unable to read from file")?;
-        let mut rows = Vec::new();
+        let file = fs::File::open(filename).context("unable to read from file")?;
+
+        if file.metadata().context("unable to read from file")?.len() > LAZY_LOAD_SIZE {
+            use std::io::BufRead;
+
+            let mut reader = std::io::BufReader::new(file);
+            // The line ending is the one used by most lines in the first chunk of the file, as
+            // it can't be detected over the whole file before it has been read.
+            let line_ending = LineEnding::detect(&String::from_utf8_lossy(
+                reader.fill_buf().context("unable to read from file")?,
+            ));
+
+            let mut document = Self {
+                file_name: Some(String::from(filename)),
+                line_ending,
+                swap: Swap::for_file(filename),
+                rows: Rows::default(),
+                unloaded: Some(reader),
+                ..Self::default()
+            };
+            document
+                .load(INITIAL_ROWS)
+                .context("unable to read from file")?;
+
+            return Ok(document);
+        }
+
+        let bytes = fs::read(filename).context("unable to read from file")?;
+        let contents = String::from_utf8_lossy(&bytes);
+        let mut rows = Rows::default();
 
         for row in contents.lines() {
             rows.push(Row::from(row));
@@ -29,47 +346,433 @@ impl Document {
 
         Ok(Self {
             file_name: Some(String::from(filename)),
+            line_ending: LineEnding::detect(&contents),
+            lossy: matches!(contents, std::borrow::Cow::Owned(_)),
+            swap: Swap::for_file(filename),
             rows,
+            trailing_newline: contents.is_empty() || contents.ends_with('\n'),
+            ..Self::default()
         })
     }
 
-    pub fn save(&mut self, filename: Option<&str>) -> Result<(), std::io::Error> {
-        use {std::fs::File, std::io::Write};
+    /// Open the file, or create an empty `Document` with the file name if the file does not exist,
+    /// like vim does. The file is only created once the `Document` is saved.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the file exists but can't be read.
+    pub fn open_or_create(filename: &str) -> Result<Self> {
+        match std::fs::metadata(filename) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self {
+                file_name: Some(String::from(filename)),
+                swap: Swap::for_file(filename),
+                ..Self::default()
+            }),
+            _ => Self::open(filename),
+        }
+    }
 
+    /// Take the rows to be saved to the given file name, or the file it was opened from, so that
+    /// they can be written without holding on to the `Document`. Returns `None` if there is no
+    /// file name to save to.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the rest of a large file can't be read.
+    pub fn snapshot(&mut self, filename: Option<&str>) -> Result<Option<Snapshot>, std::io::Error> {
         if let Some(filename) = filename {
             self.file_name = Some(filename.into());
         }
 
-        if let Some(file_name) = &self.file_name {
-            let mut file = File::create(file_name)?;
-            for row in &self.rows {
-                file.write_all(row.as_bytes())?;
-                file.write_all(b"\n")?;
-            }
+        // Rows that have not been read yet would otherwise be left out of the file.
+        self.load_all()?;
+
+        Ok(self
+            .file_name
+            .clone()
+            .map(|file_name| Snapshot::new(self, file_name)))
+    }
+
+    /// Record that the rows at the given revision have been written. The `Document` is only
+    /// unmodified if it has not changed since.
+    pub fn mark_saved(&mut self, revision: usize) {
+        if revision == self.revision {
+            self.saved_revision = revision;
+            self.swap = Swap::None;
+            self.remove_swap();
+        }
+
+        self.lossy = false;
+    }
+
+    /// The file that unsaved changes are written to, alongside the file being edited, so that
+    /// they can be recovered if the editor stops without saving them.
+    #[must_use]
+    pub fn swap_file_name(&self) -> Option<String> {
+        self.file_name.as_ref().map(|file_name| {
+            hidden_sibling(file_name, "swp")
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+
+    /// Whether a swap file was left behind by an earlier session, see `recover`.
+    #[must_use]
+    pub fn has_found_swap(&self) -> bool {
+        self.swap == Swap::Found
+    }
+
+    /// Take the rows to be written to the swap file, if there are changes that haven't been
+    /// saved or written to it yet. A swap file left behind by an earlier session is not written
+    /// over.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the rest of a large file can't be read.
+    pub fn swap_snapshot(&mut self) -> Result<Option<Snapshot>, std::io::Error> {
+        if !self.is_modified()
+            || self.swap == Swap::Found
+            || self.swap == Swap::Taken(self.revision)
+        {
+            return Ok(None);
+        }
+
+        // Recovering a swap file replaces every row, so it must hold the rows of a large file
+        // that have not been read yet.
+        self.load_all()?;
+        self.swap = Swap::Taken(self.revision);
+
+        Ok(self
+            .swap_file_name()
+            .map(|swap_file_name| Snapshot::new(self, swap_file_name)))
+    }
+
+    /// Remove the swap file once the changes in it are no longer needed, such as after they have
+    /// been saved. The swap file may never have been written, so failing to remove it is not an
+    /// error.
+    pub fn remove_swap(&mut self) {
+        if self.swap == Swap::Found {
+            return;
         }
 
+        if let Some(swap_file_name) = self.swap_file_name() {
+            let _ = std::fs::remove_file(swap_file_name);
+        }
+    }
+
+    /// Replace the rows with those in the swap file left behind by an earlier session. The
+    /// recovery can be undone, and the changes still need saving.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if there is no swap file, or the rest of a large file can't be
+    /// read.
+    pub fn recover(&mut self) -> Result<()> {
+        use anyhow::Context;
+
+        let swap_file_name = self
+            .swap_file_name()
+            .ok_or_else(|| Error::msg("E305: No swap file found"))?;
+        let bytes = std::fs::read(&swap_file_name)
+            .with_context(|| format!("E305: No swap file found for {swap_file_name}"))?;
+        let contents = String::from_utf8_lossy(&bytes);
+
+        self.load_all().context("unable to read from file")?;
+
+        let mut rows: Vec<Row> = contents.lines().map(Row::from).collect();
+        if rows.is_empty() {
+            rows.push(Row::default());
+        }
+
+        self.record_change(&Position::default(), 0..self.len(), rows.len());
+        self.rows = rows.into_iter().collect();
+        self.swap = Swap::None;
+
         Ok(())
     }
 
+    /// The position at which the last change was made, or undone or redone.
+    #[must_use]
+    pub fn last_edit(&self) -> Option<Position> {
+        self.last_edit
+    }
+
+    /// Start grouping edits so that they are undone as a single step, such as all characters
+    /// typed during an Insert mode session.
+    pub fn begin_undo_group(&mut self) {
+        self.undo_group = UndoGroup::Open;
+    }
+
+    /// Stop grouping edits, each following edit will be undone individually.
+    pub fn end_undo_group(&mut self) {
+        self.undo_group = UndoGroup::Closed;
+    }
+
+    /// Revert the last edit, returning the cursor position at which the edit was made.
+    pub fn undo(&mut self) -> Option<Position> {
+        let revision = self.undo_stack.pop_back()?;
+        let cursor_position = revision.cursor_position;
+
+        self.redo_stack.push(revision.revert(&mut self.rows));
+        self.reopen_undo_group();
+        self.revision += 1;
+        self.last_edit = Some(cursor_position);
+
+        Some(cursor_position)
+    }
+
+    /// Reapply the last undone edit, returning the cursor position at which the edit was made.
+    pub fn redo(&mut self) -> Option<Position> {
+        let revision = self.redo_stack.pop()?;
+        let cursor_position = revision.cursor_position;
+
+        self.undo_stack.push_back(revision.revert(&mut self.rows));
+        self.reopen_undo_group();
+        self.revision += 1;
+        self.last_edit = Some(cursor_position);
+
+        Some(cursor_position)
+    }
+
+    /// Start a new undo group, if one is open, so that the edits after an undo or redo are not
+    /// undone along with the edits before it.
+    fn reopen_undo_group(&mut self) {
+        if self.undo_group == UndoGroup::Recorded {
+            self.undo_group = UndoGroup::Open;
+        }
+    }
+
+    /// Keep the rows in the range before an edit at the given position replaces them with
+    /// `inserted` rows, so that the edit can be undone. Edits made while an undo group is open are
+    /// undone along with the first edit in the group.
+    fn record_change(&mut self, at: &Position, rows: Range<usize>, inserted: usize) {
+        self.revision += 1;
+        self.last_edit = Some(*at);
+
+        let change = Change {
+            row: rows.start,
+            removed: rows.map(|idx| self.rows[idx].clone()).collect(),
+            inserted,
+        };
+
+        if self.undo_group == UndoGroup::Recorded {
+            if let Some(revision) = self.undo_stack.back_mut() {
+                revision.changes.push(change);
+                return;
+            }
+        }
+
+        if self.undo_stack.len() == MAX_UNDO_DEPTH {
+            self.undo_stack.pop_front();
+        }
+
+        self.undo_stack.push_back(Revision {
+            cursor_position: *at,
+            changes: vec![change],
+        });
+        self.redo_stack.clear();
+
+        if self.undo_group == UndoGroup::Open {
+            self.undo_group = UndoGroup::Recorded;
+        }
+    }
+
     pub fn delete(&mut self, at: &Position) {
         if at.row >= self.len() {
             return;
         }
 
-        if at.col == self.rows.get_mut(at.row).unwrap().len() && at.row < self.len() - 1 {
+        if at.col == self.rows[at.row].len() && at.row < self.len() - 1 {
+            self.record_change(at, at.row..at.row + 2, 1);
+
             let next_row = self.rows.remove(at.row + 1);
-            let row = self.rows.get_mut(at.row).unwrap();
-            row.append(&next_row);
+            self.rows[at.row].append(&next_row);
+            return;
+        }
+
+        // There is nothing to delete at the end of the last row.
+        if at.col >= self.rows[at.row].len() {
+            return;
+        }
+
+        self.record_change(at, at.row..at.row + 1, 1);
+
+        self.rows[at.row].delete(at.col);
+    }
+
+    /// Join the row below onto the end of the given row, separated by a single space. Leading
+    /// whitespace on the joined row is dropped and no space is added when either side is empty.
+    /// Returns the position where the rows were joined, or `None` if there is no row below.
+    pub fn join_line(&mut self, row: usize) -> Option<Position> {
+        if row + 1 >= self.len() {
+            return None;
+        }
+
+        let at = Position::new(self.rows[row].len(), row);
+        self.record_change(&at, row..row + 2, 1);
+
+        let next_row = self.rows.remove(row + 1);
+        let next_row = Row::from(&next_row.slice(next_row.first_non_blank(), next_row.len())[..]);
+        let current_row = &mut self.rows[row];
+
+        if !current_row.is_empty() && !next_row.is_empty() {
+            current_row.append(&Row::from(" "));
+        }
+
+        current_row.append(&next_row);
+
+        Some(at)
+    }
+
+    /// Replace the grapheme at the given position with the text, returning the grapheme that was
+    /// replaced. The text is appended when the position is at the end of the row, in which case
+    /// `None` is returned.
+    pub fn replace(&mut self, at: &Position, text: &str) -> Option<String> {
+        if at.row >= self.len() {
+            return None;
+        }
+
+        self.record_change(at, at.row..at.row + 1, 1);
+        self.rows[at.row].replace(at.col, text)
+    }
+
+    /// Swap the case of up to `n` graphemes on the row, starting at the given position. Returns
+    /// the number of graphemes that were toggled.
+    pub fn toggle_case(&mut self, at: &Position, n: usize) -> usize {
+        let len = self.rows.get(at.row).map_or(0, Row::len);
+        let end = cmp::min(at.col.saturating_add(n), len);
+
+        if at.col >= end {
+            return 0;
+        }
+
+        self.record_change(at, at.row..at.row + 1, 1);
+
+        let row = &mut self.rows[at.row];
+        for col in at.col..end {
+            row.toggle_case_at(col);
+        }
+
+        end - at.col
+    }
+
+    /// Add one level of indentation, `width` spaces, to the start of up to `n` rows from the given
+    /// row. Empty rows are left as they are.
+    pub fn indent(&mut self, row: usize, n: usize, width: usize) {
+        let end = cmp::min(row.saturating_add(n), self.len());
+
+        if !(row..end).any(|idx| !self.rows[idx].is_empty()) {
+            return;
+        }
+
+        self.record_change(&Position::new(0, row), row..end, end - row);
+
+        for idx in row..end {
+            let row = &mut self.rows[idx];
+
+            if !row.is_empty() {
+                for _ in 0..width {
+                    row.insert(0, ' ');
+                }
+            }
+        }
+    }
+
+    /// Remove up to one level of indentation from the start of up to `n` rows from the given row.
+    /// A level is `width` spaces or a single tab, rows without leading whitespace are unchanged.
+    pub fn dedent(&mut self, row: usize, n: usize, width: usize) {
+        let end = cmp::min(row.saturating_add(n), self.len());
+        let removals: Vec<usize> = (row..end)
+            .map(|idx| dedent_width(&self.rows[idx], width))
+            .collect();
+
+        if removals.iter().all(|&removal| removal == 0) {
             return;
         }
 
-        let row = self.rows.get_mut(at.row).unwrap();
-        row.delete(at.col);
+        self.record_change(&Position::new(0, row), row..end, end - row);
+
+        for (idx, removal) in (row..end).zip(removals) {
+            for _ in 0..removal {
+                self.rows[idx].delete(0);
+            }
+        }
+    }
+
+    /// Delete everything from the start position up to, but not including, the end position.
+    /// Rows between the two positions are removed and the remainder of the end row is appended
+    /// to the start row. Returns where the cursor belongs afterwards, which is the start position
+    /// kept within the document.
+    pub fn delete_range(&mut self, from: &Position, to: &Position) -> Position {
+        let Some(last_row) = self.len().checked_sub(1) else {
+            return Position::default();
+        };
+
+        if from.row > last_row {
+            return Position::new(self.rows[last_row].len(), last_row);
+        }
+
+        let start = Position::new(cmp::min(from.col, self.rows[from.row].len()), from.row);
+
+        if (from.row, from.col) >= (to.row, to.col) {
+            return start;
+        }
+
+        let to_row = cmp::min(to.row, last_row);
+        let to_col = if to.row > last_row {
+            self.rows[last_row].len()
+        } else {
+            to.col
+        };
+
+        self.record_change(from, from.row..to_row + 1, 1);
+
+        let tail = self.rows[to_row].split(to_col);
+        self.rows[from.row].split(from.col);
+        self.rows[from.row].append(&tail);
+        self.rows.drain(from.row + 1..=to_row);
+
+        start
+    }
+
+    /// The text from the start position up to, but not including, the end position. Rows are
+    /// separated by a newline.
+    #[must_use]
+    pub fn text_range(&self, from: &Position, to: &Position) -> String {
+        let mut text = String::new();
+
+        for (idx, row) in self
+            .rows
+            .iter()
+            .enumerate()
+            .skip(from.row)
+            .take((to.row + 1).saturating_sub(from.row))
+        {
+            let start = if idx == from.row { from.col } else { 0 };
+            let end = if idx == to.row { to.col } else { row.len() };
+
+            text.push_str(&row.slice(start, end));
+
+            if idx != to.row {
+                text.push('\n');
+            }
+        }
+
+        text
     }
 
+    /// Insert the character at the given position, adding a row if it is just past the last one.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the position is past the last row.
     pub fn insert(&mut self, at: &Position, ch: char) -> Result<()> {
         use std::cmp::Ordering;
 
+        if at.row <= self.len() {
+            self.record_change(at, at.row..cmp::min(at.row + 1, self.len()), 1);
+        }
+
         match at.row.cmp(&self.len()) {
             Ordering::Equal => {
                 let mut row = Row::default();
@@ -79,40 +782,1390 @@ impl Document {
                 Ok(())
             }
             Ordering::Less => {
-                let row = self.rows.get_mut(at.row).unwrap();
-                row.insert(at.col, ch);
+                self.rows[at.row].insert(at.col, ch);
                 Ok(())
             }
-            Ordering::Greater => Err(Error::from(std::io::Error::new(
-                std::io::ErrorKind::Other,
+            Ordering::Greater => Err(Error::from(std::io::Error::other(
                 "trying to insert character past current string length",
             ))),
         }
     }
 
+    /// Insert the text at the given position as a single edit, starting a new row at each newline.
+    /// Returns the position just after the inserted text.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the position is past the last row.
+    pub fn insert_str(&mut self, at: &Position, text: &str) -> Result<Position> {
+        if at.row > self.len() {
+            return Err(Error::from(std::io::Error::other(
+                "trying to insert text past the end of the document",
+            )));
+        }
+
+        let rows = at.row..cmp::min(at.row + 1, self.len());
+        self.record_change(at, rows, text.matches('\n').count() + 1);
+
+        if at.row == self.len() {
+            self.rows.push(Row::default());
+        }
+
+        let col = cmp::min(at.col, self.rows[at.row].len());
+
+        let Some((first, rest)) = text.split_once('\n') else {
+            let row = &mut self.rows[at.row];
+            let len = row.len();
+            row.insert_str(col, text);
+
+            return Ok(Position::new(col + row.len() - len, at.row));
+        };
+
+        let tail = self.rows[at.row].split(col);
+        let mut end = at.row;
+        self.rows[at.row].insert_str(col, first);
+
+        for line in rest.split('\n') {
+            end += 1;
+            self.rows.insert(end, Row::from(line));
+        }
+
+        let position = Position::new(self.rows[end].len(), end);
+        self.rows[end].append(&tail);
+
+        Ok(position)
+    }
+
     pub fn insert_newline(&mut self, at: &Position) {
         if at.row > self.len() {
             return;
         }
 
         if at.row == self.len() {
+            self.record_change(at, at.row..at.row, 1);
             self.rows.push(Row::default());
             return;
         }
 
-        let new_row = self.rows.get_mut(at.row).unwrap().split(at.col);
+        self.record_change(at, at.row..at.row + 1, 2);
+
+        let new_row = self.rows[at.row].split(at.col);
         self.rows.insert(at.row + 1, new_row);
     }
 
+    /// The number of characters in the document, not counting line breaks.
+    pub fn char_count(&self) -> usize {
+        self.rows.iter().map(Row::len).sum()
+    }
+
+    /// Count the words, lines and characters in the document. Words are found using the Unicode
+    /// word boundaries so whitespace and punctuation separate them. A document with a single
+    /// empty line counts as having no lines at all.
+    pub fn word_count(&self) -> WordCount {
+        if self.rows.iter().all(Row::is_empty) && self.len() <= 1 {
+            return WordCount::default();
+        }
+
+        WordCount {
+            words: self
+                .rows
+                .iter()
+                .map(|row| row.contents().unicode_words().count())
+                .sum(),
+            lines: self.len(),
+            chars: self.char_count(),
+        }
+    }
+
+    /// Returns `true` if the document has been changed since it was last saved.
+    #[must_use]
+    pub fn is_modified(&self) -> bool {
+        self.revision != self.saved_revision
+    }
+
+    /// Whether the file was not valid UTF-8 when it was read. The invalid bytes are shown as
+    /// replacement characters, so saving over the file would lose them.
+    #[must_use]
+    pub fn is_lossy(&self) -> bool {
+        self.lossy
+    }
+
+    /// Returns `true` if the `Document` should not be changed or saved, such as a file opened
+    /// with `:view`.
+    #[must_use]
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// The line ending written after each row when saving.
+    #[must_use]
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Change the line ending that every row is written with on the next save.
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if self.line_ending != line_ending {
+            self.line_ending = line_ending;
+            self.revision += 1;
+        }
+    }
+
+    /// The id given to the `Document` when it was created, unique within the process.
+    #[must_use]
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    #[must_use]
     pub fn file_name(&self) -> Option<&String> {
         self.file_name.as_ref()
     }
 
-    pub fn row(&self, index: usize) -> Option<&Row> {
-        self.rows.get(index)
+    /// Find the next match of the query after the given position, wrapping around the end of the
+    /// document.
+    #[must_use]
+    pub fn find(&self, query: &str, from: &Position) -> Option<Position> {
+        let len = self.rows.len();
+        let start = cmp::min(from.row, len.checked_sub(1)?);
+
+        (0..=len).find_map(|i| {
+            let row = (start + i) % len;
+            let mut matches = self.rows[row].find_all(query).into_iter();
+
+            if i == 0 {
+                matches.find(|col| *col > from.col)
+            } else {
+                matches.next()
+            }
+            .map(|col| Position::new(col, row))
+        })
     }
 
-    pub fn len(&self) -> usize {
-        self.rows.len()
+    /// Find the previous match of the query before the given position, wrapping around the start
+    /// of the document.
+    #[must_use]
+    pub fn rfind(&self, query: &str, from: &Position) -> Option<Position> {
+        let len = self.rows.len();
+        let start = cmp::min(from.row, len.checked_sub(1)?);
+
+        (0..=len).find_map(|i| {
+            let row = (start + len - i % len) % len;
+            let mut matches = self.rows[row].find_all(query).into_iter();
+
+            if i == 0 {
+                matches.rev().find(|col| *col < from.col)
+            } else {
+                matches.next_back()
+            }
+            .map(|col| Position::new(col, row))
+        })
+    }
+
+    /// Find every match of the query in the document, in order from the start.
+    #[must_use]
+    pub fn find_all(&self, query: &str) -> Vec<Position> {
+        self.rows
+            .iter()
+            .enumerate()
+            .flat_map(|(row, r)| {
+                r.find_all(query)
+                    .into_iter()
+                    .map(move |col| Position::new(col, row))
+            })
+            .collect()
+    }
+
+    /// The position of the bracket that pairs with the bracket at the given position, skipping
+    /// over any nested pairs in between. Returns `None` if there is no bracket at the position or
+    /// it has no pair.
+    #[must_use]
+    pub fn matching_bracket(&self, at: &Position) -> Option<Position> {
+        let bracket = self.rows.get(at.row)?.graphemes().nth(at.col)?;
+        let (open, close, forward) = match bracket {
+            "(" => ("(", ")", true),
+            "[" => ("[", "]", true),
+            "{" => ("{", "}", true),
+            ")" => ("(", ")", false),
+            "]" => ("[", "]", false),
+            "}" => ("{", "}", false),
+            _ => return None,
+        };
+
+        // Walk away from the bracket, counting how deeply nested each bracket of the same kind
+        // is, until the bracket at the same depth is found.
+        let graphemes: Box<dyn Iterator<Item = (Position, &str)> + '_> = if forward {
+            Box::new(
+                self.rows
+                    .iter()
+                    .enumerate()
+                    .skip(at.row)
+                    .flat_map(move |(row, r)| {
+                        r.graphemes()
+                            .enumerate()
+                            .skip(if row == at.row { at.col } else { 0 })
+                            .map(move |(col, g)| (Position::new(col, row), g))
+                    }),
+            )
+        } else {
+            Box::new(
+                self.rows
+                    .iter()
+                    .take(at.row + 1)
+                    .enumerate()
+                    .rev()
+                    .flat_map(move |(row, r)| {
+                        r.graphemes()
+                            .enumerate()
+                            .take(if row == at.row { at.col + 1 } else { r.len() })
+                            .collect::<Vec<_>>()
+                            .into_iter()
+                            .rev()
+                            .map(move |(col, g)| (Position::new(col, row), g))
+                    }),
+            )
+        };
+
+        let mut depth = 0_usize;
+
+        for (position, grapheme) in graphemes {
+            if grapheme == open || grapheme == close {
+                if (grapheme == open) == forward {
+                    depth += 1;
+                } else {
+                    depth -= 1;
+
+                    if depth == 0 {
+                        return Some(position);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// The position of the start of the next word after the given position, wrapping onto
+    /// following rows. Empty rows are treated as a word.
+    pub fn next_word_start(&self, at: &Position) -> Position {
+        let next_on_row = self
+            .rows
+            .get(at.row)
+            .and_then(|row| row.words().into_iter().find(|(start, _)| *start > at.col));
+
+        if let Some((start, _)) = next_on_row {
+            return Position::new(start, at.row);
+        }
+
+        for (idx, row) in self.rows.iter().enumerate().skip(at.row + 1) {
+            if row.is_empty() {
+                return Position::new(0, idx);
+            }
+
+            if let Some((start, _)) = row.words().first() {
+                return Position::new(*start, idx);
+            }
+        }
+
+        Position::new(self.rows.get(at.row).map_or(0, Row::len), at.row)
+    }
+
+    /// The position of the start of the word before the given position, wrapping onto previous
+    /// rows. Empty rows are treated as a word.
+    #[must_use]
+    pub fn previous_word_start(&self, at: &Position) -> Position {
+        let previous_on_row = self.rows.get(at.row).and_then(|row| {
+            row.words()
+                .into_iter()
+                .rev()
+                .find(|(start, _)| *start < at.col)
+        });
+
+        if let Some((start, _)) = previous_on_row {
+            return Position::new(start, at.row);
+        }
+
+        for (idx, row) in self.rows.iter().enumerate().take(at.row).rev() {
+            if row.is_empty() {
+                return Position::new(0, idx);
+            }
+
+            if let Some((start, _)) = row.words().last() {
+                return Position::new(*start, idx);
+            }
+        }
+
+        Position::new(0, at.row)
+    }
+
+    /// The position of the end of the word after the given position, wrapping onto following
+    /// rows.
+    pub fn word_end(&self, at: &Position) -> Position {
+        let end_on_row = self
+            .rows
+            .get(at.row)
+            .and_then(|row| row.words().into_iter().find(|(_, end)| *end > at.col));
+
+        if let Some((_, end)) = end_on_row {
+            return Position::new(end, at.row);
+        }
+
+        for (idx, row) in self.rows.iter().enumerate().skip(at.row + 1) {
+            if let Some((_, end)) = row.words().first() {
+                return Position::new(*end, idx);
+            }
+        }
+
+        Position::new(self.rows.get(at.row).map_or(0, Row::len), at.row)
+    }
+
+    #[must_use]
+    pub fn row(&self, index: usize) -> Option<&Row> {
+        self.rows.get(index)
+    }
+
+    /// The text of the line at the given index, without its line ending. Only the rows that have
+    /// been read of a large file are included, see `load`.
+    #[must_use]
+    pub fn line(&self, index: usize) -> Option<String> {
+        self.rows.get(index).map(|row| row.as_str().to_string())
+    }
+
+    /// The text of the document as it would be saved, with each row followed by the document's
+    /// line ending. The last line ending is left off when the document did not have a trailing
+    /// newline. Only the rows that have been read of a large file are included, so `load_all`
+    /// needs calling first for the whole text.
+    pub fn contents(&self) -> String {
+        let mut contents = self
+            .rows
+            .iter()
+            .map(Row::as_str)
+            .collect::<Vec<_>>()
+            .join(self.line_ending.as_str());
+
+        if self.trailing_newline && !self.rows.is_empty() {
+            contents.push_str(self.line_ending.as_str());
+        }
+
+        contents
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The number of rows in the whole document, including those of a large file that haven't
+    /// been read yet. `None` until the rows of a large file have been given by `set_row_count`.
+    #[must_use]
+    pub fn total_len(&self) -> Option<usize> {
+        match self.file_rows {
+            _ if self.is_loaded() => Some(self.len()),
+            Some(file_rows) => Some(self.len() + file_rows.saturating_sub(self.rows_read)),
+            None => None,
+        }
+    }
+
+    /// Give the number of rows in the file of a large document that is read as its rows are
+    /// needed, as found by `count_rows`.
+    pub fn set_row_count(&mut self, rows: usize) {
+        self.file_rows = Some(rows);
+    }
+
+    /// Count the rows in a file without reading them into a `Document`, so that the length of a
+    /// large file is known before all of it has been read.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the file can't be read.
+    pub fn count_rows(file_name: &str) -> Result<usize, std::io::Error> {
+        use std::io::BufRead;
+
+        let mut reader = std::io::BufReader::new(std::fs::File::open(file_name)?);
+        let mut rows = 0;
+        let mut ends_with_newline = true;
+
+        loop {
+            let bytes = reader.fill_buf()?;
+            let last = match bytes.last() {
+                Some(last) => *last,
+                None => break,
+            };
+
+            rows += bytes.split(|b| *b == b'\n').count() - 1;
+            ends_with_newline = last == b'\n';
+
+            let len = bytes.len();
+            reader.consume(len);
+        }
+
+        // The last row doesn't have to end with a newline.
+        Ok(if ends_with_newline { rows } else { rows + 1 })
+    }
+
+    /// Whether every row of the file has been read. Large files are read as their rows are
+    /// needed, until then `len` only counts the rows read so far.
+    #[must_use]
+    pub fn is_loaded(&self) -> bool {
+        self.unloaded.is_none()
+    }
+
+    /// Read rows from the rest of a large file until there are at least `rows` of them, or the
+    /// whole file has been read.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the file can't be read.
+    pub fn load(&mut self, rows: usize) -> Result<(), std::io::Error> {
+        use std::io::BufRead;
+
+        while self.rows.len() < rows {
+            let Some(reader) = &mut self.unloaded else {
+                break;
+            };
+
+            let mut bytes = Vec::new();
+            if reader.read_until(b'\n', &mut bytes)? == 0 {
+                self.unloaded = None;
+                break;
+            }
+
+            let line = String::from_utf8_lossy(&bytes);
+            self.lossy |= matches!(line, std::borrow::Cow::Owned(_));
+
+            self.trailing_newline = line.ends_with('\n');
+            let line = line
+                .strip_suffix('\n')
+                .map_or(&line[..], |line| line.strip_suffix('\r').unwrap_or(line));
+            self.rows.push(Row::from(line));
+            self.rows_read += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Read the rest of a large file.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the file can't be read.
+    pub fn load_all(&mut self) -> Result<(), std::io::Error> {
+        self.load(usize::MAX)
+    }
+}
+
+fn swap_exists(file_name: &str) -> bool {
+    hidden_sibling(file_name, "swp").exists()
+}
+
+/// The path of a hidden file alongside the given file, named after it with the extension added,
+/// such as `.notes.txt.swp` for `notes.txt`.
+fn hidden_sibling(file_name: &str, extension: &str) -> std::path::PathBuf {
+    let path = std::path::Path::new(file_name);
+
+    path.with_file_name(format!(
+        ".{}.{}",
+        path.file_name()
+            .map_or_else(|| file_name.into(), |name| name.to_string_lossy()),
+        extension
+    ))
+}
+
+/// Write the rows to a temporary file in the same directory as the target, returning its path
+/// once every row, and its line ending, has been written. The line ending is left off the last row
+/// when `trailing_newline` is `false`. The permissions of an existing target are carried over. The
+/// temporary file is named after the process and the number of the snapshot being written, so
+/// that it is never shared with another write.
+fn write_temporary(
+    file_name: &str,
+    rows: &Rows,
+    line_ending: LineEnding,
+    trailing_newline: bool,
+    number: usize,
+) -> Result<std::path::PathBuf, std::io::Error> {
+    use std::fs::{self, File};
+    use std::io::{self, Write};
+
+    let temp_path = hidden_sibling(
+        file_name,
+        &format!("{}.{number}.velm-tmp", std::process::id()),
+    );
+
+    let describe = |action: &str, e: io::Error| {
+        io::Error::new(
+            e.kind(),
+            format!("unable to {} {}: {}", action, temp_path.display(), e),
+        )
+    };
+
+    let mut file = File::create(&temp_path).map_err(|e| describe("create temporary file", e))?;
+
+    let written = rows
+        .iter()
+        .enumerate()
+        .try_for_each(|(idx, row)| -> io::Result<()> {
+            file.write_all(row.as_bytes())?;
+
+            if trailing_newline || idx + 1 < rows.len() {
+                file.write_all(line_ending.as_bytes())?;
+            }
+
+            Ok(())
+        })
+        .and_then(|()| file.sync_all());
+
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(describe("write temporary file", e));
+    }
+
+    if let Ok(metadata) = fs::metadata(file_name) {
+        let _ = fs::set_permissions(&temp_path, metadata.permissions());
+    }
+
+    Ok(temp_path)
+}
+
+/// Rename the temporary file written by `write_temporary` over the target, removing it if it
+/// can't be renamed.
+fn replace(temp_path: &std::path::Path, file_name: &str) -> Result<(), std::io::Error> {
+    use std::fs;
+    use std::io;
+
+    fs::rename(temp_path, file_name).map_err(|e| {
+        let _ = fs::remove_file(temp_path);
+        io::Error::new(
+            e.kind(),
+            format!(
+                "unable to rename {} to {}: {}",
+                temp_path.display(),
+                file_name,
+                e
+            ),
+        )
+    })
+}
+
+/// The number of leading graphemes to remove from the row to take away one level of
+/// indentation, either up to `width` spaces or a single tab.
+fn dedent_width(row: &Row, width: usize) -> usize {
+    let leading = row.slice(0, row.first_non_blank());
+    let mut removal = 0;
+
+    for ch in leading.chars().take(cmp::max(1, width)) {
+        match ch {
+            ' ' => removal += 1,
+            '\t' => return removal + 1,
+            _ => break,
+        }
+    }
+
+    removal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Document, LineEnding, WordCount, INITIAL_ROWS, LAZY_LOAD_SIZE, MAX_UNDO_DEPTH};
+    use crate::{row::Row, testing::TempFile, ui::Position};
+    use std::convert::TryFrom;
+
+    fn document(lines: &[&str]) -> Document {
+        Document {
+            rows: lines.iter().map(|line| Row::from(*line)).collect(),
+            ..Document::default()
+        }
+    }
+
+    fn contents(doc: &Document) -> Vec<String> {
+        (0..doc.len())
+            .filter_map(|idx| doc.row(idx).map(Row::contents))
+            .collect()
+    }
+
+    /// Save the document the same way a `Buffer` does, by writing a snapshot of it.
+    fn save(doc: &mut Document, filename: Option<&str>) -> Result<(), std::io::Error> {
+        if let Some(snapshot) = doc.snapshot(filename)? {
+            snapshot.write()?;
+            doc.mark_saved(snapshot.revision());
+        }
+
+        Ok(())
+    }
+
+    fn raw_contents(doc: &Document) -> Vec<&str> {
+        (0..doc.len())
+            .filter_map(|idx| doc.row(idx).map(Row::as_str))
+            .collect()
+    }
+
+    #[test]
+    fn save_replaces_the_file_without_leaving_a_temporary_file() {
+        let file_name = TempFile::new("save.txt");
+        std::fs::write(&file_name, "old contents that are longer\n").unwrap();
+
+        let mut doc = document(&["new", "contents"]);
+        save(&mut doc, Some(&file_name)).unwrap();
+
+        assert_eq!(
+            "new\ncontents\n",
+            std::fs::read_to_string(&file_name).unwrap()
+        );
+        let temp_prefix = format!(".velm-{}-save.txt.", std::process::id());
+        assert!(!std::fs::read_dir(std::env::temp_dir())
+            .unwrap()
+            .filter_map(|entry| entry.unwrap().file_name().into_string().ok())
+            .any(|name| name.starts_with(&temp_prefix) && name.ends_with(".velm-tmp")));
+    }
+
+    #[test]
+    fn snapshots_written_out_of_order_leave_the_latest_rows_in_the_file() {
+        let file_name = TempFile::new("save-order.txt");
+
+        let mut doc = document(&["old"]);
+        let old = doc.snapshot(Some(&file_name)).unwrap().unwrap();
+        doc.insert(&Position::new(3, 0), '!').unwrap();
+        let new = doc.snapshot(None).unwrap().unwrap();
+
+        new.write().unwrap();
+        old.write().unwrap();
+        assert_eq!("old!\n", std::fs::read_to_string(&file_name).unwrap());
+    }
+
+    #[test]
+    fn large_files_are_read_as_their_rows_are_needed() {
+        let file_name = TempFile::new("large.txt");
+        let rows = usize::try_from(LAZY_LOAD_SIZE).unwrap() / 10 + 1;
+        std::fs::write(&file_name, "123456789\r\n".repeat(rows) + "last").unwrap();
+
+        let mut doc = Document::open(&file_name).unwrap();
+        assert!(!doc.is_loaded());
+        assert_eq!(INITIAL_ROWS, doc.len());
+        assert_eq!(LineEnding::CrLf, doc.line_ending());
+        assert_eq!(Some("123456789".into()), doc.line(0));
+
+        doc.load(INITIAL_ROWS + 10).unwrap();
+        assert_eq!(INITIAL_ROWS + 10, doc.len());
+
+        doc.load_all().unwrap();
+        assert!(doc.is_loaded());
+        assert_eq!(rows + 1, doc.len());
+        assert_eq!(Some("last".into()), doc.line(rows));
+    }
+
+    #[test]
+    fn the_line_ending_of_a_large_file_is_the_one_used_by_most_of_its_first_lines() {
+        let file_name = TempFile::new("large-crlf.txt");
+        let rows = usize::try_from(LAZY_LOAD_SIZE).unwrap() / 10 + 1;
+        std::fs::write(
+            &file_name,
+            "first\n".to_string() + &"12345678\r\n".repeat(rows),
+        )
+        .unwrap();
+
+        let doc = Document::open(&file_name).unwrap();
+        assert!(!doc.is_loaded());
+        assert_eq!(LineEnding::CrLf, doc.line_ending());
+    }
+
+    #[test]
+    fn the_length_of_a_large_file_is_known_once_its_rows_are_counted() {
+        let file_name = TempFile::new("large-count.txt");
+        let rows = usize::try_from(LAZY_LOAD_SIZE).unwrap() / 10 + 1;
+        std::fs::write(&file_name, "123456789\n".repeat(rows) + "last").unwrap();
+
+        let mut doc = Document::open(&file_name).unwrap();
+        assert_eq!(None, doc.total_len());
+
+        doc.set_row_count(Document::count_rows(&file_name).unwrap());
+        assert_eq!(Some(rows + 1), doc.total_len());
+
+        doc.insert_newline(&Position::new(0, 0));
+        doc.load(INITIAL_ROWS + 10).unwrap();
+        assert!(!doc.is_loaded());
+        assert_eq!(Some(rows + 2), doc.total_len());
+
+        doc.load_all().unwrap();
+        assert_eq!(Some(rows + 2), doc.total_len());
+    }
+
+    #[test]
+    fn count_rows_counts_a_last_row_without_a_newline() {
+        let file_name = TempFile::new("count.txt");
+
+        std::fs::write(&file_name, "one\ntwo\n").unwrap();
+        assert_eq!(2, Document::count_rows(&file_name).unwrap());
+        std::fs::write(&file_name, "one\ntwo").unwrap();
+        assert_eq!(2, Document::count_rows(&file_name).unwrap());
+        std::fs::write(&file_name, "").unwrap();
+        assert_eq!(0, Document::count_rows(&file_name).unwrap());
+    }
+
+    #[test]
+    fn saving_a_large_file_writes_the_rows_that_have_not_been_read() {
+        let file_name = TempFile::new("large-save.txt");
+        let contents = "123456789\n".repeat(usize::try_from(LAZY_LOAD_SIZE).unwrap() / 10 + 1);
+        std::fs::write(&file_name, &contents).unwrap();
+
+        let mut doc = Document::open(&file_name).unwrap();
+        save(&mut doc, None).unwrap();
+
+        assert!(doc.is_loaded());
+        assert!(std::fs::read_to_string(&file_name).unwrap() == contents);
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced_when_opened() {
+        let file_name = TempFile::new("latin1.txt");
+        std::fs::write(&file_name, b"caf\xe9\nok\n").unwrap();
+
+        let mut doc = Document::open(&file_name).unwrap();
+
+        assert_eq!(vec!["caf\u{fffd}", "ok"], contents(&doc));
+        assert!(doc.is_lossy());
+
+        save(&mut doc, None).unwrap();
+        assert!(!doc.is_lossy());
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced_in_large_files() {
+        let file_name = TempFile::new("large-latin1.txt");
+        let mut bytes = b"\xff\xfe\n".to_vec();
+        bytes.extend(
+            "123456789\n"
+                .repeat(usize::try_from(LAZY_LOAD_SIZE).unwrap() / 10 + 1)
+                .bytes(),
+        );
+        std::fs::write(&file_name, bytes).unwrap();
+
+        let doc = Document::open(&file_name).unwrap();
+
+        assert_eq!(Some("\u{fffd}\u{fffd}".into()), doc.line(0));
+        assert!(doc.is_lossy());
+    }
+
+    #[test]
+    fn valid_utf8_is_not_lossy() {
+        let file_name = TempFile::new("utf8.txt");
+        std::fs::write(&file_name, "caf\u{e9}\n").unwrap();
+
+        assert!(!Document::open(&file_name).unwrap().is_lossy());
+    }
+
+    #[test]
+    fn unsaved_changes_are_written_to_the_swap_file_until_they_are_saved() {
+        let file_name = TempFile::new("swap.txt");
+        let mut doc = Document::open_or_create(&file_name).unwrap();
+        let swap_file_name = doc.swap_file_name().unwrap();
+        assert!(swap_file_name.ends_with(&format!(".velm-{}-swap.txt.swp", std::process::id())));
+        assert!(doc.swap_snapshot().unwrap().is_none());
+
+        doc.insert(&Position::default(), 'a').unwrap();
+        doc.swap_snapshot().unwrap().unwrap().write().unwrap();
+        assert_eq!("a\n", std::fs::read_to_string(&swap_file_name).unwrap());
+        assert!(doc.swap_snapshot().unwrap().is_none());
+
+        save(&mut doc, None).unwrap();
+        assert!(!std::path::Path::new(&swap_file_name).exists());
+    }
+
+    #[test]
+    fn swap_files_left_behind_can_be_recovered() {
+        let file_name = TempFile::new("recover.txt");
+        let mut doc = Document::open_or_create(&file_name).unwrap();
+        let swap_file_name = doc.swap_file_name().unwrap();
+        std::fs::write(&file_name, "saved\n").unwrap();
+        std::fs::write(&swap_file_name, "unsaved\nchanges\n").unwrap();
+
+        doc = Document::open(&file_name).unwrap();
+        assert!(doc.has_found_swap());
+        doc.insert(&Position::default(), 'a').unwrap();
+        assert!(doc.swap_snapshot().unwrap().is_none());
+
+        doc.recover().unwrap();
+        assert_eq!(vec!["unsaved", "changes"], contents(&doc));
+        assert!(!doc.has_found_swap());
+        assert!(doc.is_modified());
+
+        doc.undo();
+        assert_eq!(vec!["asaved"], contents(&doc));
+
+        std::fs::remove_file(&swap_file_name).unwrap();
+    }
+
+    #[test]
+    fn the_swap_file_of_a_large_file_holds_the_rows_that_have_not_been_read() {
+        let file_name = TempFile::new("large-swap.txt");
+        let rows = usize::try_from(LAZY_LOAD_SIZE).unwrap() / 10 + 1;
+        std::fs::write(&file_name, "123456789\n".repeat(rows)).unwrap();
+
+        let mut doc = Document::open(&file_name).unwrap();
+        let swap_file_name = doc.swap_file_name().unwrap();
+        doc.insert(&Position::default(), 'a').unwrap();
+        doc.swap_snapshot().unwrap().unwrap().write().unwrap();
+
+        let swapped = std::fs::read_to_string(&swap_file_name).unwrap();
+        assert_eq!(rows, swapped.lines().count());
+
+        std::fs::remove_file(&swap_file_name).unwrap();
+    }
+
+    #[test]
+    fn open_or_create_creates_an_empty_named_document_for_a_missing_file() {
+        let file_name = TempFile::new("missing.txt");
+
+        let doc = Document::open_or_create(&file_name).unwrap();
+
+        assert_eq!(Some(&file_name.to_string()), doc.file_name());
+        assert_eq!(vec![""], contents(&doc));
+        assert!(!doc.is_modified());
+        assert!(!std::path::Path::new(&*file_name).exists());
+    }
+
+    #[test]
+    fn open_or_create_reports_files_that_can_not_be_read() {
+        let dir = std::env::temp_dir().to_string_lossy().into_owned();
+
+        assert!(Document::open_or_create(&dir).is_err());
+    }
+
+    #[test]
+    fn join_line_appends_the_next_row_with_a_single_space() {
+        let mut doc = document(&["fn main() {", "    body();", "", "}"]);
+
+        assert_eq!(Some(Position::new(11, 0)), doc.join_line(0));
+        assert_eq!(vec!["fn main() { body();", "", "}"], contents(&doc));
+
+        assert_eq!(Some(Position::new(0, 1)), doc.join_line(1));
+        assert_eq!(vec!["fn main() { body();", "}"], contents(&doc));
+
+        assert_eq!(None, doc.join_line(1));
+        assert!(doc.undo().is_some());
+        assert_eq!(vec!["fn main() { body();", "", "}"], contents(&doc));
+    }
+
+    #[test]
+    fn find_all_returns_every_match() {
+        let doc = document(&["one two one", "three", "one"]);
+
+        assert_eq!(
+            vec![
+                Position::new(0, 0),
+                Position::new(8, 0),
+                Position::new(0, 2)
+            ],
+            doc.find_all("one")
+        );
+        assert!(doc.find_all("four").is_empty());
+    }
+
+    #[test]
+    fn matching_bracket_skips_nested_pairs() {
+        let doc = document(&["fn main() {", "    if (a[0]) {}", "}"]);
+
+        assert_eq!(
+            Some(Position::new(0, 2)),
+            doc.matching_bracket(&Position::new(10, 0))
+        );
+        assert_eq!(
+            Some(Position::new(10, 0)),
+            doc.matching_bracket(&Position::new(0, 2))
+        );
+        assert_eq!(
+            Some(Position::new(7, 1)),
+            doc.matching_bracket(&Position::new(12, 1))
+        );
+        assert_eq!(None, doc.matching_bracket(&Position::new(0, 0)));
+    }
+
+    #[test]
+    fn unbalanced_brackets_have_no_match() {
+        let doc = document(&["(a", "b))"]);
+
+        assert_eq!(
+            Some(Position::new(1, 1)),
+            doc.matching_bracket(&Position::new(0, 0))
+        );
+        assert_eq!(None, doc.matching_bracket(&Position::new(2, 1)));
+    }
+
+    #[test]
+    fn toggle_case_stops_at_the_end_of_the_row() {
+        let mut doc = document(&["hello", "world"]);
+
+        assert_eq!(3, doc.toggle_case(&Position::new(2, 0), 5));
+        assert_eq!(vec!["heLLO", "world"], contents(&doc));
+
+        assert_eq!(0, doc.toggle_case(&Position::new(5, 0), 1));
+        assert!(doc.undo().is_some());
+        assert_eq!(vec!["hello", "world"], contents(&doc));
+    }
+
+    #[test]
+    fn indent_adds_spaces_to_rows_that_are_not_empty() {
+        let mut doc = document(&["fn main() {", "", "body();", "}"]);
+
+        doc.indent(1, 2, 4);
+        assert_eq!(vec!["fn main() {", "", "    body();", "}"], contents(&doc));
+
+        doc.indent(3, 5, 2);
+        assert_eq!(
+            vec!["fn main() {", "", "    body();", "  }"],
+            contents(&doc)
+        );
+
+        assert!(doc.undo().is_some());
+        assert_eq!(vec!["fn main() {", "", "    body();", "}"], contents(&doc));
+    }
+
+    #[test]
+    fn dedent_removes_at_most_one_level_of_leading_whitespace() {
+        let mut doc = document(&["        deep", "\ttab", "  shallow", "flush"]);
+
+        doc.dedent(0, 4, 4);
+        assert_eq!(
+            vec!["    deep", "tab", "shallow", "flush"],
+            raw_contents(&doc)
+        );
+
+        doc.dedent(1, 3, 4);
+        assert!(doc.undo().is_some());
+        assert_eq!(
+            vec!["        deep", "\ttab", "  shallow", "flush"],
+            raw_contents(&doc)
+        );
+    }
+
+    #[test]
+    fn word_count_separates_words_by_whitespace_and_punctuation() {
+        let doc = document(&["The quick  brown fox,", "", "\tjumps over the lazy dög."]);
+
+        assert_eq!(
+            WordCount {
+                words: 9,
+                lines: 3,
+                chars: 46,
+            },
+            doc.word_count()
+        );
+        assert_eq!(WordCount::default(), Document::default().word_count());
+    }
+
+    #[test]
+    fn contents_joins_the_rows_with_the_line_ending() {
+        let mut doc = document(&["hello", "", "\tworld"]);
+        assert_eq!("hello\n\n\tworld\n", doc.contents());
+
+        doc.line_ending = LineEnding::CrLf;
+        doc.trailing_newline = false;
+        assert_eq!("hello\r\n\r\n\tworld", doc.contents());
+    }
+
+    #[test]
+    fn line_returns_the_text_of_a_single_row() {
+        let doc = document(&["hello", "world"]);
+
+        assert_eq!(Some("world".to_string()), doc.line(1));
+        assert_eq!(None, doc.line(2));
+    }
+
+    #[test]
+    fn line_ending_is_detected_from_the_majority_of_lines() {
+        assert_eq!(LineEnding::Lf, LineEnding::detect("a\nb\n"));
+        assert_eq!(LineEnding::CrLf, LineEnding::detect("a\r\nb\r\n"));
+        assert_eq!(LineEnding::CrLf, LineEnding::detect("a\r\nb\nc\r\n"));
+        assert_eq!(LineEnding::Lf, LineEnding::detect("a\r\nb\n"));
+        assert_eq!(LineEnding::Lf, LineEnding::detect("a"));
+    }
+
+    #[test]
+    fn save_writes_back_the_original_line_ending() {
+        let file_name = TempFile::new("crlf.txt");
+        std::fs::write(&file_name, "one\r\ntwo\nthree\r\n").unwrap();
+
+        let mut doc = Document::open(&file_name).unwrap();
+        assert_eq!(LineEnding::CrLf, doc.line_ending());
+        assert_eq!(vec!["one", "two", "three"], contents(&doc));
+
+        save(&mut doc, None).unwrap();
+        assert_eq!(
+            "one\r\ntwo\r\nthree\r\n",
+            std::fs::read_to_string(&file_name).unwrap()
+        );
+
+        doc.set_line_ending(LineEnding::Lf);
+        assert!(doc.is_modified());
+
+        save(&mut doc, None).unwrap();
+        assert_eq!(
+            "one\ntwo\nthree\n",
+            std::fs::read_to_string(&file_name).unwrap()
+        );
+    }
+
+    #[test]
+    fn save_does_not_add_a_trailing_newline_that_was_not_there() {
+        let file_name = TempFile::new("no-newline.txt");
+        std::fs::write(&file_name, "one\ntwo").unwrap();
+
+        save(&mut Document::open(&file_name).unwrap(), None).unwrap();
+
+        assert_eq!(b"one\ntwo".to_vec(), std::fs::read(&file_name).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_preserves_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let file_name = TempFile::new("permissions.sh");
+        std::fs::write(&file_name, "").unwrap();
+        std::fs::set_permissions(&file_name, std::fs::Permissions::from_mode(0o750)).unwrap();
+
+        save(&mut document(&["echo hi"]), Some(&file_name)).unwrap();
+
+        let mode = std::fs::metadata(&file_name).unwrap().permissions().mode();
+        assert_eq!(0o750, mode & 0o777);
+    }
+
+    #[test]
+    fn save_fails_without_touching_anything_when_the_directory_is_missing() {
+        let file_name = TempFile::new("missing/save.txt");
+        let mut doc = document(&["text"]);
+
+        assert!(save(&mut doc, Some(&file_name)).is_err());
+        assert!(!std::path::Path::new(&*file_name).exists());
+    }
+
+    #[test]
+    fn undo_and_redo_revert_and_reapply_edits() {
+        let mut doc = document(&["ab"]);
+
+        doc.insert(&Position::new(2, 0), 'c').unwrap();
+        doc.insert_newline(&Position::new(1, 0));
+        assert_eq!(vec!["a", "bc"], contents(&doc));
+
+        assert_eq!(Some(Position::new(1, 0)), doc.undo());
+        assert_eq!(vec!["abc"], contents(&doc));
+        assert_eq!(Some(Position::new(2, 0)), doc.undo());
+        assert_eq!(vec!["ab"], contents(&doc));
+        assert_eq!(None, doc.undo());
+
+        assert_eq!(Some(Position::new(2, 0)), doc.redo());
+        assert_eq!(vec!["abc"], contents(&doc));
+        assert_eq!(Some(Position::new(1, 0)), doc.redo());
+        assert_eq!(vec!["a", "bc"], contents(&doc));
+        assert_eq!(None, doc.redo());
+    }
+
+    #[test]
+    fn last_edit_moves_to_each_change_and_each_undo_and_redo() {
+        let mut doc = document(&["one", "two", "three"]);
+        assert_eq!(None, doc.last_edit());
+
+        doc.insert(&Position::new(5, 2), '!').unwrap();
+        assert_eq!(Some(Position::new(5, 2)), doc.last_edit());
+        doc.insert_newline(&Position::new(0, 0));
+        assert_eq!(Some(Position::new(0, 0)), doc.last_edit());
+
+        doc.undo();
+        assert_eq!(Some(Position::new(0, 0)), doc.last_edit());
+        doc.undo();
+        assert_eq!(Some(Position::new(5, 2)), doc.last_edit());
+        doc.redo();
+        assert_eq!(Some(Position::new(5, 2)), doc.last_edit());
+    }
+
+    #[test]
+    fn only_the_most_recent_edits_can_be_undone() {
+        let mut doc = document(&[""]);
+
+        for col in 0..=MAX_UNDO_DEPTH {
+            doc.insert(&Position::new(col, 0), 'a').unwrap();
+        }
+
+        while doc.undo().is_some() {}
+        assert_eq!(vec!["a"], contents(&doc));
+    }
+
+    #[test]
+    fn edits_in_an_undo_group_are_undone_together() {
+        let mut doc = document(&[""]);
+
+        doc.begin_undo_group();
+        doc.insert(&Position::new(0, 0), 'h').unwrap();
+        doc.insert(&Position::new(1, 0), 'i').unwrap();
+        doc.end_undo_group();
+        doc.delete(&Position::new(0, 0));
+        assert_eq!(vec!["i"], contents(&doc));
+
+        doc.undo();
+        assert_eq!(vec!["hi"], contents(&doc));
+        doc.undo();
+        assert_eq!(vec![""], contents(&doc));
+    }
+
+    #[test]
+    fn grouped_edits_that_add_and_remove_rows_are_undone_and_redone_in_order() {
+        let mut doc = document(&["one", "two", "three"]);
+
+        doc.begin_undo_group();
+        doc.insert_newline(&Position::new(1, 0));
+        doc.insert_str(&Position::new(0, 1), "x\ny").unwrap();
+        doc.join_line(2);
+        doc.delete_range(&Position::new(1, 2), &Position::new(2, 3));
+        doc.end_undo_group();
+        assert_eq!(vec!["o", "x", "yree"], contents(&doc));
+
+        doc.undo();
+        assert_eq!(vec!["one", "two", "three"], contents(&doc));
+
+        doc.redo();
+        assert_eq!(vec!["o", "x", "yree"], contents(&doc));
+    }
+
+    #[test]
+    fn new_edits_clear_the_redo_stack() {
+        let mut doc = document(&[""]);
+
+        doc.insert(&Position::new(0, 0), 'a').unwrap();
+        doc.undo();
+        doc.insert(&Position::new(0, 0), 'b').unwrap();
+
+        assert_eq!(None, doc.redo());
+        assert_eq!(vec!["b"], contents(&doc));
+    }
+
+    #[test]
+    fn find_returns_the_next_match_wrapping_around_the_end() {
+        let doc = document(&["one two", "three", "two one two"]);
+
+        assert_eq!(
+            Some(Position::new(4, 0)),
+            doc.find("two", &Position::new(0, 0))
+        );
+        assert_eq!(
+            Some(Position::new(0, 2)),
+            doc.find("two", &Position::new(4, 0))
+        );
+        assert_eq!(
+            Some(Position::new(8, 2)),
+            doc.find("two", &Position::new(0, 2))
+        );
+        assert_eq!(
+            Some(Position::new(4, 0)),
+            doc.find("two", &Position::new(8, 2))
+        );
+        assert_eq!(None, doc.find("four", &Position::new(0, 0)));
+    }
+
+    #[test]
+    fn rfind_returns_the_previous_match_wrapping_around_the_start() {
+        let doc = document(&["one two", "three", "two one two"]);
+
+        assert_eq!(
+            Some(Position::new(0, 2)),
+            doc.rfind("two", &Position::new(8, 2))
+        );
+        assert_eq!(
+            Some(Position::new(4, 0)),
+            doc.rfind("two", &Position::new(0, 2))
+        );
+        assert_eq!(
+            Some(Position::new(8, 2)),
+            doc.rfind("two", &Position::new(4, 0))
+        );
+        assert_eq!(None, doc.rfind("four", &Position::new(0, 0)));
+    }
+
+    #[test]
+    fn delete_range_removes_text_across_rows() {
+        let mut doc = document(&["one two", "three", "four five"]);
+
+        doc.delete_range(&Position::new(4, 0), &Position::new(5, 2));
+        assert_eq!(vec!["one five"], contents(&doc));
+
+        doc.delete_range(&Position::new(1, 0), &Position::new(3, 0));
+        assert_eq!(vec!["o five"], contents(&doc));
+    }
+
+    #[test]
+    fn delete_range_within_a_row() {
+        let mut doc = document(&["one two three", "four"]);
+
+        let at = doc.delete_range(&Position::new(3, 0), &Position::new(7, 0));
+
+        assert_eq!(vec!["one three", "four"], contents(&doc));
+        assert_eq!(Position::new(3, 0), at);
+    }
+
+    #[test]
+    fn delete_range_joins_adjacent_rows() {
+        let mut doc = document(&["one", "two", "three"]);
+
+        let at = doc.delete_range(&Position::new(3, 0), &Position::new(0, 1));
+        assert_eq!(vec!["onetwo", "three"], contents(&doc));
+        assert_eq!(Position::new(3, 0), at);
+
+        let at = doc.delete_range(&Position::new(1, 0), &Position::new(2, 1));
+        assert_eq!(vec!["oree"], contents(&doc));
+        assert_eq!(Position::new(1, 0), at);
+    }
+
+    #[test]
+    fn delete_range_removes_every_row_in_between() {
+        let mut doc = document(&["one", "two", "three", "four", "five"]);
+
+        let at = doc.delete_range(&Position::new(0, 1), &Position::new(0, 4));
+
+        assert_eq!(vec!["one", "five"], contents(&doc));
+        assert_eq!(Position::new(0, 1), at);
+    }
+
+    #[test]
+    fn delete_range_is_clamped_to_the_document() {
+        let mut doc = document(&["one", "two"]);
+
+        let at = doc.delete_range(&Position::new(2, 0), &Position::new(9, 9));
+        assert_eq!(vec!["on"], contents(&doc));
+        assert_eq!(Position::new(2, 0), at);
+
+        let at = doc.delete_range(&Position::new(1, 0), &Position::new(0, 0));
+        assert_eq!(vec!["on"], contents(&doc));
+        assert_eq!(Position::new(1, 0), at);
+
+        let at = doc.delete_range(&Position::new(0, 5), &Position::new(0, 6));
+        assert_eq!(Position::new(2, 0), at);
+    }
+
+    #[test]
+    fn insert_str_inserts_text_within_a_row() {
+        let mut doc = document(&["one four"]);
+
+        let at = doc.insert_str(&Position::new(4, 0), "two three ").unwrap();
+
+        assert_eq!(vec!["one two three four"], contents(&doc));
+        assert_eq!(Position::new(14, 0), at);
+    }
+
+    #[test]
+    fn insert_str_starts_a_new_row_at_each_newline() {
+        let mut doc = document(&["one four", "five"]);
+
+        let at = doc
+            .insert_str(&Position::new(4, 0), "two\nthree\n")
+            .unwrap();
+
+        assert_eq!(vec!["one two", "three", "four", "five"], contents(&doc));
+        assert_eq!(Position::new(0, 2), at);
+
+        doc.undo();
+        assert_eq!(vec!["one four", "five"], contents(&doc));
+    }
+
+    #[test]
+    fn insert_str_adds_a_row_at_the_end_of_the_document() {
+        let mut doc = Document::default();
+
+        let at = doc.insert_str(&Position::new(0, 0), "a\nb").unwrap();
+        assert_eq!(vec!["a", "b"], contents(&doc));
+        assert_eq!(Position::new(1, 1), at);
+
+        assert!(doc.insert_str(&Position::new(0, 3), "c").is_err());
+    }
+
+    #[test]
+    fn text_range_joins_rows_with_newlines() {
+        let doc = document(&["one two", "three", "four five"]);
+
+        assert_eq!(
+            "two\nthree\nfour",
+            doc.text_range(&Position::new(4, 0), &Position::new(4, 2))
+        );
+        assert_eq!(
+            "ne",
+            doc.text_range(&Position::new(1, 0), &Position::new(3, 0))
+        );
+    }
+
+    #[test]
+    fn edits_mark_the_document_as_modified() {
+        let mut doc = document(&[""]);
+        assert!(!doc.is_modified());
+
+        doc.insert_newline(&Position::new(0, 0));
+        assert!(doc.is_modified());
+    }
+
+    #[test]
+    fn deleting_at_the_end_of_the_last_row_leaves_the_document_unmodified() {
+        let mut doc = document(&["ab"]);
+
+        doc.delete(&Position::new(2, 0));
+        assert_eq!(vec!["ab"], contents(&doc));
+        assert!(!doc.is_modified());
+        assert_eq!(None, doc.undo());
+    }
+
+    #[test]
+    fn next_word_start_moves_within_and_across_rows() {
+        let doc = document(&["fn main() {", "", "    let x;"]);
+
+        assert_eq!(
+            Position::new(3, 0),
+            doc.next_word_start(&Position::new(0, 0))
+        );
+        assert_eq!(
+            Position::new(0, 1),
+            doc.next_word_start(&Position::new(10, 0))
+        );
+        assert_eq!(
+            Position::new(4, 2),
+            doc.next_word_start(&Position::new(0, 1))
+        );
+        assert_eq!(
+            Position::new(10, 2),
+            doc.next_word_start(&Position::new(9, 2))
+        );
+    }
+
+    #[test]
+    fn previous_word_start_moves_within_and_across_rows() {
+        let doc = document(&["fn main() {", "", "    let x;"]);
+
+        assert_eq!(
+            Position::new(4, 2),
+            doc.previous_word_start(&Position::new(8, 2))
+        );
+        assert_eq!(
+            Position::new(0, 1),
+            doc.previous_word_start(&Position::new(4, 2))
+        );
+        assert_eq!(
+            Position::new(10, 0),
+            doc.previous_word_start(&Position::new(0, 1))
+        );
+        assert_eq!(
+            Position::new(0, 0),
+            doc.previous_word_start(&Position::new(0, 0))
+        );
+    }
+
+    #[test]
+    fn word_end_moves_within_and_across_rows() {
+        let doc = document(&["fn main() {", "", "    let x;"]);
+
+        assert_eq!(Position::new(1, 0), doc.word_end(&Position::new(0, 0)));
+        assert_eq!(Position::new(6, 0), doc.word_end(&Position::new(1, 0)));
+        assert_eq!(Position::new(6, 2), doc.word_end(&Position::new(10, 0)));
     }
 }
@@ -1,12 +1,70 @@
-use crate::communication::{Command, Message};
+use crate::communication::{Command, Message, FLASH_DURATION};
 use crate::component::{Component, Window};
-use crate::mode::Normal;
+use crate::config::Config;
+use crate::keymap::Keymap;
+use crate::mode::{Normal, Replace, Search};
 use crate::render::{View, Viewport};
-use crate::{Canvas, Event, EventStream, Mode};
+use crate::ui::{Position, Rect, Theme};
+use crate::{Canvas, Event, EventStream, Key, Mode, MouseKind};
 use anyhow::{Error, Result};
+use std::cmp;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
 
+/// How often a `Message::Tick` is sent unless configured otherwise.
+const DEFAULT_TICK_RATE: Duration = Duration::from_millis(250);
+
+/// How many `Command` results can be waiting to be handled unless configured otherwise.
+const DEFAULT_CHANNEL_CAPACITY: usize = 64;
+
+/// The longest that rendering is put off for while input keeps arriving, so that a long burst of
+/// input is still drawn as it is handled.
+const MAX_FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// The most times that `.` repeats the last change, so that a large count can't queue more
+/// messages than there is memory for.
+const MAX_REPEAT_COUNT: usize = 10_000;
+
+/// The number of `Command`s that are still running in `Editor::consume`.
+#[derive(Default)]
+struct Running {
+    /// Commands that are the result of input are in the foreground and further input waits for
+    /// them to complete, so that a command line is parsed before the next key is handled.
+    /// Commands that follow on from the result of another command, such as each step of a grep,
+    /// and those that run in the background, such as saves, allow input to continue.
+    foreground: usize,
+    /// Background commands are waited for once the input has ended, so that a save started by
+    /// the last of the input still finishes.
+    background: usize,
+}
+
+impl Running {
+    /// The count of foreground, or background, commands.
+    fn count(&mut self, foreground: bool) -> &mut usize {
+        if foreground {
+            &mut self.foreground
+        } else {
+            &mut self.background
+        }
+    }
+
+    /// Returns `true` once every command has completed.
+    fn is_empty(&self) -> bool {
+        self.foreground == 0 && self.background == 0
+    }
+}
+
+/// The outcome of dispatching a `Message` within the `Editor`.
+enum Dispatched {
+    /// The message was expanded into further messages, which have been queued, so there is
+    /// nothing new to render yet.
+    Expanded,
+    /// The message was handled by the root `Component`, possibly resulting in a `Command`.
+    Updated(Option<Command>),
+}
+
 /// `Editor` is the entry point into the application and is responsible for orchestrating
 /// communication between `Component`s.
 pub struct Editor<'a, VC, C>
@@ -14,9 +72,20 @@ where
     VC: View + Component,
     C: Canvas,
 {
+    channel_capacity: usize,
+    keymap: Keymap,
+    /// The messages that make up the last change, replayed by `Message::RepeatLastChange`.
+    last_change: Vec<Message>,
+    /// The change being recorded while in Insert mode, it becomes the last change on leaving.
+    recording: Option<Vec<Message>>,
     mode: Mode,
     root_component: VC,
     should_quit: bool,
+    /// The `Command`s that result from opening a file before the Editor is run, such as counting
+    /// the lines of a large file. They are started in the background once it runs.
+    startup_commands: Vec<Command>,
+    theme: Theme,
+    tick_rate: Duration,
     viewport: Viewport<'a, C>,
 }
 
@@ -24,24 +93,127 @@ impl<'a, C> Editor<'a, Window, C>
 where
     C: Canvas,
 {
-    /// Create a new editor using the default `View` `Component` and the given `Canvas`.
+    /// Create a new editor using the default `View` `Component` and the given `Canvas`. The user's
+    /// config file is loaded, if it is invalid then the default `Config` is used and the problem
+    /// is shown in the status bar.
     ///
     /// # Errors
     ///
     /// Can error while creating the `Viewport` if the underlying `Canvas` has IO issues.
     pub fn new(canvas: &'a mut C) -> Result<Self> {
+        let (config, config_error) = match Config::load() {
+            Ok(config) => (config, None),
+            Err(e) => (Config::default(), Some(e)),
+        };
+
+        let mut editor = Self::with_config(canvas, config)?;
+
+        if let Some(e) = config_error {
+            editor
+                .root_component
+                .update(Message::StatusError(format!("{e:#}")))?;
+        }
+
+        Ok(editor)
+    }
+
+    /// Open the file in the first buffer rather than starting on the welcome screen. A file that
+    /// does not exist yet is created once it is saved, any other problem opening the file is
+    /// shown in the status bar.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the root `Component` fails to handle the file being opened.
+    pub fn with_file(mut self, file_name: &str) -> Result<Self> {
+        let cmd = self
+            .root_component
+            .update(Message::OpenFile(file_name.into()))?;
+        self.startup_commands.extend(cmd);
+
+        Ok(self)
+    }
+
+    /// Open the file read-only in the first buffer, like `:view`, rather than starting on the
+    /// welcome screen.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the root `Component` fails to handle the file being opened.
+    pub fn with_read_only_file(mut self, file_name: &str) -> Result<Self> {
+        let cmd = self
+            .root_component
+            .update(Message::ViewFile(file_name.into()))?;
+        self.startup_commands.extend(cmd);
+
+        Ok(self)
+    }
+
+    /// Create a new editor using the default `View` `Component`, the given `Canvas` and the given
+    /// `Config`.
+    ///
+    /// # Errors
+    ///
+    /// Can error while creating the `Viewport` if the underlying `Canvas` has IO issues.
+    pub fn with_config(canvas: &'a mut C, config: Config) -> Result<Self> {
         use anyhow::Context;
 
         let mode = Mode::default();
         let viewport = Viewport::new(canvas).context("unable to initialise Viewport")?;
+        let keymap = Keymap::new(&config.keymap);
+        let theme = config.theme;
 
         Ok(Self {
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            keymap,
+            last_change: Vec::new(),
+            recording: None,
             mode: mode.clone(),
-            root_component: Window::new(viewport.area(), mode),
+            root_component: Window::new(viewport.area(), mode, config),
             should_quit: false,
+            startup_commands: Vec::new(),
+            theme,
+            tick_rate: DEFAULT_TICK_RATE,
             viewport,
         })
     }
+
+    /// The whole text of the document in the focused buffer as it would be saved, or `None`
+    /// when no file has been opened. The rest of a large file is read first.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the rest of a large file can't be read.
+    pub fn contents(&mut self) -> Result<Option<String>> {
+        use anyhow::Context;
+
+        let Some(buffer) = self.root_component.active_buffer_mut() else {
+            return Ok(None);
+        };
+        buffer
+            .load_all()
+            .context("unable to read the rest of the document")?;
+
+        Ok(Some(buffer.document().contents()))
+    }
+
+    /// The text of the line at the given index in the focused buffer, without its line ending.
+    /// The rows of a large file are read up to the line first.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the rows of a large file can't be read.
+    pub fn line(&mut self, index: usize) -> Result<Option<String>> {
+        use anyhow::Context;
+
+        let Some(buffer) = self.root_component.active_buffer_mut() else {
+            return Ok(None);
+        };
+        buffer
+            .load(index.saturating_add(1))
+            .context("unable to read the rest of the document")?;
+
+        Ok(buffer.document().line(index))
+    }
 }
 
 impl<'a, VC, C> Editor<'a, VC, C>
@@ -49,107 +221,1043 @@ where
     VC: Component + View,
     C: Canvas,
 {
-    /// Consume the given `EventStream` to run/drive the Editor.
+    /// Set how often a `Message::Tick` is sent to the root `Component`.
+    #[must_use]
+    pub fn with_tick_rate(mut self, tick_rate: Duration) -> Self {
+        self.tick_rate = tick_rate;
+        self
+    }
+
+    /// Set how many `Command` results can be waiting to be handled at once, at least 1. Commands
+    /// are spawned independently so many can complete together, such as each step of a grep
+    /// alongside the timers clearing flash messages. Once the channel is full the tasks running
+    /// them wait for the Editor to catch up, so a small capacity only slows them down rather than
+    /// losing results.
+    #[must_use]
+    pub fn with_channel_capacity(mut self, channel_capacity: usize) -> Self {
+        self.channel_capacity = channel_capacity;
+        self
+    }
+
+    /// Consume the given `EventStream` to run/drive the Editor. The Editor runs until it is told
+    /// to quit or the `EventStream` ends, in which case any messages that are already queued are
+    /// processed before returning.
     ///
     /// # Errors
     ///
-    /// Will return `Err` when a message was received on the `err_tx`.
+    /// Will return `Err` when reading from the `EventStream` or rendering fails. Errors from
+    /// updating the root `Component` are shown in the status bar instead.
     pub async fn consume(&mut self, mut event_stream: EventStream) -> Result<()> {
         use anyhow::Context;
 
-        // TODO: figure out the buffer size of these channels. Is this even async?
-        let (err_tx, mut err_rx) = mpsc::channel::<Error>(1);
-        let (cmd_tx, mut cmd_rx) = mpsc::channel::<Command>(1);
-        let (msg_tx, mut msg_rx) = mpsc::channel(1);
+        // Results of `Command`s arrive on this channel once their spawned task completes, along
+        // with whether the command was in the foreground.
+        let (msg_tx, mut msg_rx) = mpsc::channel(self.channel_capacity.max(1));
+
+        // Messages produced by the Editor itself are queued here and processed, in order, before
+        // any further input is read. This ensures that the effects of one key press are seen by
+        // the next, such as `i` entering Insert mode before the following character is typed.
+        let mut pending = VecDeque::new();
+        let mut input_closed = false;
+        let mut running = Running::default();
+
+        for cmd in self.startup_commands.drain(..) {
+            *running.count(false) += 1;
+            spawn_command(msg_tx.clone(), false, cmd);
+        }
+
+        // Ticks that are missed while the Editor is busy are skipped rather than sent in a burst.
+        let mut ticker = tokio::time::interval(self.tick_rate);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
         // Render the initial view so that we don't have to wait for an input event to
         // see something on the screen.
         self.viewport
-            .render(&self.root_component)
+            .render(&self.root_component, &self.theme)
             .context("unable to render the initial view")?;
 
+        let mut last_render = Instant::now();
+        let mut needs_render = false;
+
         while !self.should_quit {
-            tokio::select! {
-                Some(e) = err_rx.recv() => {
-                    return Err(e);
-                }
-                Some(event) = event_stream.next() => {
-                    match event {
-                        Event::KeyPressed(key) => {
-                            if let Some(msg) = match self.mode {
-                                Mode::Execute(ref mode) => mode.handle(key),
-                                Mode::Insert(ref mode) => mode.handle(key),
-                                Mode::Normal(ref mut mode) => mode.handle(key),
-                            } {
-                                msg_tx
-                                    .send(msg)
-                                    .await
-                                    .expect("unable to send msg on closed msg_tx channel");
-                            }
+            let (msg, foreground) = if let Some(queued) = pending.pop_front() {
+                queued
+            } else {
+                // Keys released by the keymap are handled as input, so they wait for any
+                // foreground commands and are handled before any further input is read.
+                let key = if running.foreground == 0 {
+                    self.keymap.next_key()
+                } else {
+                    None
+                };
+
+                let read_input = running.foreground == 0 && !input_closed;
+
+                match key {
+                    Some(key) => {
+                        let Some(msg) = self.message_for_released_key(key) else {
+                            continue;
+                        };
+                        (msg, true)
+                    }
+                    None if input_closed && running.is_empty() => break,
+                    None => tokio::select! {
+                        biased;
+
+                        Some((foreground, msg)) = msg_rx.recv() => {
+                            *running.count(foreground) -= 1;
+                            (msg, foreground)
                         }
-                        Event::ReadFailed(e) => {
-                            err_tx
-                                .send(Error::new(e))
-                                .await
-                                .expect("unable to send on closed err_tx channel");
+                        event = event_stream.next(), if read_input => {
+                            let Some(msg) = self.message_for_input(event, &mut input_closed)? else {
+                                continue;
+                            };
+                            (msg, true)
                         }
-                        _ => (),
-                    }
+                        _ = ticker.tick() => {
+                            self.keymap.expire(&self.mode);
+                            (Message::Tick, false)
+                        }
+                    },
                 }
-                Some(cmd) = cmd_rx.recv() => {
-                    let msg_tx = msg_tx.clone();
-                    // Each command is spawned in its own async block as they may take time to complete.
-                    tokio::spawn(async move {
-                        msg_tx
-                            .send(cmd())
-                            .await
-                            .expect("unable to send cmd result on closed msg_tx channel");
-                    });
+            };
+
+            let is_flash = matches!(msg, Message::Flash(_));
+            let is_tick = matches!(msg, Message::Tick);
+            let cmd_foreground = foreground && !msg.runs_in_background();
+
+            let cmd = match self.dispatch(msg, foreground, &mut pending) {
+                Dispatched::Expanded => continue,
+                Dispatched::Updated(cmd) => cmd,
+            };
+
+            if let Some(cmd) = cmd {
+                *running.count(cmd_foreground) += 1;
+                spawn_command(msg_tx.clone(), cmd_foreground, cmd);
+            }
+
+            // The flash is cleared from the background so that input is not held up while the
+            // timer runs. The timer starts once the flash is shown so that it never fires early.
+            if is_flash {
+                clear_flash_later(msg_tx.clone());
+            }
+
+            // Ticks only drive timers, and anything that they change is drawn by the messages that
+            // follow, such as the keys released by the keymap or the reply once a swap file has
+            // been written. Redrawing for every tick would draw the same view over and over.
+            if is_tick {
+                continue;
+            }
+
+            // Rendering waits while more input can be handled straight away, such as keys
+            // repeating while one is held down or arriving together over a slow connection, so
+            // that a burst of input is drawn once in its final state.
+            if last_render.elapsed() < MAX_FRAME_INTERVAL {
+                let reading_input = running.foreground == 0;
+
+                if !pending.is_empty() || (reading_input && self.keymap.has_ready_keys()) {
+                    needs_render = true;
+                    continue;
                 }
-                Some(msg) = msg_rx.recv() => {
-                    if let Message::Quit = msg {
-                        self.should_quit = true;
+
+                if reading_input && !input_closed {
+                    if let Some(event) = read_ready_event(&mut event_stream).await {
+                        if let Some(msg) = self.message_for_input(event, &mut input_closed)? {
+                            pending.push_back((msg, true));
+                        }
+
+                        needs_render = true;
+                        continue;
                     }
+                }
+            }
 
-                    if let Message::EnterMode(mode) = msg.clone() {
-                        self.mode = mode;
+            self.render()?;
+            last_render = Instant::now();
+            needs_render = false;
+        }
+
+        // The last of the input may have ended the loop before it was drawn.
+        if needs_render {
+            self.render()?;
+        }
+
+        Ok(())
+    }
+
+    /// Handle a single input `Event` without an async runtime, for driving the Editor from
+    /// another application or a test. The `Event` goes through the same keymap and mode dispatch
+    /// as in `consume`. Any `Command`s that result are run straight away, along with those that
+    /// follow on from them, so everything the `Event` causes has been handled and rendered by the
+    /// time this returns. The first `Event` also runs those left by opening a file on start. Flash messages are not cleared automatically as there is no timer.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` when the `Event` reports that reading input failed or rendering fails.
+    /// Errors from updating the root `Component` are shown in the status bar instead.
+    pub fn handle_event(&mut self, event: Event) -> Result<()> {
+        let mut pending: VecDeque<_> = self
+            .startup_commands
+            .drain(..)
+            .map(|cmd| (cmd(), false))
+            .collect();
+
+        if let Some(msg) = self.message_for_event(event)? {
+            pending.push_back((msg, true));
+        }
+
+        while !self.should_quit {
+            let (msg, foreground) = match pending.pop_front() {
+                Some(queued) => queued,
+                None => match self.keymap.next_key() {
+                    Some(key) => {
+                        let Some(msg) = self.message_for_released_key(key) else {
+                            continue;
+                        };
+                        (msg, true)
                     }
+                    None => break,
+                },
+            };
 
-                    if let Message::ParseCommandLineInput(input) = msg {
-                        if let Mode::Execute(ref mode) = self.mode {
-                            let msg = mode.parse(&input);
+            if let Dispatched::Updated(Some(cmd)) = self.dispatch(msg, foreground, &mut pending) {
+                pending.push_back((cmd(), foreground));
+            }
+        }
 
-                            self.mode = Mode::Normal(Normal::default());
+        self.render()
+    }
 
-                            if let Some(msg) = msg {
-                                 msg_tx
-                                .send(msg)
-                                .await
-                                .expect("unable to send msg on closed msg_tx channel");
-                            }
-                        }
+    /// Returns `true` once the Editor has been told to quit.
+    #[must_use]
+    pub fn should_quit(&self) -> bool {
+        self.should_quit
+    }
 
-                        continue;
+    /// Handle a message within the Editor, passing it on to the root `Component` unless it
+    /// expands into other messages. Expanded messages are queued at the front of `pending` so
+    /// that they are handled next, in order.
+    fn dispatch(
+        &mut self,
+        msg: Message,
+        foreground: bool,
+        pending: &mut VecDeque<(Message, bool)>,
+    ) -> Dispatched {
+        if let Message::RepeatLastChange(n) = msg {
+            let change = self.last_change.iter().cloned().rev();
+            for msg in std::iter::repeat_n(change, cmp::min(n, MAX_REPEAT_COUNT)).flatten() {
+                pending.push_front((msg, foreground));
+            }
+
+            return Dispatched::Expanded;
+        }
+
+        if let Message::Batch(msgs) = msg {
+            for msg in msgs.into_iter().rev() {
+                pending.push_front((msg, foreground));
+            }
+
+            return Dispatched::Expanded;
+        }
+
+        if let Message::ForceQuit = msg {
+            self.should_quit = true;
+        }
+
+        if let Message::EnterMode(mode) = msg.clone() {
+            self.mode = mode;
+        }
+
+        if let Message::ParseCommandLineInput(input) = msg {
+            let msg = match self.mode {
+                Mode::Execute(ref mode) => mode.parse(&input),
+                Mode::Search(_) => Some(Search::parse(&input)),
+                _ => None,
+            };
+
+            self.mode = Mode::Normal(Normal::default());
+
+            if let Some(msg) = msg {
+                pending.push_front((msg, foreground));
+            }
+
+            return Dispatched::Expanded;
+        }
+
+        match self.root_component.update(msg) {
+            Ok(cmd) => Dispatched::Updated(cmd),
+            Err(e) => {
+                // Errors from components, such as failing to save a document, are recoverable so
+                // they are reported to the user instead of stopping the Editor.
+                pending.push_front((Message::StatusError(format!("{e:#}")), foreground));
+                Dispatched::Expanded
+            }
+        }
+    }
+
+    /// Render the root `Component` with the cursor shape for the current mode.
+    fn render(&mut self) -> Result<()> {
+        use anyhow::Context;
+
+        self.viewport
+            .set_cursor_shape(self.mode.cursor_shape())
+            .context("unable to update cursor shape")?;
+
+        self.viewport
+            .render(&self.root_component, &self.theme)
+            .context("rendering error occurred")
+    }
+
+    /// Record the message for a key if it is part of a change, so that the change can be repeated.
+    /// A change that enters Insert or Replace mode, such as `A`, includes everything typed until
+    /// the mode is left. Motions are not recorded.
+    fn record_change(&mut self, msg: &Message) {
+        match self.mode {
+            Mode::Normal(_) if enters_typing_mode(msg) => self.recording = Some(vec![msg.clone()]),
+            Mode::Normal(_) if msg.is_change() => self.last_change = vec![msg.clone()],
+            Mode::Insert(_) | Mode::Replace(_) => {
+                if let Some(recording) = self.recording.as_mut() {
+                    if let Message::EnterMode(Mode::Normal(_)) = msg {
+                        recording.push(msg.clone());
+                        self.last_change = self.recording.take().unwrap_or_default();
+                    } else if msg.is_change() {
+                        recording.push(msg.clone());
                     }
+                }
+            }
+            _ => (),
+        }
+    }
 
-                    match self.root_component.update(msg) {
-                        Ok(Some(cmd)) => {
-                            cmd_tx.send(cmd).await.expect("unable to send on closed cmd_tx channel");
-                        }
-                        Err(e) => {
-                            err_tx.send(e.context("error during root_component update")).await.expect("unable to send on closed err_tx channel");
-                        }
-                        _ => (),
+    /// Map a `Key` to the `Message` that it represents in the current mode.
+    fn message_for_key(&mut self, key: Key) -> Option<Message> {
+        match self.mode {
+            Mode::Execute(ref mode) => mode.handle(key),
+            Mode::Insert(ref mode) => mode.handle(key),
+            Mode::Replace(_) => Replace::handle(key),
+            Mode::Normal(ref mut mode) => mode.handle(key),
+            Mode::Search(_) => Search::handle(key),
+            Mode::Visual(ref mut mode) => mode.handle(key),
+        }
+    }
+
+    /// Map a `Key` released by the keymap to its `Message`, recording it if it is part of a
+    /// change.
+    fn message_for_released_key(&mut self, key: Key) -> Option<Message> {
+        let msg = self.message_for_key(key)?;
+        self.record_change(&msg);
+        Some(msg)
+    }
+
+    /// Map the next input `Event` to its `Message`, or flush the keymap once the input has
+    /// ended as no more keys are coming to complete a mapping.
+    fn message_for_input(
+        &mut self,
+        event: Option<Event>,
+        input_closed: &mut bool,
+    ) -> Result<Option<Message>> {
+        if let Some(event) = event {
+            return self.message_for_event(event);
+        }
+
+        self.keymap.flush(&self.mode);
+        *input_closed = true;
+
+        Ok(None)
+    }
+
+    /// Map an input `Event` to the `Message` that it represents in the current mode.
+    fn message_for_event(&mut self, event: Event) -> Result<Option<Message>> {
+        use anyhow::Context;
+
+        Ok(match event {
+            // Keys go through the keymap first, they are handled once the keymap releases them.
+            Event::KeyPressed(key) => {
+                self.keymap.press(&self.mode, key);
+                None
+            }
+            Event::MouseInput { col, row, kind } => match kind {
+                MouseKind::LeftClick => Some(Message::MouseClick(Position::new(col, row))),
+                MouseKind::ScrollUp => Some(Message::MoveCursorUp(1)),
+                MouseKind::ScrollDown => Some(Message::MoveCursorDown(1)),
+                MouseKind::Unknown => None,
+            },
+            Event::WindowResized(width, height) => {
+                let area = Rect::new(usize::from(width), usize::from(height));
+
+                self.viewport
+                    .resize(area)
+                    .context("unable to resize viewport")?;
+
+                Some(Message::Resize(area))
+            }
+            // Pasted text bypasses the keymap so that it is inserted exactly as it was copied.
+            // Terminals send line breaks in pasted text as carriage returns.
+            Event::Paste(text) => match self.mode {
+                Mode::Insert(_) => Some(Message::InsertText(
+                    text.replace("\r\n", "\n").replace('\r', "\n"),
+                )),
+                _ => None,
+            },
+            Event::ReadFailed(e) => return Err(Error::new(e).context("unable to read input")),
+        })
+    }
+}
+
+/// Run the `Command` on the blocking thread pool, as it may take time to complete or block on IO
+/// such as writing a file, sending the result back to the `Editor` along with whether the command
+/// was in the foreground. The `Editor` may have quit by the time the command completes, there is
+/// nothing left to handle the result in that case so it is dropped.
+fn spawn_command(
+    msg_tx: mpsc::Sender<(bool, Message)>,
+    foreground: bool,
+    cmd: Command,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        if let Ok(msg) = tokio::task::spawn_blocking(cmd).await {
+            let _ = msg_tx.send((foreground, msg)).await;
+        }
+    })
+}
+
+/// The next input `Event`, if one can be read straight away without waiting for it. `Some(None)`
+/// means that the input has ended.
+async fn read_ready_event(event_stream: &mut EventStream) -> Option<Option<Event>> {
+    tokio::select! {
+        biased;
+
+        event = event_stream.next() => Some(event),
+        () = std::future::ready(()) => None,
+    }
+}
+
+/// Send `Message::ClearFlash` once the flash has been shown for `FLASH_DURATION`.
+fn clear_flash_later(msg_tx: mpsc::Sender<(bool, Message)>) {
+    tokio::spawn(async move {
+        tokio::time::sleep(FLASH_DURATION).await;
+        // The Editor may have stopped before the flash expired, there is nothing left to clear in
+        // that case.
+        let _ = msg_tx.send((false, Message::ClearFlash)).await;
+    });
+}
+
+/// Returns `true` if the message, or any message in a batch, enters Insert or Replace mode.
+fn enters_typing_mode(msg: &Message) -> bool {
+    match msg {
+        Message::EnterMode(Mode::Insert(_) | Mode::Replace(_)) => true,
+        Message::Batch(msgs) => msgs.iter().any(enters_typing_mode),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        spawn_command, Editor, Keymap, Viewport, DEFAULT_CHANNEL_CAPACITY, DEFAULT_TICK_RATE,
+    };
+    use crate::communication::{Command, Message};
+    use crate::component::Component;
+    use crate::render::{Frame, View};
+    use crate::testing::TempFile;
+    use crate::ui::Theme;
+    use crate::{from_events, from_keys, parse_keys, Config, Event, Mode, TestCanvas};
+    use anyhow::Result;
+
+    /// An Editor driving the given component, rather than a `Window`, to see what it is sent.
+    fn with_root_component<VC: Component + View>(
+        canvas: &mut TestCanvas,
+        root_component: VC,
+    ) -> Editor<'_, VC, TestCanvas> {
+        Editor {
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            keymap: Keymap::default(),
+            last_change: Vec::new(),
+            recording: None,
+            mode: Mode::default(),
+            root_component,
+            should_quit: false,
+            startup_commands: Vec::new(),
+            theme: Theme::default(),
+            tick_rate: DEFAULT_TICK_RATE,
+            viewport: Viewport::new(canvas).unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn typed_text_is_saved_and_rendered() {
+        let file_name = TempFile::new("editor.txt");
+
+        let mut canvas = TestCanvas::new(80, 10);
+
+        Editor::with_config(&mut canvas, Config::default())
+            .unwrap()
+            .consume(from_keys(&format!(
+                "ihello<Enter>world<Esc>:w {}<Enter>",
+                &*file_name
+            )))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            "hello\nworld\n",
+            std::fs::read_to_string(&file_name).unwrap()
+        );
+        assert!(canvas.row(0).contains("hello"));
+        assert!(canvas.row(1).contains("world"));
+        assert!(canvas.row(9).contains("2L written"));
+    }
+
+    #[tokio::test]
+    async fn options_are_changed_while_running() {
+        let mut canvas = TestCanvas::new(60, 10);
+
+        Editor::with_config(&mut canvas, Config::default())
+            .unwrap()
+            .consume(from_keys(
+                "ihello<Esc>:set nonumber<Enter>:set tabstop=0<Enter>",
+            ))
+            .await
+            .unwrap();
+
+        assert!(canvas.row(0).starts_with("hello"));
+        assert!(canvas
+            .row(9)
+            .starts_with("E487: Argument must be positive: tabstop=0"));
+    }
+
+    #[tokio::test]
+    async fn rapid_input_producing_commands_does_not_deadlock() {
+        let mut canvas = TestCanvas::new(60, 10);
+
+        let mut editor = Editor::with_config(&mut canvas, Config::default())
+            .unwrap()
+            .with_channel_capacity(1);
+        let consume = editor.consume(from_keys(&format!("i<Esc>{}", "n".repeat(500))));
+
+        tokio::time::timeout(std::time::Duration::from_secs(10), consume)
+            .await
+            .expect("editor did not finish handling the input")
+            .unwrap();
+
+        drop(editor);
+        assert!(canvas.row(9).starts_with("No previous search pattern"));
+    }
+
+    #[tokio::test]
+    async fn ticks_do_not_redraw_an_unchanged_view() {
+        use std::cell::Cell;
+
+        /// Counts how many times it is rendered, and the ticks that it is sent.
+        #[derive(Default)]
+        struct Counter {
+            renders: Cell<usize>,
+            ticks: usize,
+        }
+
+        impl Component for Counter {
+            fn update(&mut self, msg: Message) -> Result<Option<Command>> {
+                if msg == Message::Tick {
+                    self.ticks += 1;
+                }
+
+                Ok(None)
+            }
+        }
+
+        impl View for Counter {
+            fn render_to(&self, _: &mut Frame, _: &Theme) {
+                self.renders.set(self.renders.get() + 1);
+            }
+        }
+
+        let mut canvas = TestCanvas::new(60, 10);
+        let mut editor = with_root_component(&mut canvas, Counter::default())
+            .with_tick_rate(std::time::Duration::from_millis(1));
+
+        // The input stays open, without any events, while many ticks pass.
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            drop(event_tx);
+        });
+
+        editor
+            .consume(Box::pin(tokio_stream::wrappers::ReceiverStream::new(
+                event_rx,
+            )))
+            .await
+            .unwrap();
+
+        assert!(editor.root_component.ticks > 1);
+        assert_eq!(1, editor.root_component.renders.get());
+    }
+
+    #[tokio::test]
+    async fn input_is_handled_while_a_save_is_written() {
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        /// Saves once it has been sent a character, noting what it handles in order.
+        struct SlowSave {
+            handled: Vec<Message>,
+            typed_tx: mpsc::Sender<()>,
+            typed_rx: Option<mpsc::Receiver<()>>,
+        }
+
+        impl Component for SlowSave {
+            fn update(&mut self, msg: Message) -> Result<Option<Command>> {
+                let cmd: Option<Command> = match msg {
+                    Message::EndCommandLineInput => {
+                        Some(Box::new(|| Message::ParseCommandLineInput("w".into())))
+                    }
+                    Message::Save => {
+                        let typed_rx = self.typed_rx.take().unwrap();
+                        Some(Box::new(move || {
+                            let _ = typed_rx.recv_timeout(Duration::from_secs(5));
+                            Message::StatusInfo("written".into())
+                        }))
+                    }
+                    Message::InsertChar(_) => {
+                        self.typed_tx.send(()).unwrap();
+                        None
                     }
+                    _ => None,
+                };
+
+                if let Message::InsertChar(_) | Message::StatusInfo(_) = msg {
+                    self.handled.push(msg);
+                }
+
+                Ok(cmd)
+            }
+        }
+
+        impl View for SlowSave {
+            fn render_to(&self, _: &mut Frame, _: &Theme) {}
+        }
+
+        let (typed_tx, typed_rx) = mpsc::channel();
+        let mut canvas = TestCanvas::new(60, 10);
+        let mut editor = with_root_component(
+            &mut canvas,
+            SlowSave {
+                handled: Vec::new(),
+                typed_tx,
+                typed_rx: Some(typed_rx),
+            },
+        );
+
+        editor.consume(from_keys(":<Enter>ix")).await.unwrap();
+
+        assert_eq!(
+            vec![
+                Message::InsertChar('x'),
+                Message::StatusInfo("written".into())
+            ],
+            editor.root_component.handled
+        );
+    }
+
+    #[tokio::test]
+    async fn command_results_are_dropped_once_the_editor_has_quit() {
+        let (msg_tx, msg_rx) = tokio::sync::mpsc::channel(1);
+        let cmd: Command = Box::new(|| {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            Message::Tick
+        });
+
+        let handle = spawn_command(msg_tx, true, cmd);
+        drop(msg_rx);
+
+        assert!(handle.await.is_ok());
+    }
+
+    #[test]
+    fn events_can_be_handled_without_a_runtime() {
+        let mut canvas = TestCanvas::new(60, 10);
+        let mut editor = Editor::with_config(&mut canvas, Config::default()).unwrap();
+
+        for key in parse_keys("ihello world<Esc>bx:words<Enter>") {
+            editor.handle_event(Event::KeyPressed(key)).unwrap();
+        }
+
+        assert!(!editor.should_quit());
+        drop(editor);
+
+        assert!(canvas.row(0).contains("hello orld"));
+        assert!(canvas.row(9).starts_with("2 words, 1 lines, 10 characters"));
+    }
+
+    #[test]
+    fn changes_replace_text_and_are_undone_in_one_step() {
+        let first_row = |keys: &str| {
+            let mut canvas = TestCanvas::new(60, 10);
+            let mut editor = Editor::with_config(&mut canvas, Config::default()).unwrap();
+
+            for key in parse_keys(keys) {
+                editor.handle_event(Event::KeyPressed(key)).unwrap();
+            }
+
+            drop(editor);
+            canvas.row(0)
+        };
+
+        assert!(first_row("i    foo bar<Esc>^cwqux<Esc>").contains("    qux bar "));
+        assert!(first_row("i    foo bar<Esc>^cwqux<Esc>u").contains("    foo bar "));
+        assert!(first_row("i    foo bar<Esc>^wCend<Esc>").contains("    foo end "));
+        assert!(first_row("i    foo bar<Esc>^ccline<Esc>").contains("    line "));
+    }
+
+    #[test]
+    fn document_text_can_be_read_from_the_editor() {
+        let mut canvas = TestCanvas::new(60, 10);
+        let mut editor = Editor::with_config(&mut canvas, Config::default()).unwrap();
+
+        for key in parse_keys("ihello<Enter>world<Esc>") {
+            editor.handle_event(Event::KeyPressed(key)).unwrap();
+        }
+
+        assert_eq!(
+            Some("hello\nworld\n".to_string()),
+            editor.contents().unwrap()
+        );
+        assert_eq!(Some("world".to_string()), editor.line(1).unwrap());
+        assert_eq!(None, editor.line(2).unwrap());
+    }
 
-                    if let Err(e) = self.viewport.render(&self.root_component).context("rendering error occurred") {
-                        err_tx.send(e).await.expect("unable to send on closed err_tx channel");
+    #[test]
+    fn the_text_of_a_large_file_is_read_from_the_editor_in_full() {
+        let file_name = TempFile::new("editor-large-contents.txt");
+        let contents = "line\n".repeat(1_000_000) + "last\n";
+        std::fs::write(&file_name, &contents).unwrap();
+
+        let mut canvas = TestCanvas::new(60, 10);
+        let mut editor = Editor::with_config(&mut canvas, Config::default())
+            .unwrap()
+            .with_file(&file_name)
+            .unwrap();
+
+        assert_eq!(Some("last".to_string()), editor.line(1_000_000).unwrap());
+        assert!(editor.contents().unwrap() == Some(contents));
+    }
+
+    #[test]
+    fn handling_events_stops_once_the_editor_quits() {
+        let mut canvas = TestCanvas::new(60, 10);
+        let mut editor = Editor::with_config(&mut canvas, Config::default()).unwrap();
+
+        for key in parse_keys(":q!<Enter>") {
+            editor.handle_event(Event::KeyPressed(key)).unwrap();
+        }
+
+        assert!(editor.should_quit());
+    }
+
+    #[tokio::test]
+    async fn input_that_arrives_together_is_rendered_once() {
+        let mut canvas = TestCanvas::new(60, 10);
+
+        Editor::with_config(&mut canvas, Config::default())
+            .unwrap()
+            .consume(from_keys("ihello world"))
+            .await
+            .unwrap();
+
+        // The initial view and the final state, rather than a frame for every key.
+        assert!(canvas.frame_count() <= 3, "{} frames", canvas.frame_count());
+        let start = canvas.row(0).find("hello world").unwrap();
+        assert_eq!(
+            crate::ui::Position::new(start + 11, 0),
+            canvas.cursor_position()
+        );
+    }
+
+    #[tokio::test]
+    async fn word_count_is_reported() {
+        let mut canvas = TestCanvas::new(60, 10);
+
+        Editor::with_config(&mut canvas, Config::default())
+            .unwrap()
+            .consume(from_keys("ihello, world<Esc>:words<Enter>"))
+            .await
+            .unwrap();
+
+        assert!(canvas.row(9).starts_with("2 words, 1 lines, 12 characters"));
+    }
+
+    #[tokio::test]
+    async fn unknown_options_are_reported() {
+        let mut canvas = TestCanvas::new(60, 10);
+
+        Editor::with_config(&mut canvas, Config::default())
+            .unwrap()
+            .consume(from_keys(":set sideways<Enter>"))
+            .await
+            .unwrap();
+
+        assert!(canvas.row(9).starts_with("E518: Unknown option: sideways"));
+    }
+
+    #[tokio::test]
+    async fn component_errors_are_shown_without_stopping_the_editor() {
+        let file_name = std::env::temp_dir()
+            .join(format!("velm-{}-missing", std::process::id()))
+            .join("editor.txt")
+            .to_string_lossy()
+            .into_owned();
+
+        let mut canvas = TestCanvas::new(60, 10);
+
+        Editor::with_config(&mut canvas, Config::default())
+            .unwrap()
+            .consume(from_keys(&format!(
+                "ihello<Esc>:w {file_name}<Enter>aworld<Esc>"
+            )))
+            .await
+            .unwrap();
+
+        assert!(canvas.row(0).contains("helloworld"));
+        assert!(canvas.row(9).starts_with("unable to save document"));
+    }
+
+    #[tokio::test]
+    async fn mapped_keys_are_handled_in_place_of_the_keys_pressed() {
+        let mut config = Config::default();
+        config.keymap.insert.insert("jk".into(), "<Esc>".into());
+
+        let mut canvas = TestCanvas::new(40, 10);
+
+        Editor::with_config(&mut canvas, config)
+            .unwrap()
+            .consume(from_keys("iajkib<Esc>Aj"))
+            .await
+            .unwrap();
+
+        assert!(canvas.row(0).contains("abj "));
+    }
+
+    #[tokio::test]
+    async fn quit_stops_consuming_input() {
+        let mut canvas = TestCanvas::new(40, 10);
+
+        Editor::with_config(&mut canvas, Config::default())
+            .unwrap()
+            .consume(from_keys(":q<Enter>ihello"))
+            .await
+            .unwrap();
+
+        assert!(!canvas.rows().iter().any(|row| row.contains("hello")));
+    }
+
+    #[tokio::test]
+    async fn pasted_text_is_inserted_without_being_mapped() {
+        let mut config = Config::default();
+        config.keymap.insert.insert("jk".into(), "<Esc>".into());
+
+        let mut canvas = TestCanvas::new(40, 10);
+
+        let mut events: Vec<Event> = parse_keys("i").into_iter().map(Event::KeyPressed).collect();
+        events.push(Event::Paste("jk\r\nline two".into()));
+
+        Editor::with_config(&mut canvas, config)
+            .unwrap()
+            .consume(from_events(events))
+            .await
+            .unwrap();
+
+        assert!(canvas.row(0).contains("jk "));
+        assert!(canvas.row(1).contains("line two"));
+    }
+
+    #[tokio::test]
+    async fn the_last_change_is_repeated() {
+        let mut canvas = TestCanvas::new(40, 10);
+
+        Editor::with_config(&mut canvas, Config::default())
+            .unwrap()
+            .consume(from_keys("Aab<Esc>.o<Esc>iabcdefg<Esc>0xl2."))
+            .await
+            .unwrap();
+
+        assert!(canvas.row(0).contains("abab "));
+        assert!(canvas.row(1).contains("befg "));
+    }
+
+    #[tokio::test]
+    async fn a_large_count_repeats_the_last_change_a_limited_number_of_times() {
+        let mut canvas = TestCanvas::new(40, 10);
+
+        Editor::with_config(&mut canvas, Config::default())
+            .unwrap()
+            .consume(from_keys("iabc<Esc>0x99999999999999999999999."))
+            .await
+            .unwrap();
+
+        assert!(!canvas.row(0).contains('c'));
+    }
+
+    #[tokio::test]
+    async fn save_quit_only_quits_once_the_document_is_saved() {
+        let file_name = TempFile::new("save-quit.txt");
+
+        let mut canvas = TestCanvas::new(80, 10);
+
+        Editor::with_config(&mut canvas, Config::default())
+            .unwrap()
+            .consume(from_keys(&format!(
+                "ihi<Esc>:wq<Enter>a!<Esc>:wq {}<Enter>iafter",
+                &*file_name
+            )))
+            .await
+            .unwrap();
+
+        assert_eq!("hi!\n", std::fs::read_to_string(&file_name).unwrap());
+        assert!(!canvas.rows().iter().any(|row| row.contains("after")));
+    }
+
+    #[tokio::test]
+    async fn swap_files_found_on_start_can_be_recovered() {
+        let file_name = TempFile::new("editor-recover.txt");
+        let swap_file_name = std::env::temp_dir()
+            .join(format!(
+                ".velm-{}-editor-recover.txt.swp",
+                std::process::id()
+            ))
+            .to_string_lossy()
+            .into_owned();
+        std::fs::write(&file_name, "saved\n").unwrap();
+        std::fs::write(&swap_file_name, "unsaved\n").unwrap();
+
+        let mut canvas = TestCanvas::new(120, 10);
+        Editor::with_config(&mut canvas, Config::default())
+            .unwrap()
+            .with_file(&file_name)
+            .unwrap()
+            .consume(from_keys(""))
+            .await
+            .unwrap();
+
+        assert!(canvas
+            .row(9)
+            .starts_with("E325: ATTENTION Found a swap file"));
+        assert!(canvas.row(0).contains("saved"));
+
+        Editor::with_config(&mut canvas, Config::default())
+            .unwrap()
+            .with_file(&file_name)
+            .unwrap()
+            .consume(from_keys(":recover<Enter>:w<Enter>"))
+            .await
+            .unwrap();
+
+        assert!(canvas.row(0).contains("unsaved"));
+        assert_eq!("unsaved\n", std::fs::read_to_string(&file_name).unwrap());
+        assert!(!std::path::Path::new(&swap_file_name).exists());
+    }
+
+    #[tokio::test]
+    async fn the_given_file_is_open_on_start() {
+        let file_name = TempFile::new("with-file.txt");
+        std::fs::write(&file_name, "first line\n").unwrap();
+
+        let mut canvas = TestCanvas::new(80, 10);
+
+        Editor::with_config(&mut canvas, Config::default())
+            .unwrap()
+            .with_file(&file_name)
+            .unwrap()
+            .consume(from_keys(""))
+            .await
+            .unwrap();
+
+        assert!(canvas.row(0).contains("first line"));
+    }
+
+    #[tokio::test]
+    async fn the_lines_of_a_large_file_given_on_start_are_counted() {
+        let file_name = TempFile::new("with-large-file.txt");
+        std::fs::write(&file_name, "line\n".repeat(1_000_000)).unwrap();
+
+        let mut canvas = TestCanvas::new(80, 10);
+        let mut editor = Editor::with_config(&mut canvas, Config::default())
+            .unwrap()
+            .with_file(&file_name)
+            .unwrap();
+        editor.consume(from_keys("")).await.unwrap();
+
+        let buffer = editor.root_component.active_buffer().unwrap();
+        assert!(!buffer.document().is_loaded());
+        assert_eq!(1_000_000, buffer.lines_in_document());
+    }
+
+    #[tokio::test]
+    async fn input_is_handled_while_the_lines_of_an_opened_file_are_counted() {
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        /// Counts the lines of a file once it has been sent a character, noting what it handles
+        /// in order.
+        struct SlowCount {
+            handled: Vec<Message>,
+            typed_tx: mpsc::Sender<()>,
+            typed_rx: Option<mpsc::Receiver<()>>,
+        }
+
+        impl Component for SlowCount {
+            fn update(&mut self, msg: Message) -> Result<Option<Command>> {
+                let cmd: Option<Command> = match msg {
+                    Message::EndCommandLineInput => Some(Box::new(|| {
+                        Message::ParseCommandLineInput("e large.txt".into())
+                    })),
+                    Message::OpenFile(ref file_name) => {
+                        let file_name = file_name.clone();
+                        let typed_rx = self.typed_rx.take().unwrap();
+                        Some(Box::new(move || {
+                            let _ = typed_rx.recv_timeout(Duration::from_secs(5));
+                            Message::RowsCounted { file_name, rows: 1 }
+                        }))
+                    }
+                    Message::InsertChar(_) => {
+                        self.typed_tx.send(()).unwrap();
+                        None
                     }
+                    _ => None,
+                };
+
+                if let Message::InsertChar(_) | Message::RowsCounted { .. } = msg {
+                    self.handled.push(msg);
                 }
-                else => break,
+
+                Ok(cmd)
             }
         }
 
-        Ok(())
+        impl View for SlowCount {
+            fn render_to(&self, _: &mut Frame, _: &Theme) {}
+        }
+
+        let (typed_tx, typed_rx) = mpsc::channel();
+        let mut canvas = TestCanvas::new(60, 10);
+        let mut editor = with_root_component(
+            &mut canvas,
+            SlowCount {
+                handled: Vec::new(),
+                typed_tx,
+                typed_rx: Some(typed_rx),
+            },
+        );
+
+        editor.consume(from_keys(":<Enter>ix")).await.unwrap();
+
+        assert_eq!(
+            vec![
+                Message::InsertChar('x'),
+                Message::RowsCounted {
+                    file_name: "large.txt".into(),
+                    rows: 1
+                }
+            ],
+            editor.root_component.handled
+        );
     }
 }
@@ -0,0 +1,296 @@
+use std::iter::{FromIterator, Rev};
+use std::ops::{Bound, Index, IndexMut, RangeBounds};
+use std::slice;
+
+/// A sequence that keeps a gap at the position of the last insert or removal, so that further
+/// inserts and removals nearby only move the items between the old and new positions rather than
+/// every item after them. Items before the gap are held in `front` and items after it are held in
+/// `back` in reverse order, so both ends of the gap can grow and shrink from the end of a `Vec`.
+///
+/// The rows of a `Document` are held in one with the `gap-buffer` feature. A rope, such as
+/// `ropey`, stores text rather than rows, so each `Row` and its cached grapheme count would have
+/// to be rebuilt whenever it is read, while edits are made around the cursor where the gap is.
+#[derive(Debug, Clone, Default)]
+pub struct GapBuffer<T> {
+    front: Vec<T>,
+    back: Vec<T>,
+}
+
+impl<T> GapBuffer<T> {
+    pub fn len(&self) -> usize {
+        self.front.len() + self.back.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.front.is_empty() && self.back.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        match index.checked_sub(self.front.len()) {
+            None => self.front.get(index),
+            Some(offset) => self
+                .back
+                .len()
+                .checked_sub(offset + 1)
+                .and_then(|idx| self.back.get(idx)),
+        }
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        match index.checked_sub(self.front.len()) {
+            None => self.front.get_mut(index),
+            Some(offset) => match self.back.len().checked_sub(offset + 1) {
+                Some(idx) => self.back.get_mut(idx),
+                None => None,
+            },
+        }
+    }
+
+    /// Insert the item at the given index, shifting the items after it along by one.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the index is greater than the length, as `Vec::insert` does.
+    pub fn insert(&mut self, index: usize, item: T) {
+        assert!(
+            index <= self.len(),
+            "insertion index (is {}) should be <= len (is {})",
+            index,
+            self.len()
+        );
+
+        self.move_gap(index);
+        self.front.push(item);
+    }
+
+    /// Remove and return the item at the given index, shifting the items after it back by one.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the index is out of bounds, as `Vec::remove` does.
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(
+            index < self.len(),
+            "removal index (is {}) should be < len (is {})",
+            index,
+            self.len()
+        );
+
+        self.move_gap(index);
+        self.back.pop().expect("the removal index is within bounds")
+    }
+
+    pub fn push(&mut self, item: T) {
+        let len = self.len();
+        self.insert(len, item);
+    }
+
+    /// Remove the items in the range, returning them in order.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the range is out of bounds or its start is after its end.
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> std::vec::IntoIter<T> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len(),
+        };
+
+        assert!(start <= end, "drain range starts after it ends");
+        assert!(end <= self.len(), "drain range is out of bounds");
+
+        self.move_gap(start);
+        let at = self.back.len() - (end - start);
+        let mut drained = self.back.split_off(at);
+        drained.reverse();
+        drained.into_iter()
+    }
+
+    /// Replace the items in the range with the given items, returning the removed items in order.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the range is out of bounds or its start is after its end.
+    pub fn splice<R, I>(&mut self, range: R, replace_with: I) -> std::vec::IntoIter<T>
+    where
+        R: RangeBounds<usize>,
+        I: IntoIterator<Item = T>,
+    {
+        let removed = self.drain(range);
+        self.front.extend(replace_with);
+        removed
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            front: self.front.iter(),
+            back: self.back.iter().rev(),
+        }
+    }
+
+    /// Move the gap so that it starts at the given index, which must not be past the end.
+    fn move_gap(&mut self, index: usize) {
+        if index < self.front.len() {
+            let moved = self.front.drain(index..).rev();
+            self.back.extend(moved);
+        } else {
+            let at = self.back.len() - (index - self.front.len());
+            let moved = self.back.drain(at..).rev();
+            self.front.extend(moved);
+        }
+    }
+}
+
+impl<T> Index<usize> for GapBuffer<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        let len = self.len();
+        self.get(index).unwrap_or_else(|| {
+            panic!(
+                "index out of bounds: the len is {} but the index is {}",
+                len, index
+            )
+        })
+    }
+}
+
+impl<T> IndexMut<usize> for GapBuffer<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        let len = self.len();
+        self.get_mut(index).unwrap_or_else(|| {
+            panic!(
+                "index out of bounds: the len is {} but the index is {}",
+                len, index
+            )
+        })
+    }
+}
+
+impl<T> FromIterator<T> for GapBuffer<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self {
+            front: iter.into_iter().collect(),
+            back: Vec::new(),
+        }
+    }
+}
+
+/// An iterator over the items of a `GapBuffer` in order, from either end.
+pub struct Iter<'a, T> {
+    front: slice::Iter<'a, T>,
+    back: Rev<slice::Iter<'a, T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.front.next().or_else(|| self.back.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.front.len() + self.back.len();
+        (len, Some(len))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<&'a T> {
+        match n.checked_sub(self.front.len()) {
+            None => self.front.nth(n),
+            Some(n) => {
+                self.front = [].iter();
+                self.back.nth(n)
+            }
+        }
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.back.next_back().or_else(|| self.front.next_back())
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::GapBuffer;
+
+    fn items(buffer: &GapBuffer<usize>) -> Vec<usize> {
+        buffer.iter().copied().collect()
+    }
+
+    #[test]
+    fn items_are_inserted_and_removed_around_the_gap() {
+        let mut buffer: GapBuffer<usize> = (0..5).collect();
+
+        buffer.insert(2, 10);
+        buffer.insert(5, 11);
+        assert_eq!(vec![0, 1, 10, 2, 3, 11, 4], items(&buffer));
+
+        assert_eq!(10, buffer.remove(2));
+        assert_eq!(0, buffer.remove(0));
+        buffer.push(12);
+        assert_eq!(vec![1, 2, 3, 11, 4, 12], items(&buffer));
+        assert_eq!(6, buffer.len());
+    }
+
+    #[test]
+    fn items_can_be_indexed_on_either_side_of_the_gap() {
+        let mut buffer: GapBuffer<usize> = (0..5).collect();
+        buffer.insert(2, 10);
+
+        assert_eq!(Some(&1), buffer.get(1));
+        assert_eq!(Some(&4), buffer.get(5));
+        assert_eq!(None, buffer.get(6));
+        assert_eq!(Some(&3), buffer.iter().nth(4));
+        assert_eq!(None, buffer.iter().nth(6));
+
+        buffer[4] = 20;
+        assert_eq!(20, buffer[4]);
+        assert_eq!(
+            vec![4, 20, 2, 10, 1, 0],
+            buffer.iter().rev().copied().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn drained_items_are_returned_in_order() {
+        let mut buffer: GapBuffer<usize> = (0..6).collect();
+        buffer.insert(5, 10);
+
+        assert_eq!(vec![2, 3, 4], buffer.drain(2..=4).collect::<Vec<_>>());
+        assert_eq!(vec![0, 1, 10, 5], items(&buffer));
+
+        assert_eq!(vec![0, 1, 10, 5], buffer.drain(..).collect::<Vec<_>>());
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn spliced_items_replace_the_range() {
+        let mut buffer: GapBuffer<usize> = (0..6).collect();
+        buffer.insert(1, 10);
+
+        assert_eq!(
+            vec![2, 3],
+            buffer.splice(3..5, vec![20]).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![0, 10, 1, 20, 4, 5], items(&buffer));
+
+        assert!(buffer.splice(6..6, vec![30, 31]).next().is_none());
+        assert_eq!(vec![0, 10, 1, 20, 4, 5, 30, 31], items(&buffer));
+    }
+
+    #[test]
+    #[should_panic(expected = "insertion index (is 1) should be <= len (is 0)")]
+    fn inserting_past_the_end_panics() {
+        GapBuffer::<usize>::default().insert(1, 0);
+    }
+}
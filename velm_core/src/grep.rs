@@ -0,0 +1,215 @@
+use crate::component::QuickfixEntry;
+use crate::ui::Position;
+use anyhow::Result;
+use regex::Regex;
+use std::fmt;
+use std::mem;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The number of leading bytes inspected when checking whether a file is binary.
+const BINARY_CHECK_LEN: usize = 8000;
+
+/// Returns `true` if the contents look like a binary file. Much like git, we treat anything with a
+/// NUL byte near the start of the file as binary.
+pub fn is_binary(contents: &[u8]) -> bool {
+    contents.iter().take(BINARY_CHECK_LEN).any(|b| *b == 0)
+}
+
+/// The most matches that are held before being sent back as a batch.
+const BATCH_LEN: usize = 100;
+
+/// How long matches are held before being sent back in a partial batch, so that the first
+/// results are seen quickly when matches are few and far between.
+const BATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A search of the files in the current directory, started with `Search::start`. The directory
+/// is walked and each file searched on a single thread, with the matches sent back in batches as
+/// they are found. The search stops early once every handle to it has been dropped.
+#[derive(Clone)]
+pub struct Search {
+    batches: Arc<Mutex<Receiver<Vec<QuickfixEntry>>>>,
+}
+
+impl Search {
+    /// Start searching the files matching the optional glob for the regex. Hidden files and
+    /// anything ignored by `.gitignore` are skipped, as are binary files and any that can't be
+    /// read.
+    pub fn start(regex: Regex, glob: Option<&str>) -> Result<Self> {
+        Self::start_in(".", regex, glob)
+    }
+
+    /// Start searching the given file, or the files under the given directory, rather than the
+    /// current directory.
+    pub fn start_in(path: &str, regex: Regex, glob: Option<&str>) -> Result<Self> {
+        use anyhow::Context;
+        use ignore::{overrides::OverrideBuilder, WalkBuilder};
+
+        let mut overrides = OverrideBuilder::new(path);
+        if let Some(glob) = glob {
+            overrides
+                .add(glob)
+                .with_context(|| format!("invalid glob {glob}"))?;
+        }
+
+        let walk = WalkBuilder::new(path)
+            .overrides(
+                overrides
+                    .build()
+                    .context("unable to build glob overrides")?,
+            )
+            .build();
+
+        // A single batch is buffered so that the walk keeps pace with the batches being taken.
+        let (batch_tx, batch_rx) = mpsc::sync_channel(1);
+
+        thread::spawn(move || {
+            let mut batch = Vec::new();
+            let mut sent_at = Instant::now();
+
+            for entry in walk.flatten() {
+                if !entry.file_type().is_some_and(|ft| ft.is_file()) {
+                    continue;
+                }
+
+                let path = entry
+                    .path()
+                    .strip_prefix(".")
+                    .unwrap_or_else(|_| entry.path());
+                batch.extend(search_file(&regex, &path.to_string_lossy()));
+
+                if batch.len() >= BATCH_LEN
+                    || (!batch.is_empty() && sent_at.elapsed() >= BATCH_INTERVAL)
+                {
+                    // Every handle has been dropped, so nobody is waiting for the results.
+                    if batch_tx.send(mem::take(&mut batch)).is_err() {
+                        return;
+                    }
+
+                    sent_at = Instant::now();
+                }
+            }
+
+            if !batch.is_empty() {
+                let _ = batch_tx.send(batch);
+            }
+        });
+
+        Ok(Self {
+            batches: Arc::new(Mutex::new(batch_rx)),
+        })
+    }
+
+    /// Wait for the next batch of matches. An empty batch is returned once the search is done.
+    pub fn next_batch(&self) -> Vec<QuickfixEntry> {
+        self.batches
+            .lock()
+            .ok()
+            .and_then(|batches| batches.recv().ok())
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Debug for Search {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Search")
+    }
+}
+
+/// Handles to a `Search` are equal when they are for the same search.
+impl PartialEq for Search {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.batches, &other.batches)
+    }
+}
+
+impl Eq for Search {}
+
+/// Search the file at the given path, skipping it if it is binary or can't be read.
+pub fn search_file(regex: &Regex, file_name: &str) -> Vec<QuickfixEntry> {
+    match std::fs::read(file_name) {
+        Ok(contents) if !is_binary(&contents) => {
+            search_contents(regex, file_name, &String::from_utf8_lossy(&contents))
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Search each line of the contents, returning an entry for the first match on each line.
+pub fn search_contents(regex: &Regex, file_name: &str, contents: &str) -> Vec<QuickfixEntry> {
+    contents
+        .lines()
+        .enumerate()
+        .filter_map(|(row, line)| {
+            find(regex, line).map(|col| {
+                QuickfixEntry::new(Some(file_name.into()), Position::new(col, row), line)
+            })
+        })
+        .collect()
+}
+
+/// The grapheme column of the first match of the regex in the line.
+pub fn find(regex: &Regex, line: &str) -> Option<usize> {
+    regex
+        .find(line)
+        .map(|m| line[..m.start()].graphemes(true).count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_binary, search_contents, Search};
+    use crate::ui::Position;
+    use regex::Regex;
+
+    #[test]
+    fn is_binary_detects_nul_bytes() {
+        assert!(is_binary(b"ELF\0\x01\x02"));
+        assert!(!is_binary(b"fn main() {}\n"));
+    }
+
+    #[test]
+    fn search_contents_returns_an_entry_per_matching_line() {
+        let regex = Regex::new("fo+").unwrap();
+        let entries = search_contents(&regex, "test.txt", "foo\nbar\n\u{1f980} fooo foo");
+
+        assert_eq!(2, entries.len());
+        assert_eq!(Position::new(0, 0), entries[0].position);
+        assert_eq!(Position::new(2, 2), entries[1].position);
+        assert_eq!("\u{1f980} fooo foo", entries[1].text);
+        assert_eq!(Some("test.txt".to_string()), entries[1].file_name);
+    }
+
+    #[test]
+    fn search_sends_the_matches_in_batches_until_it_is_done() {
+        // Tests are run from the crate directory, so this file is found by the glob.
+        let regex = Regex::new("^const BATCH_(LEN|INTERVAL)").unwrap();
+        let search = Search::start(regex, Some("grep.rs")).unwrap();
+
+        let mut entries = Vec::new();
+        loop {
+            let batch = search.next_batch();
+            if batch.is_empty() {
+                break;
+            }
+            entries.extend(batch);
+        }
+
+        assert_eq!(2, entries.len());
+        assert!(entries
+            .iter()
+            .all(|entry| entry.file_name == Some("src/grep.rs".into())));
+        assert_eq!(search, search.clone());
+        assert_ne!(
+            search,
+            Search::start(Regex::new("a").unwrap(), None).unwrap()
+        );
+    }
+
+    #[test]
+    fn search_reports_invalid_globs() {
+        assert!(Search::start(Regex::new("a").unwrap(), Some("{")).is_err());
+    }
+}
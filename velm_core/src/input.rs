@@ -6,6 +6,7 @@ use std::pin::Pin;
 pub enum Key {
     Enter,
     Tab,
+    BackTab,
     Backspace,
     Esc,
     Left,
@@ -18,8 +19,24 @@ pub enum Key {
     End,
     PageUp,
     PageDown,
+    CtrlLeft,
+    CtrlRight,
+    CtrlHome,
+    CtrlEnd,
+    /// A function key, such as `F1`, identified by its number.
+    Function(u8),
     Char(char),
     Ctrl(char),
+    Alt(char),
+    Unknown,
+}
+
+/// The kind of mouse input received by the editor.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MouseKind {
+    LeftClick,
+    ScrollUp,
+    ScrollDown,
     Unknown,
 }
 
@@ -27,10 +44,147 @@ pub enum Key {
 #[derive(Debug)]
 pub enum Event {
     KeyPressed(Key),
-    MouseInputReceived,
+    MouseInput {
+        col: usize,
+        row: usize,
+        kind: MouseKind,
+    },
     WindowResized(u16, u16),
+    /// Text that was pasted into the terminal, delivered in one piece rather than as key presses.
+    Paste(String),
     ReadFailed(IoError),
 }
 
 /// `EventStream` is a an asynchronous tokio stream of input Events.
 pub type EventStream = Pin<Box<dyn tokio_stream::Stream<Item = Event> + Send>>;
+
+/// Build an `EventStream` that yields the given events in order and then ends. This is useful for
+/// driving the `Editor` from a script, such as in tests.
+#[must_use]
+pub fn from_events(events: Vec<Event>) -> EventStream {
+    Box::pin(tokio_stream::iter(events))
+}
+
+/// Build an `EventStream` that presses each key described by the input in order and then ends.
+/// Characters are pressed as they are, while special keys are written in angle brackets, e.g.
+/// `ihello<Esc>:w<Enter>`. See `parse_keys` for the supported key names.
+#[must_use]
+pub fn from_keys(input: &str) -> EventStream {
+    from_events(
+        parse_keys(input)
+            .into_iter()
+            .map(Event::KeyPressed)
+            .collect(),
+    )
+}
+
+/// Parse a description of key presses using vim style key notation. Special keys are written in
+/// angle brackets (`<Esc>`, `<Enter>` or `<CR>`, `<BS>`, `<Tab>`, `<Del>`, `<Up>`, `<Down>`,
+/// `<Left>`, `<Right>`, `<Home>`, `<End>`, `<F1>` to `<F12>`), Ctrl and Alt chords are written as
+/// `<C-x>` and `<A-x>`, including `<C-Left>`, `<C-Right>`, `<C-Home>` and `<C-End>`, and `<lt>` is
+/// a literal `<`. Anything else is pressed as typed.
+#[must_use]
+pub fn parse_keys(input: &str) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut rest = input;
+
+    while let Some(ch) = rest.chars().next() {
+        let special = if ch == '<' {
+            rest.find('>')
+                .and_then(|end| key_for_name(&rest[1..end]).map(|key| (key, end + 1)))
+        } else {
+            None
+        };
+
+        if let Some((key, len)) = special {
+            keys.push(key);
+            rest = &rest[len..];
+        } else {
+            keys.push(Key::Char(ch));
+            rest = &rest[ch.len_utf8()..];
+        }
+    }
+
+    keys
+}
+
+fn key_for_name(name: &str) -> Option<Key> {
+    let single_char = |s: &str| {
+        let mut chars = s.chars();
+        chars.next().filter(|_| chars.next().is_none())
+    };
+
+    Some(match name {
+        "Esc" => Key::Esc,
+        "Enter" | "CR" => Key::Enter,
+        "BS" => Key::Backspace,
+        "Tab" => Key::Tab,
+        "S-Tab" => Key::BackTab,
+        "Del" => Key::Delete,
+        "Up" => Key::Up,
+        "Down" => Key::Down,
+        "Left" => Key::Left,
+        "Right" => Key::Right,
+        "Home" => Key::Home,
+        "End" => Key::End,
+        "PageUp" => Key::PageUp,
+        "PageDown" => Key::PageDown,
+        "Insert" => Key::Insert,
+        "C-Left" => Key::CtrlLeft,
+        "C-Right" => Key::CtrlRight,
+        "C-Home" => Key::CtrlHome,
+        "C-End" => Key::CtrlEnd,
+        "lt" => Key::Char('<'),
+        _ => {
+            if let Some(chord) = name.strip_prefix("C-") {
+                Key::Ctrl(single_char(chord)?)
+            } else if let Some(chord) = name.strip_prefix("A-") {
+                Key::Alt(single_char(chord)?)
+            } else {
+                let n = name.strip_prefix('F')?.parse::<u8>().ok()?;
+                if !(1..=12).contains(&n) {
+                    return None;
+                }
+
+                Key::Function(n)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_keys, Key};
+
+    #[test]
+    fn parse_keys_handles_characters_and_special_keys() {
+        assert_eq!(
+            vec![
+                Key::Char('i'),
+                Key::Char('<'),
+                Key::Esc,
+                Key::Ctrl('w'),
+                Key::Alt('x'),
+                Key::Function(5),
+                Key::Enter,
+                Key::CtrlLeft,
+                Key::CtrlEnd,
+                Key::Char('\u{1f980}'),
+            ],
+            parse_keys("i<lt><Esc><C-w><A-x><F5><CR><C-Left><C-End>\u{1f980}")
+        );
+    }
+
+    #[test]
+    fn parse_keys_presses_unknown_names_as_typed() {
+        assert_eq!(
+            vec![
+                Key::Char('<'),
+                Key::Char('a'),
+                Key::Char('>'),
+                Key::Char('<'),
+            ],
+            parse_keys("<a><")
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use crate::config::Mappings;
+use crate::input::parse_keys;
+use crate::{Key, Mode};
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// How long to wait for the rest of a mapping before the keys pressed so far are handled as they
+/// are, e.g. a lone `j` when `jk` is mapped.
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A mapping from the keys that are pressed to the keys that are handled in their place.
+type Mapping = (Vec<Key>, Vec<Key>);
+
+/// `Keymap` translates key presses using the user's mappings before they are handled by the
+/// current `Mode`. Keys that may be the start of a mapping are held until the mapping is complete,
+/// they can no longer match or the timeout has passed. The keys that a mapping produces are not
+/// mapped again.
+#[derive(Debug, Default)]
+pub struct Keymap {
+    command: Vec<Mapping>,
+    insert: Vec<Mapping>,
+    normal: Vec<Mapping>,
+    visual: Vec<Mapping>,
+    pending: Vec<Key>,
+    pending_since: Option<Instant>,
+    ready: VecDeque<Key>,
+}
+
+impl Keymap {
+    pub fn new(mappings: &Mappings) -> Self {
+        let parse = |mappings: &BTreeMap<String, String>| {
+            mappings
+                .iter()
+                .map(|(from, to)| (parse_keys(from), parse_keys(to)))
+                .filter(|(from, _)| !from.is_empty())
+                .collect()
+        };
+
+        Self {
+            command: parse(&mappings.command),
+            insert: parse(&mappings.insert),
+            normal: parse(&mappings.normal),
+            visual: parse(&mappings.visual),
+            ..Self::default()
+        }
+    }
+
+    /// Press the key in the given mode. Any keys that are ready to be handled are then available
+    /// from `next_key`.
+    pub fn press(&mut self, mode: &Mode, key: Key) {
+        self.pending.push(key);
+        self.pending_since = Some(Instant::now());
+        self.resolve(mode, true);
+    }
+
+    /// Handle any held keys if the timeout has passed since the last key was pressed.
+    pub fn expire(&mut self, mode: &Mode) {
+        if self
+            .pending_since
+            .is_some_and(|since| since.elapsed() >= TIMEOUT)
+        {
+            self.flush(mode);
+        }
+    }
+
+    /// Stop waiting for the rest of a mapping and handle any held keys now.
+    pub fn flush(&mut self, mode: &Mode) {
+        self.resolve(mode, false);
+    }
+
+    /// Returns `true` if there are keys waiting to be handled by the current `Mode`.
+    pub fn has_ready_keys(&self) -> bool {
+        !self.ready.is_empty()
+    }
+
+    /// The next key to be handled by the current `Mode`.
+    pub fn next_key(&mut self) -> Option<Key> {
+        self.ready.pop_front()
+    }
+
+    fn resolve(&mut self, mode: &Mode, wait: bool) {
+        let mappings = match mode {
+            Mode::Execute(_) | Mode::Search(_) => &self.command,
+            Mode::Insert(_) | Mode::Replace(_) => &self.insert,
+            Mode::Normal(_) => &self.normal,
+            Mode::Visual(_) => &self.visual,
+        };
+
+        while !self.pending.is_empty() {
+            let pending = &self.pending;
+
+            if wait
+                && mappings
+                    .iter()
+                    .any(|(from, _)| from.len() > pending.len() && from.starts_with(pending))
+            {
+                return;
+            }
+
+            if let Some((_, to)) = mappings.iter().find(|(from, _)| from == pending) {
+                self.ready.extend(to);
+                self.pending.clear();
+                break;
+            }
+
+            // Nothing starts with the held keys so the first is handled as it is and the rest
+            // may still start a mapping of their own.
+            self.ready.push_back(self.pending.remove(0));
+        }
+
+        self.pending_since = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Keymap;
+    use crate::config::Mappings;
+    use crate::mode::{Insert, Mode, Normal};
+    use crate::Key;
+
+    fn keymap() -> Keymap {
+        let mut mappings = Mappings::default();
+        mappings.insert.insert("jk".into(), "<Esc>".into());
+        mappings.normal.insert("<C-s>".into(), ":w<CR>".into());
+
+        Keymap::new(&mappings)
+    }
+
+    fn ready(keymap: &mut Keymap) -> Vec<Key> {
+        std::iter::from_fn(|| keymap.next_key()).collect()
+    }
+
+    #[test]
+    fn mapped_keys_are_replaced() {
+        let mut keymap = keymap();
+        let insert = Mode::Insert(Insert);
+
+        keymap.press(&insert, Key::Char('j'));
+        assert!(ready(&mut keymap).is_empty());
+
+        keymap.press(&insert, Key::Char('k'));
+        assert_eq!(vec![Key::Esc], ready(&mut keymap));
+
+        let normal = Mode::Normal(Normal::default());
+        keymap.press(&normal, Key::Ctrl('s'));
+        assert_eq!(
+            vec![Key::Char(':'), Key::Char('w'), Key::Enter],
+            ready(&mut keymap)
+        );
+    }
+
+    #[test]
+    fn held_keys_are_released_when_the_mapping_can_not_match() {
+        let mut keymap = keymap();
+        let insert = Mode::Insert(Insert);
+
+        keymap.press(&insert, Key::Char('j'));
+        keymap.press(&insert, Key::Char('j'));
+        assert_eq!(vec![Key::Char('j')], ready(&mut keymap));
+
+        keymap.press(&insert, Key::Char('a'));
+        assert_eq!(vec![Key::Char('j'), Key::Char('a')], ready(&mut keymap));
+    }
+
+    #[test]
+    fn mappings_only_apply_to_their_mode() {
+        let mut keymap = keymap();
+        let normal = Mode::Normal(Normal::default());
+
+        keymap.press(&normal, Key::Char('j'));
+        keymap.press(&normal, Key::Char('k'));
+        assert_eq!(vec![Key::Char('j'), Key::Char('k')], ready(&mut keymap));
+    }
+
+    #[test]
+    fn flush_releases_held_keys() {
+        let mut keymap = keymap();
+        let insert = Mode::Insert(Insert);
+
+        keymap.press(&insert, Key::Char('j'));
+        keymap.expire(&insert);
+        assert!(ready(&mut keymap).is_empty());
+
+        keymap.flush(&insert);
+        assert_eq!(vec![Key::Char('j')], ready(&mut keymap));
+    }
+}
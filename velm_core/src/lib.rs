@@ -2,18 +2,32 @@
 
 mod communication;
 mod component;
+mod config;
 mod document;
 mod editor;
+#[cfg(feature = "gap-buffer")]
+mod gap_buffer;
+mod grep;
 mod input;
+mod keymap;
 mod mode;
 mod render;
 mod row;
+#[cfg(any(test, feature = "test-util"))]
+mod testing;
 
 pub mod ui;
 
+pub use config::{Config, Mappings};
 pub use editor::Editor;
-pub use input::{Event, EventStream, Key};
-pub use render::{Canvas, Cell};
+pub use input::{from_events, from_keys, parse_keys, Event, EventStream, Key, MouseKind};
+pub use render::{Canvas, Cell, CursorShape};
+#[cfg(any(test, feature = "test-util"))]
+pub use testing::TestCanvas;
+
+// Exposed so that the row storage can be benchmarked without going through the `Editor`.
+#[cfg(feature = "test-util")]
+pub use document::Document;
 
 use mode::Mode;
 use row::Row;
@@ -1,4 +1,5 @@
 use crate::communication::Message;
+use crate::render::CursorShape;
 use crate::ui::Position;
 use crate::{Key, Row};
 use std::fmt::{Display, Formatter, Result as FmtResult};
@@ -8,6 +9,9 @@ pub enum Mode {
     Execute(Execute),
     Insert(Insert),
     Normal(Normal),
+    Replace(Replace),
+    Search(Search),
+    Visual(Visual),
 }
 
 impl Default for Mode {
@@ -16,12 +20,28 @@ impl Default for Mode {
     }
 }
 
+impl Mode {
+    /// The shape of the cursor while in this mode. Like vim, the cursor is a bar while text is
+    /// being typed, an underline while text is being replaced and a block otherwise.
+    #[must_use]
+    pub fn cursor_shape(&self) -> CursorShape {
+        match self {
+            Self::Execute(_) | Self::Insert(_) | Self::Search(_) => CursorShape::Bar,
+            Self::Replace(_) => CursorShape::Underline,
+            Self::Normal(_) | Self::Visual(_) => CursorShape::Block,
+        }
+    }
+}
+
 impl Display for Mode {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         match self {
             Self::Execute(_) => write!(f, "COMMAND"),
             Self::Insert(_) => write!(f, "INSERT"),
             Self::Normal(_) => write!(f, "NORMAL"),
+            Self::Replace(_) => write!(f, "REPLACE"),
+            Self::Search(_) => write!(f, "SEARCH"),
+            Self::Visual(_) => write!(f, "VISUAL"),
         }
     }
 }
@@ -34,18 +54,7 @@ pub struct Execute {
 
 impl Execute {
     pub fn handle(&self, key: Key) -> Option<Message> {
-        match key {
-            Key::Enter => Some(Message::EndCommandLineInput),
-            Key::Char(ch) => Some(Message::InsertChar(ch)),
-            Key::Left => Some(Message::MoveCursorLeft(1)),
-            Key::Right => Some(Message::MoveCursorRight(1)),
-            Key::Backspace => Some(Message::DeleteCharBackward),
-            Key::Delete => Some(Message::DeleteCharForward),
-            Key::Home => Some(Message::MoveCursorLineStart),
-            Key::End => Some(Message::MoveCursorLineEnd),
-            Key::Esc => Some(Message::AbortCommandLineInput),
-            _ => None,
-        }
+        command_line_message_for_key(key)
     }
 
     pub fn parse(&self, command_string: &str) -> Option<Message> {
@@ -53,14 +62,53 @@ impl Execute {
     }
 }
 
+/// Key handling shared by the modes that take their input from the command line.
+fn command_line_message_for_key(key: Key) -> Option<Message> {
+    match key {
+        Key::Enter => Some(Message::EndCommandLineInput),
+        Key::Char(ch) => Some(Message::InsertChar(ch)),
+        Key::Left => Some(Message::MoveCursorLeft(1)),
+        Key::Right => Some(Message::MoveCursorRight(1)),
+        Key::Up => Some(Message::HistoryPrevious),
+        Key::Down => Some(Message::HistoryNext),
+        Key::Backspace => Some(Message::DeleteCharBackward),
+        Key::Delete => Some(Message::DeleteCharForward),
+        Key::Home => Some(Message::MoveCursorLineStart),
+        Key::End => Some(Message::MoveCursorLineEnd),
+        Key::Esc => Some(Message::AbortCommandLineInput),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct Search;
+
+impl Search {
+    #[must_use]
+    pub fn handle(key: Key) -> Option<Message> {
+        command_line_message_for_key(key)
+    }
+
+    /// An empty query repeats the last search, much like vim.
+    #[must_use]
+    pub fn parse(query: &str) -> Message {
+        if query.is_empty() {
+            Message::SearchNext
+        } else {
+            Message::Search(query.into())
+        }
+    }
+}
+
 mod execute {
     use crate::communication::Message;
     use nom::{
         branch::alt,
-        character::complete::{anychar, char},
-        combinator::{all_consuming, map, value},
+        bytes::complete::{is_not, tag},
+        character::complete::{anychar, char, digit1},
+        combinator::{all_consuming, map, opt, rest, value},
         multi::many1,
-        sequence::separated_pair,
+        sequence::{delimited, pair, preceded, separated_pair, terminated},
         IResult,
     };
 
@@ -68,6 +116,10 @@ mod execute {
         value(Message::Quit, all_consuming(char('q')))(input)
     }
 
+    fn force_quit(input: &str) -> IResult<&str, Message> {
+        value(Message::ForceQuit, all_consuming(tag("q!")))(input)
+    }
+
     fn save(input: &str) -> IResult<&str, Message> {
         value(Message::Save, all_consuming(char('w')))(input)
     }
@@ -79,8 +131,151 @@ mod execute {
         )(input)
     }
 
+    /// `wq` and `x`, optionally followed by a file name to save as. Unlike `wq`, `x` only writes
+    /// the file when there are changes to save.
+    fn save_quit(input: &str) -> IResult<&str, Message> {
+        map(
+            pair(
+                alt((value(false, tag("wq")), value(true, tag("x")))),
+                opt(preceded(char(' '), many1(anychar))),
+            ),
+            |(only_if_modified, name)| Message::SaveQuit {
+                file_name: name.map(|name| name.into_iter().collect()),
+                only_if_modified,
+            },
+        )(input)
+    }
+
+    fn edit(input: &str) -> IResult<&str, Message> {
+        map(
+            separated_pair(char('e'), char(' '), many1(anychar)),
+            |(_, name)| Message::OpenFile(name.into_iter().collect::<String>()),
+        )(input)
+    }
+
+    fn view(input: &str) -> IResult<&str, Message> {
+        map(
+            separated_pair(tag("view"), char(' '), many1(anychar)),
+            |(_, name)| Message::ViewFile(name.into_iter().collect::<String>()),
+        )(input)
+    }
+
+    /// `:noh` and `:nohlsearch` hide the search highlighting.
+    fn no_highlight(input: &str) -> IResult<&str, Message> {
+        value(
+            Message::ClearSearchHighlight,
+            all_consuming(alt((tag("nohlsearch"), tag("noh")))),
+        )(input)
+    }
+
+    fn recover(input: &str) -> IResult<&str, Message> {
+        value(
+            Message::Recover,
+            all_consuming(alt((tag("recover"), tag("rec")))),
+        )(input)
+    }
+
+    fn words(input: &str) -> IResult<&str, Message> {
+        value(Message::WordCount, all_consuming(tag("words")))(input)
+    }
+
+    fn goto_line(input: &str) -> IResult<&str, Message> {
+        map(digit1, |line: &str| {
+            Message::GoToLine(line.parse::<usize>().unwrap_or(usize::MAX))
+        })(input)
+    }
+
+    fn global(input: &str) -> IResult<&str, Message> {
+        map(
+            terminated(preceded(tag("g/"), is_not("/")), opt(char('/'))),
+            |pattern: &str| Message::GlobalSearch(pattern.into()),
+        )(input)
+    }
+
+    /// `:grep pattern [glob]` where a pattern containing spaces can be wrapped in double or single
+    /// quotes.
+    fn grep(input: &str) -> IResult<&str, Message> {
+        let pattern = alt((
+            delimited(char('"'), is_not("\""), char('"')),
+            delimited(char('\''), is_not("'"), char('\'')),
+            is_not(" "),
+        ));
+
+        map(
+            preceded(tag("grep "), pair(pattern, opt(preceded(char(' '), rest)))),
+            |(pattern, glob): (&str, Option<&str>)| {
+                Message::Grep(pattern.into(), glob.map(String::from))
+            },
+        )(input)
+    }
+
+    fn quickfix(input: &str) -> IResult<&str, Message> {
+        alt((
+            value(Message::QuickfixOpen, all_consuming(tag("copen"))),
+            value(Message::QuickfixClose, all_consuming(tag("cclose"))),
+            value(Message::QuickfixNext, all_consuming(tag("cn"))),
+            value(Message::QuickfixPrevious, all_consuming(tag("cp"))),
+        ))(input)
+    }
+
+    /// `:bn` and `:bp` cycle through the open buffers while `:b <n>` switches to the buffer
+    /// numbered `n`, counting from 1.
+    fn buffer(input: &str) -> IResult<&str, Message> {
+        alt((
+            value(Message::NextBuffer, all_consuming(tag("bn"))),
+            value(Message::PrevBuffer, all_consuming(tag("bp"))),
+            map(preceded(tag("b "), digit1), |n: &str| {
+                Message::GoToBuffer(n.parse::<usize>().unwrap_or(usize::MAX))
+            }),
+        ))(input)
+    }
+
+    fn split(input: &str) -> IResult<&str, Message> {
+        map(
+            pair(
+                alt((tag("split"), tag("sp"))),
+                opt(preceded(char(' '), rest)),
+            ),
+            |(_, file_name): (&str, Option<&str>)| Message::Split(file_name.map(String::from)),
+        )(input)
+    }
+
+    /// `:set option` and `:set nooption` turn an option on and off while `:set option=value`
+    /// gives it a value. The options themselves are checked when the message is handled.
+    fn set(input: &str) -> IResult<&str, Message> {
+        map(
+            preceded(
+                tag("set "),
+                pair(is_not("="), opt(preceded(char('='), rest))),
+            ),
+            |(key, value): (&str, Option<&str>)| Message::SetOption {
+                key: key.into(),
+                value: value.map(String::from),
+            },
+        )(input)
+    }
+
     pub fn command_for_input(input: &str) -> Option<Message> {
-        if let Ok((_, command)) = all_consuming(alt((quit, save, save_as)))(input) {
+        if let Ok((_, command)) = all_consuming(alt((
+            quit,
+            force_quit,
+            save,
+            save_as,
+            save_quit,
+            edit,
+            view,
+            recover,
+            words,
+            no_highlight,
+            goto_line,
+            global,
+            grep,
+            quickfix,
+            buffer,
+            split,
+            set,
+        )))(input)
+        {
             return Some(command);
         }
 
@@ -89,15 +284,27 @@ mod execute {
 
     #[cfg(test)]
     mod tests {
-        use super::{command_for_input, quit, save, save_as};
+        use super::{
+            buffer, command_for_input, edit, force_quit, global, goto_line, grep, quickfix, quit,
+            save, save_as, save_quit, set, split, view,
+        };
         use crate::communication::Message;
 
         #[test]
         fn test_command_for_input() {
             let tests = vec![
                 ("q", Message::Quit),
+                ("q!", Message::ForceQuit),
                 ("w", Message::Save),
                 ("w some_file.txt", Message::SaveAs("some_file.txt".into())),
+                ("g/fn main/", Message::GlobalSearch("fn main".into())),
+                ("cn", Message::QuickfixNext),
+                ("b 2", Message::GoToBuffer(2)),
+                ("words", Message::WordCount),
+                ("rec", Message::Recover),
+                ("recover", Message::Recover),
+                ("noh", Message::ClearSearchHighlight),
+                ("nohlsearch", Message::ClearSearchHighlight),
             ];
 
             for (input, command) in tests {
@@ -111,6 +318,12 @@ mod execute {
             assert_eq!(quit("q"), Ok(("", Message::Quit)));
         }
 
+        #[test]
+        fn test_force_quit() {
+            assert!(force_quit("q").is_err());
+            assert_eq!(force_quit("q!"), Ok(("", Message::ForceQuit)));
+        }
+
         #[test]
         fn test_save() {
             assert!(save("q").is_err());
@@ -125,6 +338,147 @@ mod execute {
                 Ok(("", Message::SaveAs("test.txt".into())))
             );
         }
+
+        #[test]
+        fn test_save_quit() {
+            assert!(save_quit("w").is_err());
+            assert_eq!(
+                save_quit("wq"),
+                Ok((
+                    "",
+                    Message::SaveQuit {
+                        file_name: None,
+                        only_if_modified: false
+                    }
+                ))
+            );
+            assert_eq!(
+                save_quit("x"),
+                Ok((
+                    "",
+                    Message::SaveQuit {
+                        file_name: None,
+                        only_if_modified: true
+                    }
+                ))
+            );
+            assert_eq!(
+                save_quit("wq test.txt"),
+                Ok((
+                    "",
+                    Message::SaveQuit {
+                        file_name: Some("test.txt".into()),
+                        only_if_modified: false
+                    }
+                ))
+            );
+        }
+
+        #[test]
+        fn test_edit() {
+            assert!(edit("e").is_err());
+            assert_eq!(
+                edit("e src/main.rs"),
+                Ok(("", Message::OpenFile("src/main.rs".into())))
+            );
+        }
+
+        #[test]
+        fn test_view() {
+            assert!(view("view").is_err());
+            assert_eq!(
+                view("view src/main.rs"),
+                Ok(("", Message::ViewFile("src/main.rs".into())))
+            );
+        }
+
+        #[test]
+        fn test_goto_line() {
+            assert!(goto_line("l").is_err());
+            assert_eq!(goto_line("42"), Ok(("", Message::GoToLine(42))));
+            assert_eq!(goto_line("0"), Ok(("", Message::GoToLine(0))));
+            assert_eq!(
+                goto_line("99999999999999999999999"),
+                Ok(("", Message::GoToLine(usize::MAX)))
+            );
+        }
+
+        #[test]
+        fn test_global() {
+            assert!(global("g//").is_err());
+            assert_eq!(
+                global("g/TODO/"),
+                Ok(("", Message::GlobalSearch("TODO".into())))
+            );
+            assert_eq!(
+                global("g/TODO"),
+                Ok(("", Message::GlobalSearch("TODO".into())))
+            );
+        }
+
+        #[test]
+        fn test_grep() {
+            assert!(grep("grep").is_err());
+            assert_eq!(grep("grep fn"), Ok(("", Message::Grep("fn".into(), None))));
+            assert_eq!(
+                grep("grep fn\\s+main *.rs"),
+                Ok(("", Message::Grep("fn\\s+main".into(), Some("*.rs".into()))))
+            );
+            assert_eq!(
+                grep("grep \"fn main\" *.rs"),
+                Ok(("", Message::Grep("fn main".into(), Some("*.rs".into()))))
+            );
+            assert_eq!(
+                grep("grep 'a \"b\"'"),
+                Ok(("", Message::Grep("a \"b\"".into(), None)))
+            );
+        }
+
+        #[test]
+        fn test_quickfix() {
+            assert!(quickfix("c").is_err());
+            assert_eq!(quickfix("copen"), Ok(("", Message::QuickfixOpen)));
+            assert_eq!(quickfix("cclose"), Ok(("", Message::QuickfixClose)));
+            assert_eq!(quickfix("cn"), Ok(("", Message::QuickfixNext)));
+            assert_eq!(quickfix("cp"), Ok(("", Message::QuickfixPrevious)));
+        }
+
+        #[test]
+        fn test_buffer() {
+            assert!(buffer("b").is_err());
+            assert!(buffer("b x").is_err());
+            assert_eq!(buffer("bn"), Ok(("", Message::NextBuffer)));
+            assert_eq!(buffer("bp"), Ok(("", Message::PrevBuffer)));
+            assert_eq!(buffer("b 3"), Ok(("", Message::GoToBuffer(3))));
+        }
+
+        #[test]
+        fn test_set() {
+            let set_option = |key: &str, value: Option<&str>| Message::SetOption {
+                key: key.into(),
+                value: value.map(String::from),
+            };
+
+            assert!(set("set").is_err());
+            assert!(set("set =4").is_err());
+            assert_eq!(set("set wrap"), Ok(("", set_option("wrap", None))));
+            assert_eq!(set("set nowrap"), Ok(("", set_option("nowrap", None))));
+            assert_eq!(
+                set("set tabstop=4"),
+                Ok(("", set_option("tabstop", Some("4"))))
+            );
+        }
+
+        #[test]
+        fn test_split() {
+            assert!(split("s").is_err());
+            assert_eq!(split("split"), Ok(("", Message::Split(None))));
+            assert_eq!(split("sp"), Ok(("", Message::Split(None))));
+            assert_eq!(
+                split("sp src/lib.rs"),
+                Ok(("", Message::Split(Some("src/lib.rs".into()))))
+            );
+        }
     }
 }
 
@@ -138,13 +492,18 @@ impl Insert {
             Key::Down => Some(Message::MoveCursorDown(1)),
             Key::Left => Some(Message::MoveCursorLeft(1)),
             Key::Right => Some(Message::MoveCursorRight(1)),
-            Key::Home => Some(Message::MoveCursorLineStart),
+            Key::Home => Some(Message::MoveCursorHome),
             Key::End => Some(Message::MoveCursorLineEnd),
             Key::PageUp => Some(Message::MoveCursorPageUp),
             Key::PageDown => Some(Message::MoveCursorPageDown),
+            Key::CtrlLeft => Some(Message::MoveCursorWordBackward(1)),
+            Key::CtrlRight => Some(Message::MoveCursorWordForward(1)),
+            Key::CtrlHome => Some(Message::MoveCursorDocumentStart),
+            Key::CtrlEnd => Some(Message::MoveCursorDocumentEnd),
             Key::Delete => Some(Message::DeleteCharForward),
             Key::Backspace => Some(Message::DeleteCharBackward),
             Key::Enter => Some(Message::InsertLineBreak),
+            Key::Tab => Some(Message::InsertTab),
             Key::Char(ch) => Some(Message::InsertChar(ch)),
             Key::Esc => Some(Message::EnterMode(Mode::Normal(Normal::default()))),
             _ => None,
@@ -152,6 +511,30 @@ impl Insert {
     }
 }
 
+/// `Replace` mode overwrites the characters under the cursor as text is typed.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct Replace;
+
+impl Replace {
+    #[must_use]
+    pub fn handle(key: Key) -> Option<Message> {
+        match key {
+            Key::Up => Some(Message::MoveCursorUp(1)),
+            Key::Down => Some(Message::MoveCursorDown(1)),
+            Key::Left => Some(Message::MoveCursorLeft(1)),
+            Key::Right => Some(Message::MoveCursorRight(1)),
+            Key::Home => Some(Message::MoveCursorHome),
+            Key::End => Some(Message::MoveCursorLineEnd),
+            Key::Delete => Some(Message::DeleteCharForward),
+            Key::Backspace => Some(Message::RestoreReplacedChar),
+            Key::Enter => Some(Message::InsertLineBreak),
+            Key::Char(ch) => Some(Message::ReplaceChar(ch)),
+            Key::Esc => Some(Message::EnterMode(Mode::Normal(Normal::default()))),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, Eq, PartialEq)]
 pub struct Normal {
     input_buffer: String,
@@ -159,27 +542,41 @@ pub struct Normal {
 
 impl Normal {
     pub fn handle(&mut self, key: Key) -> Option<Message> {
-        if let Key::Char(ch) = key {
-            self.input_buffer.push(ch);
-        }
-
-        if let Key::Esc = key {
-            self.input_buffer.clear();
+        match key {
+            Key::Char(ch) => self.input_buffer.push(ch),
+            Key::Ctrl('w') => self.input_buffer.push(normal::CTRL_W),
+            Key::Esc => self.input_buffer.clear(),
+            _ => (),
         }
 
         match key {
-            Key::Home => Some(Message::MoveCursorLineStart),
+            Key::Home => Some(Message::MoveCursorHome),
             Key::End => Some(Message::MoveCursorLineEnd),
             Key::PageUp => Some(Message::MoveCursorPageUp),
             Key::PageDown => Some(Message::MoveCursorPageDown),
-            Key::Insert => Some(Message::EnterMode(Mode::Insert(Insert::default()))),
+            Key::CtrlLeft => Some(Message::MoveCursorWordBackward(1)),
+            Key::CtrlRight => Some(Message::MoveCursorWordForward(1)),
+            Key::CtrlHome => Some(Message::MoveCursorDocumentStart),
+            Key::CtrlEnd => Some(Message::MoveCursorDocumentEnd),
+            Key::Insert => Some(Message::EnterMode(Mode::Insert(Insert))),
             Key::Enter => Some(Message::MoveCursorDown(1)),
+            Key::Ctrl('r') => Some(Message::Redo),
+            Key::Ctrl('g') => Some(Message::WordCount),
+            Key::Ctrl('d') => Some(Message::ScrollHalfPageDown),
+            Key::Ctrl('u') => Some(Message::ScrollHalfPageUp),
+            Key::Ctrl('e') => Some(Message::ScrollLineDown),
+            Key::Ctrl('y') => Some(Message::ScrollLineUp),
             _ => None,
         }
         .map_or_else(
             || {
                 let command = normal::command_for_input(&self.input_buffer);
-                self.input_buffer.clear();
+
+                // Keep hold of the input if it may be the start of a longer command such as `3j`.
+                if command.is_some() || !normal::is_pending(&self.input_buffer) {
+                    self.input_buffer.clear();
+                }
+
                 command
             },
             Some,
@@ -187,20 +584,85 @@ impl Normal {
     }
 }
 
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct Visual {
+    input_buffer: String,
+}
+
+impl Visual {
+    pub fn handle(&mut self, key: Key) -> Option<Message> {
+        let normal_mode = Message::EnterMode(Mode::Normal(Normal::default()));
+
+        let msg = match key {
+            Key::Esc | Key::Char('v') => Some(normal_mode),
+            Key::Char('d' | 'x') => {
+                Some(Message::Batch(vec![Message::DeleteSelection, normal_mode]))
+            }
+            Key::Char('y') => Some(Message::Batch(vec![Message::YankSelection, normal_mode])),
+            Key::Home => Some(Message::MoveCursorHome),
+            Key::End => Some(Message::MoveCursorLineEnd),
+            Key::PageUp => Some(Message::MoveCursorPageUp),
+            Key::PageDown => Some(Message::MoveCursorPageDown),
+            Key::CtrlLeft => Some(Message::MoveCursorWordBackward(1)),
+            Key::CtrlRight => Some(Message::MoveCursorWordForward(1)),
+            Key::CtrlHome => Some(Message::MoveCursorDocumentStart),
+            Key::CtrlEnd => Some(Message::MoveCursorDocumentEnd),
+            Key::Char(ch) => {
+                self.input_buffer.push(ch);
+
+                let movement = normal::movement_for_input(&self.input_buffer);
+                if movement.is_some() || !normal::is_pending(&self.input_buffer) {
+                    self.input_buffer.clear();
+                }
+
+                return movement;
+            }
+            _ => None,
+        };
+
+        self.input_buffer.clear();
+        msg
+    }
+}
+
 mod normal {
-    use super::{Execute, Insert, Mode};
+    use super::{Execute, Insert, Mode, Replace, Search, Visual};
     use crate::communication::Message;
     use nom::{
         branch::alt,
+        bytes::complete::tag,
         character::complete::{char, digit0, one_of},
-        combinator::{all_consuming, map, recognize, value},
-        sequence::pair,
+        combinator::{all_consuming, map, opt, recognize, value},
+        sequence::{pair, preceded},
         IResult,
     };
 
+    /// Ctrl-w is held in the input as the control character that terminals send for it.
+    pub const CTRL_W: char = '\u{17}';
+
     pub fn command_for_input(input: &str) -> Option<Message> {
-        if let Ok((_, command)) =
-            all_consuming(alt((command_mode, insert_mode, movement_action)))(input)
+        if let Ok((_, command)) = all_consuming(alt((
+            command_mode,
+            visual_mode,
+            paste,
+            search_mode,
+            search_next,
+            insert_mode,
+            replace_mode,
+            append_mode,
+            movement_action,
+            goto_last_edit,
+            open_line,
+            join_lines,
+            delete_chars,
+            delete_word,
+            change,
+            toggle_case,
+            indent,
+            window_command,
+            repeat,
+            undo,
+        )))(input)
         {
             return Some(command);
         }
@@ -208,6 +670,40 @@ mod normal {
         None
     }
 
+    /// Parse input that is only allowed to move the cursor, such as in Visual mode.
+    pub fn movement_for_input(input: &str) -> Option<Message> {
+        if let Ok((_, command)) = all_consuming(movement_action)(input) {
+            return Some(command);
+        }
+
+        None
+    }
+
+    /// Returns `true` if the input is an incomplete command, such as a multiplier or a key that
+    /// must be followed by another.
+    pub fn is_pending(input: &str) -> bool {
+        all_consuming(pending)(input).is_ok()
+    }
+
+    fn pending(input: &str) -> IResult<&str, &str> {
+        recognize(pair(
+            opt(multiplier),
+            opt(alt((tag("da"), recognize(prefix_key)))),
+        ))(input)
+    }
+
+    fn prefix_key(input: &str) -> IResult<&str, char> {
+        alt((
+            char('`'),
+            char(CTRL_W),
+            char('g'),
+            char('>'),
+            char('<'),
+            char('d'),
+            char('c'),
+        ))(input)
+    }
+
     fn command_mode(input: &str) -> IResult<&str, Message> {
         value(
             Message::EnterMode(Mode::Execute(Execute::default())),
@@ -215,13 +711,153 @@ mod normal {
         )(input)
     }
 
+    fn visual_mode(input: &str) -> IResult<&str, Message> {
+        value(
+            Message::EnterMode(Mode::Visual(Visual::default())),
+            char('v'),
+        )(input)
+    }
+
+    fn paste(input: &str) -> IResult<&str, Message> {
+        value(Message::Paste, char('p'))(input)
+    }
+
+    fn search_mode(input: &str) -> IResult<&str, Message> {
+        value(Message::EnterMode(Mode::Search(Search)), char('/'))(input)
+    }
+
+    fn search_next(input: &str) -> IResult<&str, Message> {
+        alt((
+            value(Message::SearchNext, char('n')),
+            value(Message::SearchPrevious, char('N')),
+        ))(input)
+    }
+
     fn insert_mode(input: &str) -> IResult<&str, Message> {
+        value(Message::EnterMode(Mode::Insert(Insert)), char('i'))(input)
+    }
+
+    fn replace_mode(input: &str) -> IResult<&str, Message> {
+        value(Message::EnterMode(Mode::Replace(Replace)), char('R'))(input)
+    }
+
+    fn append_mode(input: &str) -> IResult<&str, Message> {
+        map(one_of("aAI"), |c| {
+            Message::Batch(vec![
+                match c {
+                    'a' => Message::MoveCursorAfterChar,
+                    'A' => Message::MoveCursorLineEnd,
+                    'I' => Message::MoveCursorLineFirstNonBlank,
+                    _ => unreachable!(),
+                },
+                Message::EnterMode(Mode::Insert(Insert)),
+            ])
+        })(input)
+    }
+
+    fn open_line(input: &str) -> IResult<&str, Message> {
+        map(one_of("oO"), |c| {
+            Message::Batch(vec![
+                Message::EnterMode(Mode::Insert(Insert)),
+                if c == 'o' {
+                    Message::OpenLineBelow
+                } else {
+                    Message::OpenLineAbove
+                },
+            ])
+        })(input)
+    }
+
+    /// `J` joins the line below onto the current line. Like vim, a count is the total number of
+    /// lines to join so `J` and `2J` both join two lines.
+    fn join_lines(input: &str) -> IResult<&str, Message> {
+        map(pair(count, char('J')), |(n, _)| {
+            Message::JoinLines(n.saturating_sub(1).max(1))
+        })(input)
+    }
+
+    fn delete_chars(input: &str) -> IResult<&str, Message> {
+        map(pair(count, one_of("xX")), |(n, c)| {
+            if c == 'x' {
+                Message::DeleteCharsUnderCursor(n)
+            } else {
+                Message::DeleteCharsBeforeCursor(n)
+            }
+        })(input)
+    }
+
+    /// `dw`, `db` and `de` delete as far as the word motion would move the cursor, `daw` deletes
+    /// the word under the cursor along with its surrounding whitespace.
+    fn delete_word(input: &str) -> IResult<&str, Message> {
+        map(
+            pair(
+                count,
+                preceded(char('d'), alt((tag("aw"), tag("w"), tag("b"), tag("e")))),
+            ),
+            |(n, motion)| match motion {
+                "w" => Message::DeleteWordForward(n),
+                "b" => Message::DeleteWordBackward(n),
+                "e" => Message::DeleteToWordEnd(n),
+                _ => Message::DeleteAWord(n),
+            },
+        )(input)
+    }
+
+    /// `cc` changes whole lines, `C` changes to the end of the line and `cw` changes words. Insert
+    /// mode is entered first so that the text typed in place of the change is undone with it.
+    fn change(input: &str) -> IResult<&str, Message> {
+        map(
+            pair(count, alt((tag("cc"), tag("cw"), tag("C")))),
+            |(n, key)| {
+                Message::Batch(vec![
+                    Message::EnterMode(Mode::Insert(Insert)),
+                    match key {
+                        "cc" => Message::ChangeLine(n),
+                        "cw" => Message::ChangeWord(n),
+                        _ => Message::ChangeToLineEnd,
+                    },
+                ])
+            },
+        )(input)
+    }
+
+    fn toggle_case(input: &str) -> IResult<&str, Message> {
+        map(pair(count, char('~')), |(n, _)| Message::ToggleCase(n))(input)
+    }
+
+    /// `>>` and `<<` indent and dedent the line, a count is the number of lines to change.
+    fn indent(input: &str) -> IResult<&str, Message> {
+        map(pair(count, alt((tag(">>"), tag("<<")))), |(n, key)| {
+            if key == ">>" {
+                Message::IndentLine(n)
+            } else {
+                Message::DedentLine(n)
+            }
+        })(input)
+    }
+
+    /// `<C-w>w`, or `<C-w><C-w>`, moves focus to the next pane.
+    fn window_command(input: &str) -> IResult<&str, Message> {
         value(
-            Message::EnterMode(Mode::Insert(Insert::default())),
-            char('i'),
+            Message::FocusNextPane,
+            pair(char(CTRL_W), alt((char('w'), char(CTRL_W)))),
         )(input)
     }
 
+    fn repeat(input: &str) -> IResult<&str, Message> {
+        map(pair(count, char('.')), |(n, _)| {
+            Message::RepeatLastChange(n)
+        })(input)
+    }
+
+    fn undo(input: &str) -> IResult<&str, Message> {
+        value(Message::Undo, char('u'))(input)
+    }
+
+    fn goto_last_edit(input: &str) -> IResult<&str, Message> {
+        value(Message::GotoLastEdit, tag("`."))(input)
+    }
+
     fn non_zero_digit(input: &str) -> IResult<&str, char> {
         one_of("123456789")(input)
     }
@@ -230,31 +866,187 @@ mod normal {
         recognize(pair(non_zero_digit, digit0))(input)
     }
 
+    /// An optional multiplier preceding a command, defaulting to 1 when it is omitted.
+    fn count(input: &str) -> IResult<&str, usize> {
+        map(opt(multiplier), |m| {
+            m.map_or(1, |m| m.parse().unwrap_or(usize::MAX))
+        })(input)
+    }
+
     fn movement_key(input: &str) -> IResult<&str, char> {
-        alt((char('h'), char('j'), char('k'), char('l')))(input)
+        one_of("hjklwbe")(input)
     }
 
-    fn single_move_action(input: &str) -> IResult<&str, Message> {
-        map(movement_key, |c| match c {
-            'h' => Message::MoveCursorLeft(1),
-            'j' => Message::MoveCursorDown(1),
-            'k' => Message::MoveCursorUp(1),
-            'l' => Message::MoveCursorRight(1),
+    fn movement_for_key(key: char, n: usize) -> Message {
+        match key {
+            'h' => Message::MoveCursorLeft(n),
+            'j' => Message::MoveCursorDown(n),
+            'k' => Message::MoveCursorUp(n),
+            'l' => Message::MoveCursorRight(n),
+            'w' => Message::MoveCursorWordForward(n),
+            'b' => Message::MoveCursorWordBackward(n),
+            'e' => Message::MoveCursorWordEnd(n),
             _ => unreachable!(),
-        })(input)
+        }
     }
 
-    fn multi_move_action(input: &str) -> IResult<&str, Message> {
-        map(pair(multiplier, movement_key), |(m, c)| match c {
-            'h' => Message::MoveCursorLeft(m.parse::<usize>().unwrap()),
-            'j' => Message::MoveCursorDown(m.parse::<usize>().unwrap()),
-            'k' => Message::MoveCursorUp(m.parse::<usize>().unwrap()),
-            'l' => Message::MoveCursorRight(m.parse::<usize>().unwrap()),
+    fn move_action(input: &str) -> IResult<&str, Message> {
+        map(pair(count, movement_key), |(n, c)| movement_for_key(c, n))(input)
+    }
+
+    /// `0`, `^` and `$` move to the start, first non-blank character and end of the line. A
+    /// leading `0` is never a multiplier so it is always a movement.
+    fn line_move_action(input: &str) -> IResult<&str, Message> {
+        map(one_of("0^$"), |c| match c {
+            '0' => Message::MoveCursorLineStart,
+            '^' => Message::MoveCursorLineFirstNonBlank,
+            '$' => Message::MoveCursorLineEnd,
             _ => unreachable!(),
         })(input)
     }
 
+    /// `gg` and `G` jump to the first and last line, or to line n when given a count.
+    fn document_move_action(input: &str) -> IResult<&str, Message> {
+        map(
+            pair(opt(multiplier), alt((tag("gg"), tag("G")))),
+            |(m, key)| match m {
+                Some(n) => Message::GoToLine(n.parse().unwrap_or(usize::MAX)),
+                None => Message::Batch(vec![
+                    if key == "gg" {
+                        Message::MoveCursorDocumentStart
+                    } else {
+                        Message::MoveCursorDocumentEnd
+                    },
+                    Message::MoveCursorLineFirstNonBlank,
+                ]),
+            },
+        )(input)
+    }
+
     fn movement_action(input: &str) -> IResult<&str, Message> {
-        alt((single_move_action, multi_move_action))(input)
+        alt((move_action, line_move_action, document_move_action))(input)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{command_for_input, is_pending};
+        use crate::communication::Message;
+        use crate::mode::{Insert, Mode, Replace};
+
+        #[test]
+        fn test_command_for_input() {
+            let tests = vec![
+                ("3j", Message::MoveCursorDown(3)),
+                ("w", Message::MoveCursorWordForward(1)),
+                ("12b", Message::MoveCursorWordBackward(12)),
+                ("2e", Message::MoveCursorWordEnd(2)),
+                ("`.", Message::GotoLastEdit),
+                ("u", Message::Undo),
+                (".", Message::RepeatLastChange(1)),
+                ("3.", Message::RepeatLastChange(3)),
+                ("J", Message::JoinLines(1)),
+                ("2J", Message::JoinLines(1)),
+                ("4J", Message::JoinLines(3)),
+                ("x", Message::DeleteCharsUnderCursor(1)),
+                ("3x", Message::DeleteCharsUnderCursor(3)),
+                ("X", Message::DeleteCharsBeforeCursor(1)),
+                ("dw", Message::DeleteWordForward(1)),
+                ("3dw", Message::DeleteWordForward(3)),
+                ("db", Message::DeleteWordBackward(1)),
+                ("de", Message::DeleteToWordEnd(1)),
+                ("daw", Message::DeleteAWord(1)),
+                ("~", Message::ToggleCase(1)),
+                ("R", Message::EnterMode(Mode::Replace(Replace))),
+                ("5~", Message::ToggleCase(5)),
+                (">>", Message::IndentLine(1)),
+                ("3>>", Message::IndentLine(3)),
+                ("<<", Message::DedentLine(1)),
+                ("2<<", Message::DedentLine(2)),
+                ("\u{17}w", Message::FocusNextPane),
+                ("\u{17}\u{17}", Message::FocusNextPane),
+                ("N", Message::SearchPrevious),
+                ("0", Message::MoveCursorLineStart),
+                ("^", Message::MoveCursorLineFirstNonBlank),
+                ("$", Message::MoveCursorLineEnd),
+                ("10l", Message::MoveCursorRight(10)),
+                (
+                    "99999999999999999999999w",
+                    Message::MoveCursorWordForward(usize::MAX),
+                ),
+                ("12G", Message::GoToLine(12)),
+                ("3gg", Message::GoToLine(3)),
+                (
+                    "gg",
+                    Message::Batch(vec![
+                        Message::MoveCursorDocumentStart,
+                        Message::MoveCursorLineFirstNonBlank,
+                    ]),
+                ),
+                (
+                    "G",
+                    Message::Batch(vec![
+                        Message::MoveCursorDocumentEnd,
+                        Message::MoveCursorLineFirstNonBlank,
+                    ]),
+                ),
+                (
+                    "A",
+                    Message::Batch(vec![
+                        Message::MoveCursorLineEnd,
+                        Message::EnterMode(Mode::Insert(Insert)),
+                    ]),
+                ),
+                (
+                    "2cw",
+                    Message::Batch(vec![
+                        Message::EnterMode(Mode::Insert(Insert)),
+                        Message::ChangeWord(2),
+                    ]),
+                ),
+                (
+                    "cc",
+                    Message::Batch(vec![
+                        Message::EnterMode(Mode::Insert(Insert)),
+                        Message::ChangeLine(1),
+                    ]),
+                ),
+                (
+                    "C",
+                    Message::Batch(vec![
+                        Message::EnterMode(Mode::Insert(Insert)),
+                        Message::ChangeToLineEnd,
+                    ]),
+                ),
+                (
+                    "o",
+                    Message::Batch(vec![
+                        Message::EnterMode(Mode::Insert(Insert)),
+                        Message::OpenLineBelow,
+                    ]),
+                ),
+            ];
+
+            for (input, command) in tests {
+                assert_eq!(command_for_input(input), Some(command));
+            }
+        }
+
+        #[test]
+        fn test_is_pending() {
+            assert!(is_pending("3"));
+            assert!(is_pending("12"));
+            assert!(is_pending("`"));
+            assert!(is_pending("\u{17}"));
+            assert!(is_pending("g"));
+            assert!(is_pending("5g"));
+            assert!(is_pending(">"));
+            assert!(is_pending("3<"));
+            assert!(is_pending("d"));
+            assert!(is_pending("2da"));
+            assert!(is_pending("c"));
+            assert!(!is_pending("0"));
+            assert!(!is_pending("z"));
+            assert!(!is_pending("`z"));
+        }
     }
 }
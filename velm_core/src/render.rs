@@ -1,4 +1,4 @@
-use crate::ui::{Color, Position, Rect};
+use crate::ui::{grapheme_width, Color, Position, Rect, Theme};
 use anyhow::Result;
 use std::io::Error as IoError;
 use thiserror::Error;
@@ -24,12 +24,25 @@ pub trait Canvas {
     /// Show the cursor.
     fn show_cursor(&mut self) -> Result<(), IoError>;
 
+    /// Set the shape of the cursor.
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<(), IoError>;
+
     /// Get the size of the ui.
     fn size(&self) -> Result<Rect, IoError>;
 }
 
+/// The shape of the cursor, such as the bar that is shown while inserting text.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum CursorShape {
+    #[default]
+    Block,
+    Bar,
+    Underline,
+}
+
 /// A single cell within the frame. Each cell has a position, symbol (the shown character)
-/// and style information.
+/// and style information. Wide characters are held in a single cell followed by spacer cells
+/// with an empty symbol.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Cell {
     position: Position,
@@ -61,6 +74,12 @@ impl Cell {
         self.symbol = " ".into();
     }
 
+    /// Returns `true` if the Cell is covered by the wide character drawn in the Cell before it.
+    #[must_use]
+    pub fn is_spacer(&self) -> bool {
+        self.symbol.is_empty()
+    }
+
     /// Returns the Cell's symbol.
     #[must_use]
     pub fn symbol(&self) -> &String {
@@ -126,19 +145,21 @@ impl Frame {
         self.cursor_position
     }
 
-    /// Diff the current `Frame` with the other `Frame` to get a list of changed `Cell`s.
+    /// Diff the current `Frame` with the other `Frame` to get a list of changed `Cell`s. Frames
+    /// covering different areas can not be compared cell by cell, so every `Cell` of the other
+    /// `Frame` is changed.
     fn diff<'a>(&self, other: &'a Frame) -> Vec<&'a Cell> {
-        let front_buffer = &self.cells;
-        let back_buffer = &other.cells;
-
-        let mut updates = vec![];
-        for (i, (front, back)) in back_buffer.iter().zip(front_buffer.iter()).enumerate() {
-            if front != back {
-                updates.push(&back_buffer[i]);
-            }
+        if self.area != other.area {
+            return other.cells.iter().collect();
         }
 
-        updates
+        other
+            .cells
+            .iter()
+            .zip(self.cells.iter())
+            .filter(|(back, front)| back != front)
+            .map(|(back, _)| back)
+            .collect()
     }
 
     fn index_of(&self, position: &Position) -> Result<usize, OutOfBoundsError> {
@@ -159,19 +180,32 @@ impl Frame {
 
     /// Write a line into the `Frame`. This will overwrite any Cells currently set in the `Frame`'s
     /// given line. If the string does not fill the line it, the rest of the line will be cleared.
+    /// Graphemes that do not fit within the width of the `Frame` are dropped so that a long line
+    /// never spills onto the next.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` without writing anything if the row is outside of the `Frame`, such as
+    /// while a `View` catches up with a resize.
     pub fn write_line(
         &mut self,
         row_number: usize,
         string: &str,
         foreground: Color,
         background: Color,
-    ) {
-        let index = self.index_of(&Position::new(0, row_number)).unwrap();
+    ) -> Result<(), OutOfBoundsError> {
+        let index = self.index_of(&Position::new(0, row_number))?;
+        let mut col = 0;
+
+        for grapheme in string[..].graphemes(true) {
+            let width = grapheme_width(grapheme);
 
-        for (i, grapheme) in string[..].graphemes(true).enumerate() {
-            // TODO: do we want to cap the line length here? If the line is longer than the width do we truncate?
+            // A wide character that would be cut in half by the edge of the Frame is not drawn.
+            if col + width > self.area.width {
+                break;
+            }
 
-            let cell_idx = index + i;
+            let cell_idx = index + col;
             self.cells[cell_idx] = Cell::new(
                 self.cells[cell_idx].position.col,
                 self.cells[cell_idx].position.row,
@@ -179,11 +213,43 @@ impl Frame {
                 foreground,
                 background,
             );
+
+            // The terminal draws wide characters over the following cells so they are filled with
+            // empty spacers that are skipped when drawing.
+            for spacer_idx in cell_idx + 1..cell_idx + width {
+                self.cells[spacer_idx] = Cell::new(
+                    self.cells[spacer_idx].position.col,
+                    self.cells[spacer_idx].position.row,
+                    "",
+                    foreground,
+                    background,
+                );
+            }
+
+            col += width;
         }
 
-        for i in index + string[..].graphemes(true).count()..index + self.area.width {
+        for i in index + col..index + self.area.width {
             self.cells[i].reset();
         }
+
+        Ok(())
+    }
+
+    /// Override the foreground color of the `Cell` at the given position. Positions outside of
+    /// the `Frame` are ignored.
+    pub fn set_foreground(&mut self, position: &Position, color: Color) {
+        if let Ok(idx) = self.index_of(position) {
+            self.cells[idx].foreground = color;
+        }
+    }
+
+    /// Override the background color of the `Cell` at the given position. Positions outside of
+    /// the `Frame` are ignored.
+    pub fn set_background(&mut self, position: &Position, color: Color) {
+        if let Ok(idx) = self.index_of(position) {
+            self.cells[idx].background = color;
+        }
     }
 
     /// Set the cursor position for the final frame render.
@@ -194,7 +260,7 @@ impl Frame {
 
 /// `View` can be implemented on any `Component` to allow it to be drawn to the `Viewport`.
 pub trait View {
-    fn render_to(&self, frame: &mut Frame);
+    fn render_to(&self, frame: &mut Frame, theme: &Theme);
 }
 
 /// The area of the screen that we can draw to. The Viewport is responsible for handling
@@ -204,6 +270,7 @@ pub struct Viewport<'a, C: Canvas> {
     canvas: &'a mut C,
     frames: [Frame; 2],
     current_frame_idx: usize,
+    cursor_shape: CursorShape,
 }
 
 impl<'a, C: Canvas> Viewport<'a, C> {
@@ -218,6 +285,7 @@ impl<'a, C: Canvas> Viewport<'a, C> {
             canvas,
             frames: [Frame::empty(area), Frame::empty(area)],
             current_frame_idx: 0,
+            cursor_shape: CursorShape::default(),
         })
     }
 
@@ -226,23 +294,62 @@ impl<'a, C: Canvas> Viewport<'a, C> {
         self.area
     }
 
+    /// Resize the viewport to the given area. Both `Frame`s are reallocated and the `Canvas` is
+    /// cleared so that the next render repaints the whole screen.
+    pub fn resize(&mut self, area: Rect) -> Result<()> {
+        use anyhow::Context;
+
+        self.canvas
+            .clear()
+            .context("unable to clear canvas for resize")?;
+
+        self.area = area;
+        self.frames = [Frame::empty(area), Frame::empty(area)];
+        self.current_frame_idx = 0;
+
+        Ok(())
+    }
+
+    /// Set the shape of the cursor. The `Canvas` is only updated when the shape changes.
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        use anyhow::Context;
+
+        if shape == self.cursor_shape {
+            return Ok(());
+        }
+
+        self.canvas
+            .set_cursor_shape(shape)
+            .context("unable to set cursor shape")?;
+        self.cursor_shape = shape;
+
+        self.canvas.flush().context("unable to flush canvas")
+    }
+
     /// Draw the current `Frame` to the screen. This will call the given callback allowing the caller
     /// to define render order and cursor position. `Frame` swapping and diff is handled here to
     /// ensure that only the required screen cells are updated.
-    pub fn render<V: View>(&mut self, view: &V) -> Result<()> {
+    pub fn render<V: View>(&mut self, view: &V, theme: &Theme) -> Result<()> {
         use anyhow::Context;
 
-        self.canvas
-            .hide_cursor()
-            .context("unable to hide cursor pre draw")?;
-
-        view.render_to(&mut self.frames[self.current_frame_idx]);
+        view.render_to(&mut self.frames[self.current_frame_idx], theme);
 
         let next_cursor_pos = self.frames[self.current_frame_idx].cursor_position;
 
         let previous_frame = &self.frames[1 - self.current_frame_idx];
         let changes = previous_frame.diff(&self.frames[self.current_frame_idx]);
 
+        // Nothing has changed, such as after moving past the end of a row, so there is no need to
+        // touch the screen.
+        if changes.is_empty() && next_cursor_pos == previous_frame.cursor_position {
+            self.swap_buffers();
+            return Ok(());
+        }
+
+        self.canvas
+            .hide_cursor()
+            .context("unable to hide cursor pre draw")?;
+
         self.canvas
             .draw(changes.into_iter())
             .context("unable to draw buffer diff")?;
@@ -268,9 +375,112 @@ impl<'a, C: Canvas> Viewport<'a, C> {
 
 impl<'a, G: Canvas> Drop for Viewport<'a, G> {
     /// When the Viewport goes out of scope (application has ended) we want to ensure that the
-    /// screen is cleared and flushed to leave the user with a clean terminal.
+    /// screen is cleared and flushed to leave the user with a clean terminal. The cursor is put
+    /// back to a block so that the terminal is not left with the shape of the last mode.
     fn drop(&mut self) {
         self.canvas.clear().unwrap();
+        self.canvas.set_cursor_shape(CursorShape::Block).unwrap();
         self.canvas.flush().unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{CursorShape, Frame, Viewport};
+    use crate::ui::{Color, Position, Rect};
+    use crate::TestCanvas;
+
+    fn symbols(frame: &Frame) -> Vec<&str> {
+        frame
+            .cells
+            .iter()
+            .map(|cell| cell.symbol().as_str())
+            .collect()
+    }
+
+    #[test]
+    fn diff_contains_only_the_changed_cells() {
+        let front = Frame::empty(Rect::new(3, 2));
+        let mut back = Frame::empty(Rect::new(3, 2));
+        back.write_line(1, "a", Color::Reset, Color::Reset).unwrap();
+
+        let changes = front.diff(&back);
+
+        assert_eq!(1, changes.len());
+        assert_eq!(&Position::new(0, 1), changes[0].position());
+    }
+
+    #[test]
+    fn diff_of_frames_with_different_areas_contains_every_cell() {
+        let front = Frame::empty(Rect::new(3, 2));
+        let back = Frame::empty(Rect::new(5, 4));
+
+        let changes = front.diff(&back);
+
+        assert_eq!(20, changes.len());
+        assert_eq!(&Position::new(4, 3), changes[19].position());
+        assert_eq!(6, back.diff(&front).len());
+    }
+
+    #[test]
+    fn write_line_places_wide_characters_over_two_cells() {
+        let mut frame = Frame::empty(Rect::new(6, 1));
+        frame
+            .write_line(0, "a\u{1f980}\u{4e16}b", Color::Reset, Color::Reset)
+            .unwrap();
+
+        assert_eq!(
+            vec!["a", "\u{1f980}", "", "\u{4e16}", "", "b"],
+            symbols(&frame)
+        );
+        assert!(frame.cells[2].is_spacer());
+        assert!(!frame.cells[3].is_spacer());
+    }
+
+    #[test]
+    fn write_line_does_not_split_a_wide_character_at_the_edge() {
+        let mut frame = Frame::empty(Rect::new(3, 1));
+        frame
+            .write_line(0, "ab\u{1f980}", Color::Reset, Color::Reset)
+            .unwrap();
+
+        assert_eq!(vec!["a", "b", " "], symbols(&frame));
+    }
+
+    #[test]
+    fn write_line_truncates_an_over_long_line_without_touching_the_next_row() {
+        let mut frame = Frame::empty(Rect::new(4, 2));
+        frame
+            .write_line(1, "next", Color::Reset, Color::Reset)
+            .unwrap();
+        frame
+            .write_line(0, "far too long", Color::Reset, Color::Reset)
+            .unwrap();
+
+        assert_eq!(
+            vec!["f", "a", "r", " ", "n", "e", "x", "t"],
+            symbols(&frame)
+        );
+    }
+
+    #[test]
+    fn write_line_beyond_the_frame_is_an_error() {
+        let mut frame = Frame::empty(Rect::new(4, 2));
+
+        assert!(frame
+            .write_line(2, "oops", Color::Reset, Color::Reset)
+            .is_err());
+        assert_eq!(vec![" "; 8], symbols(&frame));
+    }
+
+    #[test]
+    fn cursor_shape_is_reset_to_a_block_when_the_viewport_is_dropped() {
+        let mut canvas = TestCanvas::new(10, 2);
+
+        let mut viewport = Viewport::new(&mut canvas).unwrap();
+        viewport.set_cursor_shape(CursorShape::Bar).unwrap();
+        drop(viewport);
+
+        assert_eq!(CursorShape::Block, canvas.cursor_shape());
+    }
+}
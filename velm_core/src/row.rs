@@ -1,86 +1,269 @@
+use crate::ui::grapheme_width;
+use std::cmp;
 use unicode_segmentation::UnicodeSegmentation;
 
+/// Drawn in place of the first column of a tab by `Row::to_list_string`.
+const TAB_INDICATOR: char = '→';
+
+/// Drawn in place of each trailing space by `Row::to_list_string`.
+const TRAILING_SPACE_INDICATOR: char = '·';
+
 /// A single row of text within the editor.
 #[derive(Debug, Default, PartialEq, Eq, Clone)]
 pub struct Row {
     string: String,
+    /// The number of graphemes in the string. It is counted once whenever the string changes,
+    /// rather than each time it is needed, as segmenting a long row is slow.
+    len: usize,
 }
 
 impl Row {
-    /// Convert the Row to a String allowing for a fixed length to be taken.
-    pub fn to_string(&self, start: usize, end: usize) -> String {
-        use std::cmp;
+    /// Convert the Row to a String allowing for a fixed length to be taken. The start and end are
+    /// display columns so wide characters take up two of them and tabs expand to the next
+    /// multiple of `tab_width`. Tabs, and wide characters that are cut by either edge, are
+    /// replaced by spaces to keep the result exactly as wide as requested.
+    pub fn to_string(&self, start: usize, end: usize, tab_width: usize) -> String {
+        self.render(start, end, tab_width, false)
+    }
+
+    /// As `to_string`, except whitespace is made visible like vim's `list` option. Tabs are drawn
+    /// as an arrow followed by spaces up to the next tab stop and trailing spaces as a dot, so
+    /// each indicator takes up exactly the columns of the whitespace that it stands for.
+    pub fn to_list_string(&self, start: usize, end: usize, tab_width: usize) -> String {
+        self.render(start, end, tab_width, true)
+    }
+
+    /// The display columns that `to_list_string` draws an indicator at.
+    pub fn whitespace_columns(&self, tab_width: usize) -> Vec<usize> {
+        let trailing_from = self.trailing_spaces_from();
+
+        self.columns(tab_width)
+            .enumerate()
+            .filter(|(idx, (grapheme, _, _))| {
+                *grapheme == "\t" || (*grapheme == " " && *idx >= trailing_from)
+            })
+            .map(|(_, (_, col, _))| col)
+            .collect()
+    }
+
+    /// The grapheme index that the spaces at the end of the Row start at.
+    fn trailing_spaces_from(&self) -> usize {
+        self.string.trim_end_matches(' ').graphemes(true).count()
+    }
 
-        let end = cmp::min(end, self.string.len());
-        let start = cmp::min(start, end);
+    fn render(&self, start: usize, end: usize, tab_width: usize, list: bool) -> String {
+        let trailing_from = self.trailing_spaces_from();
         let mut result = String::new();
 
-        for grapheme in self.string[..]
-            .graphemes(true)
-            .skip(start)
-            .take(end - start)
-        {
-            if grapheme == "\t" {
-                result.push(' ');
-            } else {
+        for (idx, (grapheme, col, width)) in self.columns(tab_width).enumerate() {
+            if col >= end {
+                break;
+            }
+
+            if col + width <= start {
+                continue;
+            }
+
+            let visible = cmp::min(col + width, end) - cmp::max(col, start);
+
+            // A tab that starts before the visible columns is left blank rather than showing an
+            // indicator part way through.
+            if list && grapheme == "\t" && col >= start {
+                result.push(TAB_INDICATOR);
+                result.push_str(&" ".repeat(visible - 1));
+            } else if list && grapheme == " " && idx >= trailing_from {
+                result.push(TRAILING_SPACE_INDICATOR);
+            } else if grapheme != "\t" && col >= start && col + width <= end {
                 result.push_str(grapheme);
+            } else {
+                result.push_str(&" ".repeat(visible));
             }
         }
 
         result
     }
 
-    /// Convert the full Row to a String.
+    /// Convert the full Row to a String. Each tab is converted to a single space.
     pub fn contents(&self) -> String {
-        self.to_string(0, self.len())
+        self.to_string(0, self.width(1), 1)
     }
 
     /// Append another Row to the current Row.
     pub fn append(&mut self, new: &Self) {
-        self.string = format!("{}{}", self.string, new.string);
+        self.string.push_str(&new.string);
+        self.count_graphemes();
     }
 
     /// Delete the character at the given index. If the index is greater than the length of the
     /// Row then nothing will happen.
     pub fn delete(&mut self, at: usize) {
-        if at >= self.len() {
-            return;
+        if let Some(range) = self.grapheme_range(at) {
+            self.string.replace_range(range, "");
+            self.count_graphemes();
         }
-
-        let mut result: String = self.string[..].graphemes(true).take(at).collect();
-        let remainder: String = self.string[..].graphemes(true).skip(at + 1).collect();
-        result.push_str(&remainder);
-        self.string = result;
     }
 
     /// Insert a character at the given position in the Row. If the index is greater than the
     /// length of the Row then the character will be insterted at the next position.
     pub fn insert(&mut self, at: usize, ch: char) {
-        if at >= self.len() {
-            self.string.push(ch);
-            return;
-        }
+        self.insert_str(at, ch.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Insert the text before the grapheme at the given index in the Row. If the index is greater
+    /// than the length of the Row then the text is appended.
+    pub fn insert_str(&mut self, at: usize, text: &str) {
+        let idx = self.byte_index(at);
+
+        self.string.insert_str(idx, text);
+        self.count_graphemes();
+    }
+
+    /// Replace the grapheme at the given index with the text, returning the grapheme that was
+    /// replaced. If the index is greater than the length of the Row then the text is appended and
+    /// `None` is returned.
+    pub fn replace(&mut self, at: usize, text: &str) -> Option<String> {
+        let replaced = if let Some(range) = self.grapheme_range(at) {
+            let replaced = self.string[range.clone()].to_string();
+            self.string.replace_range(range, text);
+            Some(replaced)
+        } else {
+            self.string.push_str(text);
+            None
+        };
+
+        self.count_graphemes();
+        replaced
+    }
+
+    /// Swap the case of the grapheme at the given index. Characters whose case does not map to a
+    /// single character, such as `ß` which uppercases to `SS`, are left as they are. If the index
+    /// is greater than the length of the Row then nothing will happen.
+    pub fn toggle_case_at(&mut self, at: usize) {
+        fn toggle(ch: char) -> char {
+            let swapped: Vec<char> = if ch.is_lowercase() {
+                ch.to_uppercase().collect()
+            } else if ch.is_uppercase() {
+                ch.to_lowercase().collect()
+            } else {
+                return ch;
+            };
 
-        let mut result: String = self.string[..].graphemes(true).take(at).collect();
-        let remainder: String = self.string[..].graphemes(true).skip(at).collect();
+            match swapped[..] {
+                [swapped] => swapped,
+                _ => ch,
+            }
+        }
 
-        result.push(ch);
-        result.push_str(&remainder);
-        self.string = result;
+        if let Some(range) = self.grapheme_range(at) {
+            let toggled: String = self.string[range.clone()].chars().map(toggle).collect();
+            self.string.replace_range(range, &toggled);
+            self.count_graphemes();
+        }
     }
 
     /// Split the Row at the given position, returning a new Row with the split string and updating
     /// the current row to the first half of the split.
     pub fn split(&mut self, at: usize) -> Self {
-        let beginning: String = self.string[..].graphemes(true).take(at).collect();
-        let remainder: String = self.string[..].graphemes(true).skip(at).collect();
-        self.string = beginning;
+        let remainder = self.string.split_off(self.byte_index(at));
+        self.count_graphemes();
         Self::from(&remainder[..])
     }
 
+    /// Take the graphemes between the start (inclusive) and end (exclusive) index. Unlike
+    /// `to_string` this leaves tabs untouched.
+    pub fn slice(&self, start: usize, end: usize) -> String {
+        self.string[..]
+            .graphemes(true)
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .collect()
+    }
+
+    /// The graphemes that make up the Row, in order.
+    pub fn graphemes(&self) -> unicode_segmentation::Graphemes<'_> {
+        self.string[..].graphemes(true)
+    }
+
     /// The length of the Row. Graphemes are accounted for.
     pub fn len(&self) -> usize {
-        self.string[..].graphemes(true).count()
+        self.len
+    }
+
+    fn count_graphemes(&mut self) {
+        self.len = self.string.graphemes(true).count();
+    }
+
+    /// The byte index in the string of the grapheme at the given index, or the end of the
+    /// string if the index is past the end of the Row.
+    fn byte_index(&self, at: usize) -> usize {
+        if at >= self.len {
+            return self.string.len();
+        }
+
+        self.string
+            .grapheme_indices(true)
+            .nth(at)
+            .map_or(self.string.len(), |(idx, _)| idx)
+    }
+
+    /// The range of bytes in the string taken up by the grapheme at the given index.
+    fn grapheme_range(&self, at: usize) -> Option<std::ops::Range<usize>> {
+        if at >= self.len {
+            return None;
+        }
+
+        self.string
+            .grapheme_indices(true)
+            .nth(at)
+            .map(|(idx, grapheme)| idx..idx + grapheme.len())
+    }
+
+    /// Each grapheme in the Row along with the display column it starts at and the number of
+    /// columns it takes up. Tabs take up the columns up to the next multiple of `tab_width`.
+    fn columns(&self, tab_width: usize) -> impl Iterator<Item = (&str, usize, usize)> + '_ {
+        let tab_width = cmp::max(1, tab_width);
+        let mut col = 0;
+
+        self.string[..].graphemes(true).map(move |grapheme| {
+            let width = if grapheme == "\t" {
+                tab_width - col % tab_width
+            } else {
+                grapheme_width(grapheme)
+            };
+
+            let start = col;
+            col += width;
+
+            (grapheme, start, width)
+        })
+    }
+
+    /// The number of display columns taken up by the Row.
+    pub fn width(&self, tab_width: usize) -> usize {
+        self.width_to(self.len(), tab_width)
+    }
+
+    /// The number of display columns taken up by the graphemes before the given index. This is
+    /// the column that the grapheme at the index is drawn at.
+    pub fn width_to(&self, at: usize, tab_width: usize) -> usize {
+        self.columns(tab_width)
+            .take(at)
+            .map(|(_, _, width)| width)
+            .sum()
+    }
+
+    /// The index of the grapheme drawn at the given display column. Columns covered by the rest
+    /// of a wide character or tab map to that grapheme and columns past the end of the Row map to
+    /// the length of the Row.
+    pub fn index_at_width(&self, col: usize, tab_width: usize) -> usize {
+        self.columns(tab_width)
+            .position(|(_, start, width)| start + width > col)
+            .unwrap_or_else(|| self.len())
+    }
+
+    /// The text of the Row exactly as it is stored, tabs included.
+    pub fn as_str(&self) -> &str {
+        &self.string
     }
 
     /// Convert the Row to an array of bytes for writing.
@@ -92,6 +275,59 @@ impl Row {
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// The grapheme index of the first character that isn't whitespace. If the Row is blank then
+    /// the length of the Row is returned.
+    pub fn first_non_blank(&self) -> usize {
+        self.string[..]
+            .graphemes(true)
+            .position(|g| !g.chars().all(char::is_whitespace))
+            .unwrap_or_else(|| self.len())
+    }
+
+    /// The start and end grapheme index of each word in the Row. Whitespace separates words and
+    /// punctuation is treated as a word of its own.
+    pub fn words(&self) -> Vec<(usize, usize)> {
+        let mut words = Vec::new();
+        let mut idx = 0;
+
+        for segment in self.string.split_word_bounds() {
+            let len = segment.graphemes(true).count();
+
+            if !segment.chars().all(char::is_whitespace) {
+                words.push((idx, idx + len - 1));
+            }
+
+            idx += len;
+        }
+
+        words
+    }
+
+    /// Find every non-overlapping occurrence of the given pattern in the Row, returning the
+    /// grapheme index that each match starts at.
+    pub fn find_all(&self, pattern: &str) -> Vec<usize> {
+        if pattern.is_empty() {
+            return Vec::new();
+        }
+
+        self.string
+            .match_indices(pattern)
+            .map(|(byte_idx, _)| self.string[..byte_idx].graphemes(true).count())
+            .collect()
+    }
+
+    /// Find the first occurrence of the given pattern in the Row, returning the grapheme index
+    /// that the match starts at.
+    pub fn find(&self, pattern: &str) -> Option<usize> {
+        if pattern.is_empty() {
+            return None;
+        }
+
+        self.string
+            .find(pattern)
+            .map(|byte_idx| self.string[..byte_idx].graphemes(true).count())
+    }
 }
 
 /// This is the only way a Row can be constructed.
@@ -99,6 +335,7 @@ impl From<&str> for Row {
     fn from(slice: &str) -> Self {
         Self {
             string: String::from(slice),
+            len: slice.graphemes(true).count(),
         }
     }
 }
@@ -109,22 +346,49 @@ mod tests {
 
     #[test]
     fn to_string_can_take_a_snippet_of_the_row() {
-        assert_eq!("Hello", &Row::from("Hello World!").to_string(0, 5));
-        assert_eq!("World", &Row::from("Hello World!").to_string(6, 11));
+        assert_eq!("Hello", &Row::from("Hello World!").to_string(0, 5, 1));
+        assert_eq!("World", &Row::from("Hello World!").to_string(6, 11, 1));
         assert_eq!(
             "\u{1f980}\u{1f980}\u{1f980}",
-            &Row::from("\u{1f980}\u{1f980}\u{1f980}\u{1f980}\u{1f980}").to_string(1, 4)
+            &Row::from("\u{1f980}\u{1f980}\u{1f980}\u{1f980}\u{1f980}").to_string(2, 8, 1)
+        );
+    }
+
+    #[test]
+    fn to_string_pads_wide_characters_cut_by_the_edges() {
+        assert_eq!(
+            " \u{4e16} ",
+            &Row::from("\u{1f980}\u{4e16}\u{1f980}").to_string(1, 5, 1)
         );
     }
 
     #[test]
     fn to_string_clamps_to_row_len() {
-        assert_eq!("Hello", &Row::from("Hello").to_string(0, 100));
+        assert_eq!("Hello", &Row::from("Hello").to_string(0, 100, 1));
+    }
+
+    #[test]
+    fn to_list_string_shows_tabs_and_trailing_spaces() {
+        let row = Row::from("\ta b  ");
+
+        assert_eq!("→   a b··", &row.to_list_string(0, 10, 4));
+        assert_eq!("  a b·", &row.to_list_string(2, 8, 4));
+        assert_eq!("→ ", &Row::from("\tx").to_list_string(0, 2, 4));
+        assert_eq!(vec![0, 7, 8], row.whitespace_columns(4));
+        assert_eq!(row.width(4), row.to_list_string(0, 10, 4).chars().count());
     }
 
     #[test]
     fn to_string_converts_tab_to_space() {
-        assert_eq!("  ", &Row::from("\t\t\t\t").to_string(1, 3));
+        assert_eq!("  ", &Row::from("\t\t\t\t").to_string(1, 3, 1));
+    }
+
+    #[test]
+    fn to_string_expands_tabs_to_the_next_tab_stop() {
+        assert_eq!("a   b", &Row::from("a\tb").to_string(0, 10, 4));
+        assert_eq!("ab  c", &Row::from("ab\tc").to_string(0, 10, 4));
+        assert_eq!("    x", &Row::from("\tx").to_string(0, 10, 4));
+        assert_eq!("  x", &Row::from("\tx").to_string(2, 10, 4));
     }
 
     #[test]
@@ -173,6 +437,44 @@ mod tests {
         assert_eq!("1\u{1f980}2", &row.contents());
     }
 
+    #[test]
+    fn text_can_be_inserted_between_graphemes() {
+        let text = "e\u{301}\u{1f980}";
+
+        let mut row = Row::from("a\u{1f980}b");
+        row.insert_str(0, text);
+        assert_eq!("e\u{301}\u{1f980}a\u{1f980}b", row.as_str());
+        assert_eq!(5, row.len());
+
+        let mut row = Row::from("a\u{1f980}b");
+        row.insert_str(2, text);
+        assert_eq!("a\u{1f980}e\u{301}\u{1f980}b", row.as_str());
+
+        let mut row = Row::from("a\u{1f980}b");
+        row.insert_str(10, text);
+        assert_eq!("a\u{1f980}be\u{301}\u{1f980}", row.as_str());
+    }
+
+    #[test]
+    fn replace_overwrites_a_grapheme_or_appends_past_the_end() {
+        let mut row = Row::from("a\u{1f980}c");
+        assert_eq!(Some("\u{1f980}".to_string()), row.replace(1, "b"));
+        assert_eq!(None, row.replace(3, "d"));
+        assert_eq!("abcd", &row.contents());
+    }
+
+    #[test]
+    fn toggle_case_at_swaps_the_case_of_a_single_grapheme() {
+        let mut row = Row::from("aB1\u{e9}\u{df}");
+        row.toggle_case_at(0);
+        row.toggle_case_at(1);
+        row.toggle_case_at(2);
+        row.toggle_case_at(3);
+        row.toggle_case_at(4);
+        row.toggle_case_at(10);
+        assert_eq!("Ab1\u{c9}\u{df}", &row.contents());
+    }
+
     #[test]
     fn row_can_be_split_at_position() {
         let mut row = Row::from("\u{1f980} Rust is awesome!");
@@ -181,6 +483,13 @@ mod tests {
         assert_eq!("awesome!", &other.contents());
     }
 
+    #[test]
+    fn slice_takes_graphemes_without_converting_tabs() {
+        assert_eq!("\tis", &Row::from("Rust\tis great").slice(4, 7));
+        assert_eq!("great", &Row::from("Rust\tis great").slice(8, 100));
+        assert_eq!("", &Row::from("Rust").slice(3, 1));
+    }
+
     #[test]
     fn len_is_calculated() {
         assert_eq!(12, Row::from("Hello World!").len());
@@ -205,4 +514,107 @@ mod tests {
     fn is_empty_is_false_when_len_is_greater_than_zero() {
         assert!(!Row::from("123").is_empty());
     }
+
+    #[test]
+    fn first_non_blank_skips_leading_whitespace() {
+        assert_eq!(2, Row::from(" \tfn main() {}").first_non_blank());
+        assert_eq!(0, Row::from("fn").first_non_blank());
+        assert_eq!(3, Row::from("   ").first_non_blank());
+    }
+
+    #[test]
+    fn words_returns_the_bounds_of_each_word() {
+        assert_eq!(
+            vec![(0, 2), (4, 9), (11, 11), (13, 13), (14, 14)],
+            Row::from("let crab_1 = x;").words()
+        );
+        assert_eq!(vec![(0, 0), (2, 5)], Row::from("\u{1f980} rust").words());
+        assert_eq!(vec![(2, 6)], Row::from("  hello  ").words());
+        assert!(Row::default().words().is_empty());
+    }
+
+    #[test]
+    fn find_returns_grapheme_index_of_first_match() {
+        assert_eq!(Some(6), Row::from("Hello World World").find("World"));
+        assert_eq!(Some(2), Row::from("\u{1f980}\u{1f980}Rust").find("Rust"));
+    }
+
+    #[test]
+    fn find_all_returns_grapheme_index_of_each_match() {
+        assert_eq!(vec![1, 6], Row::from("\u{1f980}rust rust").find_all("rust"));
+        assert!(Row::from("rust").find_all("").is_empty());
+    }
+
+    #[test]
+    fn find_returns_none_when_pattern_is_missing_or_empty() {
+        assert_eq!(None, Row::from("Hello").find("World"));
+        assert_eq!(None, Row::from("Hello").find(""));
+    }
+
+    #[test]
+    fn width_counts_wide_characters_as_two_columns() {
+        let row = Row::from("a\u{1f980}\u{4e16}\u{754c}b");
+
+        assert_eq!(5, row.len());
+        assert_eq!(8, row.width(4));
+        assert_eq!(0, row.width_to(0, 4));
+        assert_eq!(1, row.width_to(1, 4));
+        assert_eq!(3, row.width_to(2, 4));
+        assert_eq!(7, row.width_to(4, 4));
+    }
+
+    #[test]
+    fn index_at_width_maps_display_columns_to_graphemes() {
+        let row = Row::from("a\u{1f980}b");
+
+        assert_eq!(0, row.index_at_width(0, 4));
+        assert_eq!(1, row.index_at_width(1, 4));
+        assert_eq!(1, row.index_at_width(2, 4));
+        assert_eq!(2, row.index_at_width(3, 4));
+        assert_eq!(3, row.index_at_width(10, 4));
+    }
+
+    #[test]
+    fn tabs_take_up_the_columns_to_the_next_tab_stop() {
+        let row = Row::from("a\tb\t");
+
+        assert_eq!(8, row.width(4));
+        assert_eq!(4, row.width_to(2, 4));
+        assert_eq!(1, row.index_at_width(3, 4));
+        assert_eq!(2, row.index_at_width(4, 4));
+        assert_eq!(4, row.width(1));
+    }
+
+    #[test]
+    fn len_follows_edits_to_the_row() {
+        let mut row = Row::from("e\u{1f980}");
+        row.insert_str(1, "\u{301}");
+        assert_eq!(2, row.len());
+        row.replace(0, "ab");
+        assert_eq!(3, row.len());
+        row.toggle_case_at(0);
+        assert_eq!("Ab\u{1f980}", row.as_str());
+        let tail = row.split(1);
+        assert_eq!((1, 2), (row.len(), tail.len()));
+        row.append(&tail);
+        row.delete(2);
+        assert_eq!("Ab", row.as_str());
+        assert_eq!(2, row.len());
+    }
+
+    /// A rough benchmark rather than a check. Run it with
+    /// `cargo test --release -p velm_core -- --ignored --nocapture`.
+    #[test]
+    #[ignore = "benchmark"]
+    fn inserting_many_characters_into_a_long_row() {
+        let mut row = Row::from("x".repeat(10_000).as_str());
+        let start = std::time::Instant::now();
+
+        for at in 0..10_000 {
+            row.insert(at * 2 % row.len(), 'a');
+        }
+
+        println!("inserted 10000 characters in {:?}", start.elapsed());
+        assert_eq!(20_000, row.len());
+    }
 }
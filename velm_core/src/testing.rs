@@ -0,0 +1,228 @@
+use crate::render::{Canvas, Cell, CursorShape};
+use crate::ui::{Color, Position, Rect};
+use std::io::Error as IoError;
+
+/// An in-memory `Canvas` that records drawn `Cell`s into a grid so that tests can assert on what
+/// would have been shown on screen without needing a terminal.
+///
+/// The grid and cursor are captured each time the canvas is flushed after a draw. This means the
+/// last rendered frame can still be inspected once the `Viewport` has cleared the canvas on drop.
+pub struct TestCanvas {
+    size: Rect,
+    cells: Vec<Vec<Cell>>,
+    cursor: Position,
+    cursor_visible: bool,
+    cursor_shape: CursorShape,
+    drawn: bool,
+    frames: usize,
+    screen: Vec<Vec<Cell>>,
+    screen_cursor_position: Position,
+}
+
+impl TestCanvas {
+    /// Create a blank `TestCanvas` of the given size.
+    #[must_use]
+    pub fn new(width: usize, height: usize) -> Self {
+        let cells = Self::blank(width, height);
+
+        Self {
+            size: Rect::new(width, height),
+            screen: cells.clone(),
+            cells,
+            cursor: Position::default(),
+            cursor_visible: true,
+            cursor_shape: CursorShape::default(),
+            drawn: false,
+            frames: 0,
+            screen_cursor_position: Position::default(),
+        }
+    }
+
+    fn blank(width: usize, height: usize) -> Vec<Vec<Cell>> {
+        (0..height)
+            .map(|row| {
+                (0..width)
+                    .map(|col| Cell::new(col, row, " ", Color::Reset, Color::Reset))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The `Cell` at the given position as of the last flush.
+    #[must_use]
+    pub fn cell(&self, col: usize, row: usize) -> Option<&Cell> {
+        self.screen.get(row).and_then(|cells| cells.get(col))
+    }
+
+    /// The text shown on the given row as of the last flush. Spacers following wide characters
+    /// are skipped so the text reads as it would on screen.
+    #[must_use]
+    pub fn row(&self, row: usize) -> String {
+        self.screen.get(row).map_or(String::new(), |cells| {
+            cells
+                .iter()
+                .filter(|cell| !cell.is_spacer())
+                .map(|cell| cell.symbol().as_str())
+                .collect()
+        })
+    }
+
+    /// The text shown on every row as of the last flush.
+    #[must_use]
+    pub fn rows(&self) -> Vec<String> {
+        (0..self.size.height).map(|row| self.row(row)).collect()
+    }
+
+    /// The position of the cursor as of the last flush.
+    #[must_use]
+    pub fn cursor_position(&self) -> Position {
+        self.screen_cursor_position
+    }
+
+    /// The number of times that drawn `Cell`s have been flushed to the screen.
+    #[must_use]
+    pub fn frame_count(&self) -> usize {
+        self.frames
+    }
+
+    /// Returns `true` if the cursor is currently shown.
+    #[must_use]
+    pub fn is_cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    /// The shape that the cursor was last set to.
+    #[must_use]
+    pub fn cursor_shape(&self) -> CursorShape {
+        self.cursor_shape
+    }
+}
+
+impl Canvas for TestCanvas {
+    fn clear(&mut self) -> Result<(), IoError> {
+        self.cells = Self::blank(self.size.width, self.size.height);
+        Ok(())
+    }
+
+    fn draw<'a, I: Iterator<Item = &'a Cell>>(&mut self, cells: I) -> Result<(), IoError> {
+        for cell in cells {
+            let Position { col, row } = *cell.position();
+
+            if let Some(target) = self.cells.get_mut(row).and_then(|cells| cells.get_mut(col)) {
+                *target = cell.clone();
+            }
+        }
+
+        self.drawn = true;
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        if self.drawn {
+            self.screen = self.cells.clone();
+            self.screen_cursor_position = self.cursor;
+            self.drawn = false;
+            self.frames += 1;
+        }
+
+        Ok(())
+    }
+
+    fn hide_cursor(&mut self) -> Result<(), IoError> {
+        self.cursor_visible = false;
+        Ok(())
+    }
+
+    fn position_cursor(&mut self, row: usize, col: usize) -> Result<(), IoError> {
+        self.cursor = Position::new(col, row);
+        Ok(())
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<(), IoError> {
+        self.cursor_shape = shape;
+        Ok(())
+    }
+
+    fn show_cursor(&mut self) -> Result<(), IoError> {
+        self.cursor_visible = true;
+        Ok(())
+    }
+
+    fn size(&self) -> Result<Rect, IoError> {
+        Ok(self.size)
+    }
+}
+
+/// The name of a file in the temporary directory, removed when the `TempFile` is dropped so that
+/// it is cleaned up even when the test using it fails. The name includes the process id, so that
+/// test runs at the same time don't share the file.
+#[cfg(test)]
+pub(crate) struct TempFile(String);
+
+#[cfg(test)]
+impl TempFile {
+    pub(crate) fn new(name: &str) -> Self {
+        Self(
+            std::env::temp_dir()
+                .join(format!("velm-{}-{}", std::process::id(), name))
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempFile {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<std::path::Path> for TempFile {
+    fn as_ref(&self) -> &std::path::Path {
+        self.0.as_ref()
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TestCanvas;
+    use crate::render::{Frame, View, Viewport};
+    use crate::ui::{Color, Position, Theme};
+
+    struct Greeting;
+
+    impl View for Greeting {
+        fn render_to(&self, frame: &mut Frame, _theme: &Theme) {
+            frame
+                .write_line(1, "hi \u{1f980}", Color::Reset, Color::Reset)
+                .unwrap();
+            frame.set_cursor_position(Position::new(3, 1));
+        }
+    }
+
+    #[test]
+    fn records_the_last_rendered_frame() {
+        let mut canvas = TestCanvas::new(6, 2);
+
+        {
+            let mut viewport = Viewport::new(&mut canvas).unwrap();
+            viewport.render(&Greeting, &Theme::default()).unwrap();
+        }
+
+        assert_eq!(vec!["      ", "hi \u{1f980} "], canvas.rows());
+        assert_eq!(Position::new(3, 1), canvas.cursor_position());
+        assert!(canvas.cell(4, 1).unwrap().is_spacer());
+    }
+}
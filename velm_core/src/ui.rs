@@ -1,3 +1,8 @@
+use serde::{de, Deserialize, Deserializer};
+use std::cmp;
+use std::str::FromStr;
+use thiserror::Error;
+
 /// Colors supported by the editor.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Color {
@@ -28,6 +33,282 @@ impl Default for Color {
     }
 }
 
+impl Color {
+    /// Parse a hex color in the form `#RRGGBB`, or the short form `#RGB`, into `Color::Rgb`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the input is not a `#` followed by three or six hex digits.
+    pub fn from_hex(hex: &str) -> Result<Self, ParseColorError> {
+        let invalid = || ParseColorError(hex.into());
+
+        let digits = hex
+            .strip_prefix('#')
+            .filter(|digits| digits.chars().all(|ch| ch.is_ascii_hexdigit()))
+            .ok_or_else(invalid)?;
+
+        let component = |s: &str| u8::from_str_radix(s, 16).map_err(|_| invalid());
+
+        match digits.len() {
+            // Each digit of the short form is repeated, e.g. `#f80` is `#ff8800`.
+            3 => {
+                let short = |i: usize| component(&digits[i..=i]).map(|c| c * 17);
+                Ok(Color::Rgb(short(0)?, short(1)?, short(2)?))
+            }
+            6 => Ok(Color::Rgb(
+                component(&digits[0..2])?,
+                component(&digits[2..4])?,
+                component(&digits[4..6])?,
+            )),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// The index of the nearest color in the xterm 256 color palette, for terminals that can't
+    /// show 24-bit colors. Named colors are the first 16 entries of the palette. `Reset` is the
+    /// terminal's own default so it has no index.
+    #[must_use]
+    pub fn to_ansi256(self) -> Option<u8> {
+        Some(match self {
+            Color::Reset => return None,
+            Color::Black => 0,
+            Color::Red => 1,
+            Color::Green => 2,
+            Color::Yellow => 3,
+            Color::Blue => 4,
+            Color::Magenta => 5,
+            Color::Cyan => 6,
+            Color::Gray => 7,
+            Color::DarkGray => 8,
+            Color::LightRed => 9,
+            Color::LightGreen => 10,
+            Color::LightYellow => 11,
+            Color::LightBlue => 12,
+            Color::LightMagenta => 13,
+            Color::LightCyan => 14,
+            Color::White => 15,
+            Color::AnsiValue(value) => value,
+            Color::Rgb(r, g, b) => rgb_to_ansi256(r, g, b),
+        })
+    }
+}
+
+/// The levels of each component in the 6x6x6 color cube that makes up entries 16 to 231 of the
+/// xterm 256 color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Find the nearest color in either the color cube or the grayscale ramp, which is entries 232 to
+/// 255 of the palette.
+fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    let cube_index = |v: u8| match v {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (v - 35) / 40,
+    };
+    let distance = |(r2, g2, b2): (u8, u8, u8)| {
+        [(r, r2), (g, g2), (b, b2)]
+            .iter()
+            .map(|&(x, y)| (i32::from(x) - i32::from(y)).pow(2))
+            .sum::<i32>()
+    };
+
+    let (ri, gi, bi) = (cube_index(r), cube_index(g), cube_index(b));
+    let cube = (
+        CUBE_LEVELS[usize::from(ri)],
+        CUBE_LEVELS[usize::from(gi)],
+        CUBE_LEVELS[usize::from(bi)],
+    );
+
+    let (gray_index, gray_distance) = (0..24)
+        .map(|i: u8| {
+            let gray = 8 + 10 * i;
+            (i, distance((gray, gray, gray)))
+        })
+        .min_by_key(|&(_, distance)| distance)
+        .unwrap_or((0, i32::MAX));
+
+    if gray_distance < distance(cube) {
+        232 + gray_index
+    } else {
+        16 + 36 * ri + 6 * gi + bi
+    }
+}
+
+/// The error returned when a string does not name a `Color`.
+#[derive(Error, Debug, PartialEq)]
+#[error("unknown color {0}")]
+pub struct ParseColorError(String);
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    /// Parse a color from its name, e.g. `red`, `dark-gray` or `light_blue`, or from hex such as
+    /// `#3f3f3f`. Names are case insensitive and `reset` (or `default`) is the terminal's own
+    /// color.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with('#') {
+            return Self::from_hex(s);
+        }
+
+        let name = s
+            .chars()
+            .filter(|ch| !matches!(ch, '-' | '_' | ' '))
+            .collect::<String>()
+            .to_lowercase();
+
+        Ok(match name.as_str() {
+            "reset" | "default" => Color::Reset,
+            "black" => Color::Black,
+            "red" => Color::Red,
+            "green" => Color::Green,
+            "yellow" => Color::Yellow,
+            "blue" => Color::Blue,
+            "magenta" => Color::Magenta,
+            "cyan" => Color::Cyan,
+            "gray" | "grey" => Color::Gray,
+            "darkgray" | "darkgrey" => Color::DarkGray,
+            "lightred" => Color::LightRed,
+            "lightgreen" => Color::LightGreen,
+            "lightyellow" => Color::LightYellow,
+            "lightblue" => Color::LightBlue,
+            "lightmagenta" => Color::LightMagenta,
+            "lightcyan" => Color::LightCyan,
+            "white" => Color::White,
+            _ => return Err(ParseColorError(s.into())),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// `Theme` assigns a `Color` to each part of the ui that is drawn. Every `View` is given the theme
+/// to draw with so that the colors can be swapped out as a whole.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// Document text and any other text without a role of its own.
+    pub foreground: Color,
+    pub background: Color,
+    pub line_number: Color,
+    /// The `~` drawn on the rows past the end of a document.
+    pub tilde: Color,
+    /// The background of the selected text in Visual mode.
+    pub selection: Color,
+    /// The background of the line that the cursor is on, when highlighted.
+    pub current_line: Color,
+    /// The indicators drawn for tabs and trailing spaces when whitespace is shown.
+    pub whitespace: Color,
+    /// The background of the bracket under the cursor and the bracket that it pairs with.
+    pub matching_bracket: Color,
+    /// Every match of the last search.
+    pub search_match_fg: Color,
+    pub search_match_bg: Color,
+    /// The status bar in Normal mode.
+    pub status_fg: Color,
+    pub status_bg: Color,
+    pub insert_status_fg: Color,
+    pub insert_status_bg: Color,
+    pub visual_status_fg: Color,
+    pub visual_status_bg: Color,
+    /// The status bar while entering a command or search.
+    pub command_status_fg: Color,
+    pub command_status_bg: Color,
+    /// The divider below a pane that is not focused.
+    pub divider_fg: Color,
+    pub divider_bg: Color,
+    pub active_divider_fg: Color,
+    pub active_divider_bg: Color,
+    /// The selected entry in a list, such as the quickfix list.
+    pub highlight_fg: Color,
+    pub highlight_bg: Color,
+    pub error_fg: Color,
+    pub error_bg: Color,
+}
+
+impl Theme {
+    /// The default theme, intended for terminals with a dark background.
+    #[must_use]
+    pub fn dark() -> Self {
+        Self {
+            foreground: Color::Reset,
+            background: Color::Reset,
+            line_number: Color::DarkGray,
+            tilde: Color::Gray,
+            selection: Color::DarkGray,
+            current_line: Color::AnsiValue(236),
+            whitespace: Color::DarkGray,
+            matching_bracket: Color::Cyan,
+            search_match_fg: Color::Black,
+            search_match_bg: Color::Yellow,
+            status_fg: Color::White,
+            status_bg: Color::Blue,
+            insert_status_fg: Color::Black,
+            insert_status_bg: Color::Green,
+            visual_status_fg: Color::Black,
+            visual_status_bg: Color::Magenta,
+            command_status_fg: Color::Black,
+            command_status_bg: Color::Yellow,
+            divider_fg: Color::Black,
+            divider_bg: Color::Gray,
+            active_divider_fg: Color::Black,
+            active_divider_bg: Color::White,
+            highlight_fg: Color::Black,
+            highlight_bg: Color::Gray,
+            error_fg: Color::White,
+            error_bg: Color::Red,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+/// The number of terminal columns taken up by the grapheme when drawn. Wide characters such as
+/// emoji and CJK take up two columns. Graphemes that have no width of their own, such as control
+/// characters, are given a single column so that the cursor can always be placed on them.
+#[must_use]
+pub fn grapheme_width(grapheme: &str) -> usize {
+    use std::cmp;
+    use unicode_width::UnicodeWidthStr;
+
+    cmp::max(1, grapheme.width())
+}
+
+/// How line numbers are displayed in the gutter of a `Buffer`.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum LineNumberStyle {
+    /// Each line shows its own line number.
+    #[default]
+    Absolute,
+    /// Each line shows its distance from the cursor's line.
+    Relative,
+    /// As `Relative`, except the cursor's line shows its own line number.
+    Hybrid,
+}
+
+impl LineNumberStyle {
+    /// The number to display for the (0 based) row given the row that the cursor is on.
+    #[must_use]
+    pub fn number_for(self, row: usize, cursor_row: usize) -> usize {
+        let distance = row.abs_diff(cursor_row);
+
+        match self {
+            LineNumberStyle::Absolute => row + 1,
+            LineNumberStyle::Hybrid if distance == 0 => row + 1,
+            LineNumberStyle::Relative | LineNumberStyle::Hybrid => distance,
+        }
+    }
+}
+
 /// A position in ui space.
 #[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
 pub struct Position {
@@ -37,6 +318,7 @@ pub struct Position {
 
 impl Position {
     /// Create a new Position.
+    #[must_use]
     pub fn new(col: usize, row: usize) -> Self {
         Self { col, row }
     }
@@ -58,6 +340,7 @@ pub struct Rect {
 
 impl Rect {
     /// Create a new Rect with default Position (0, 0).
+    #[must_use]
     pub fn new(width: usize, height: usize) -> Self {
         Self {
             width,
@@ -67,6 +350,7 @@ impl Rect {
     }
 
     /// Create a new Rect with a set Position.
+    #[must_use]
     pub fn positioned(width: usize, height: usize, col: usize, row: usize) -> Self {
         Self {
             width,
@@ -76,42 +360,169 @@ impl Rect {
     }
 
     /// Returns the area of the Rect.
+    #[must_use]
     pub fn area(&self) -> usize {
         self.width.saturating_mul(self.height)
     }
 
     /// Returns the leftmost possible value of the Rect. **Note**: This is zero based.
+    #[must_use]
     pub fn left(&self) -> usize {
         self.position.col
     }
 
     /// Returns the rightmost possible value of the Rect. **Note**: This is zero based.
+    #[must_use]
     pub fn right(&self) -> usize {
         self.position.col + self.width - 1
     }
 
     /// Returns the topmost possible value of the Rect. **Note**: This is zero based.
+    #[must_use]
     pub fn top(&self) -> usize {
         self.position.row
     }
 
     /// Returns the bottommost possible value of the Rect. **Note**: This is zero based.
+    #[must_use]
     pub fn bottom(&self) -> usize {
         self.position.row + self.height - 1
     }
 
     /// Check if the given position is within the Rect, taking the Rect's Position into
     /// consideration.
+    #[must_use]
     pub fn contains(&self, position: &Position) -> bool {
         let Position { col, row } = *position;
 
         col >= self.left() && col <= self.right() && row >= self.top() && row <= self.bottom()
     }
+
+    /// Split the Rect into the rows above `at` and the rows from `at` onwards. The split is
+    /// clamped to the height of the Rect so the bottom Rect may be empty.
+    #[must_use]
+    pub fn split_horizontal(&self, at: usize) -> (Rect, Rect) {
+        let at = cmp::min(at, self.height);
+
+        (
+            Rect::positioned(self.width, at, self.left(), self.top()),
+            Rect::positioned(self.width, self.height - at, self.left(), self.top() + at),
+        )
+    }
+
+    /// Split the Rect into the columns left of `at` and the columns from `at` onwards. The split
+    /// is clamped to the width of the Rect so the right Rect may be empty.
+    #[must_use]
+    pub fn split_vertical(&self, at: usize) -> (Rect, Rect) {
+        let at = cmp::min(at, self.width);
+
+        (
+            Rect::positioned(at, self.height, self.left(), self.top()),
+            Rect::positioned(self.width - at, self.height, self.left() + at, self.top()),
+        )
+    }
+
+    /// Shrink the Rect by the margin on all sides, keeping it centred. A margin that is too large
+    /// for the Rect leaves an empty Rect in the middle.
+    #[must_use]
+    pub fn inner(&self, margin: usize) -> Rect {
+        let horizontal = cmp::min(margin, self.width / 2);
+        let vertical = cmp::min(margin, self.height / 2);
+
+        Rect::positioned(
+            self.width.saturating_sub(margin * 2),
+            self.height.saturating_sub(margin * 2),
+            self.left() + horizontal,
+            self.top() + vertical,
+        )
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Position, Rect};
+    use super::{grapheme_width, Color, LineNumberStyle, Position, Rect};
+
+    #[test]
+    fn grapheme_width_accounts_for_wide_characters() {
+        assert_eq!(grapheme_width("a"), 1);
+        assert_eq!(grapheme_width("\u{1f980}"), 2);
+        assert_eq!(grapheme_width("\u{4e16}"), 2);
+        assert_eq!(grapheme_width("\t"), 1);
+    }
+
+    #[test]
+    fn colors_are_parsed_from_their_names() {
+        assert_eq!(Ok(Color::Red), "red".parse());
+        assert_eq!(Ok(Color::DarkGray), "dark-gray".parse());
+        assert_eq!(Ok(Color::LightBlue), "Light_Blue".parse());
+        assert_eq!(Ok(Color::Reset), "default".parse());
+        assert!("chartreuse".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn hex_colors_are_parsed() {
+        assert_eq!(Ok(Color::Rgb(63, 63, 63)), Color::from_hex("#3f3f3f"));
+        assert_eq!(Ok(Color::Rgb(171, 205, 239)), Color::from_hex("#ABCDEF"));
+        assert_eq!(Ok(Color::Rgb(255, 136, 0)), Color::from_hex("#f80"));
+        assert_eq!(Ok(Color::Rgb(1, 2, 3)), "#010203".parse());
+    }
+
+    #[test]
+    fn malformed_hex_colors_are_rejected() {
+        for hex in &[
+            "",
+            "#",
+            "3f3f3f",
+            "#3f3f3",
+            "#3f3f3f3f",
+            "#ggg",
+            "#+1+2+3",
+            "#\u{e9}\u{e9}",
+        ] {
+            assert!(Color::from_hex(hex).is_err(), "{} should be rejected", hex);
+        }
+    }
+
+    #[test]
+    fn rgb_colors_map_to_the_nearest_256_color() {
+        assert_eq!(Some(196), Color::Rgb(255, 0, 0).to_ansi256());
+        assert_eq!(Some(46), Color::Rgb(0, 255, 0).to_ansi256());
+        assert_eq!(Some(21), Color::Rgb(0, 0, 255).to_ansi256());
+        assert_eq!(Some(16), Color::Rgb(0, 0, 0).to_ansi256());
+        assert_eq!(Some(231), Color::Rgb(255, 255, 255).to_ansi256());
+        assert_eq!(Some(244), Color::Rgb(128, 128, 128).to_ansi256());
+        assert_eq!(Some(236), Color::Rgb(50, 48, 49).to_ansi256());
+        assert_eq!(Some(208), Color::Rgb(255, 136, 0).to_ansi256());
+    }
+
+    #[test]
+    fn named_colors_map_to_the_first_16_colors() {
+        assert_eq!(Some(1), Color::Red.to_ansi256());
+        assert_eq!(Some(8), Color::DarkGray.to_ansi256());
+        assert_eq!(Some(15), Color::White.to_ansi256());
+        assert_eq!(Some(42), Color::AnsiValue(42).to_ansi256());
+        assert_eq!(None, Color::Reset.to_ansi256());
+    }
+
+    #[test]
+    fn absolute_line_numbers_ignore_the_cursor() {
+        assert_eq!(LineNumberStyle::Absolute.number_for(0, 5), 1);
+        assert_eq!(LineNumberStyle::Absolute.number_for(5, 5), 6);
+    }
+
+    #[test]
+    fn relative_line_numbers_count_up_in_both_directions() {
+        assert_eq!(LineNumberStyle::Relative.number_for(3, 5), 2);
+        assert_eq!(LineNumberStyle::Relative.number_for(5, 5), 0);
+        assert_eq!(LineNumberStyle::Relative.number_for(7, 5), 2);
+    }
+
+    #[test]
+    fn hybrid_line_numbers_show_the_absolute_number_on_the_cursor_line() {
+        assert_eq!(LineNumberStyle::Hybrid.number_for(4, 5), 1);
+        assert_eq!(LineNumberStyle::Hybrid.number_for(5, 5), 6);
+        assert_eq!(LineNumberStyle::Hybrid.number_for(6, 5), 1);
+    }
 
     #[test]
     fn new_sets_default_position() {
@@ -183,4 +594,54 @@ mod tests {
         let r = Rect::positioned(10, 10, 10, 10);
         assert_eq!(r.contains(&Position::new(20, 20)), false);
     }
+
+    #[test]
+    fn split_horizontal_tiles_the_rect() {
+        let (top, bottom) = Rect::positioned(10, 8, 2, 3).split_horizontal(5);
+
+        assert_eq!(Rect::positioned(10, 5, 2, 3), top);
+        assert_eq!(Rect::positioned(10, 3, 2, 8), bottom);
+        assert_eq!(top.bottom() + 1, bottom.top());
+    }
+
+    #[test]
+    fn split_horizontal_clamps_to_the_height() {
+        let (top, bottom) = Rect::positioned(10, 8, 2, 3).split_horizontal(20);
+
+        assert_eq!(Rect::positioned(10, 8, 2, 3), top);
+        assert_eq!(0, bottom.height);
+    }
+
+    #[test]
+    fn split_vertical_tiles_the_rect() {
+        let (left, right) = Rect::positioned(10, 8, 2, 3).split_vertical(4);
+
+        assert_eq!(Rect::positioned(4, 8, 2, 3), left);
+        assert_eq!(Rect::positioned(6, 8, 6, 3), right);
+        assert_eq!(left.right() + 1, right.left());
+    }
+
+    #[test]
+    fn split_vertical_clamps_to_the_width() {
+        let (left, right) = Rect::positioned(10, 8, 2, 3).split_vertical(11);
+
+        assert_eq!(Rect::positioned(10, 8, 2, 3), left);
+        assert_eq!(0, right.width);
+    }
+
+    #[test]
+    fn inner_shrinks_on_all_sides() {
+        assert_eq!(
+            Rect::positioned(6, 2, 3, 4),
+            Rect::positioned(10, 6, 1, 2).inner(2)
+        );
+    }
+
+    #[test]
+    fn inner_does_not_underflow() {
+        let r = Rect::positioned(3, 4, 1, 1).inner(5);
+
+        assert_eq!(0, r.area());
+        assert_eq!(Position::new(2, 3), r.position);
+    }
 }
@@ -1,30 +1,76 @@
 use anyhow::Result;
 use crossterm::{
-    cursor::{Hide, MoveTo, Show},
-    event::{DisableMouseCapture, EnableMouseCapture},
+    cursor::{Hide, MoveTo, SetCursorStyle, Show},
+    event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
     style::{Color as CrosstermColor, Print, SetBackgroundColor, SetForegroundColor},
     terminal::{Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use std::io::{self, Error as IoError, Write};
 use velm_core::{
-    ui::{Color as VelmColor, Rect},
-    Canvas, Cell, Event, EventStream, Key as VelmKey,
+    ui::{grapheme_width, Color as VelmColor, Position, Rect},
+    Canvas, Cell, CursorShape, Event, EventStream, Key as VelmKey, MouseKind,
 };
 
+#[cfg(feature = "termion")]
+mod termion_canvas;
+
+#[cfg(feature = "termion")]
+pub use termion_canvas::{map_termion_event_stream, TermionCanvas};
+
 /// Map the events coming from the crossterm EventStream into the events that are expected by the application.
 pub fn map_crossterm_event_stream() -> EventStream {
     use futures::StreamExt;
 
-    Box::pin(crossterm::event::EventStream::new().map(|possible_event| {
-        use crossterm::event as ctevent;
+    Box::pin(
+        crossterm::event::EventStream::new().filter_map(|possible_event| {
+            use crossterm::event::{self as ctevent, KeyEventKind};
+
+            futures::future::ready(match possible_event {
+                // Terminals that report key releases send them alongside each press.
+                Ok(ctevent::Event::Key(key)) if key.kind == KeyEventKind::Release => None,
+                Ok(ctevent::Event::Key(key)) => Some(Event::KeyPressed(Key::from(key).0)),
+                Ok(ctevent::Event::Mouse(event)) => Some(Event::MouseInput {
+                    col: usize::from(event.column),
+                    row: usize::from(event.row),
+                    kind: mouse_kind(event.kind),
+                }),
+                Ok(ctevent::Event::Resize(x, y)) => Some(Event::WindowResized(x, y)),
+                Ok(ctevent::Event::Paste(text)) => Some(Event::Paste(text)),
+                Ok(_) => None,
+                Err(e) => Some(Event::ReadFailed(e)),
+            })
+        }),
+    )
+}
+
+/// Map the crossterm mouse event kind into the kinds of mouse input understood by the application.
+fn mouse_kind(kind: crossterm::event::MouseEventKind) -> MouseKind {
+    use crossterm::event::{MouseButton, MouseEventKind};
 
-        match possible_event {
-            Ok(ctevent::Event::Key(key)) => Event::KeyPressed(Key::from(key).0),
-            Ok(ctevent::Event::Mouse(_)) => Event::MouseInputReceived,
-            Ok(ctevent::Event::Resize(x, y)) => Event::WindowResized(x, y),
-            Err(e) => Event::ReadFailed(e),
-        }
-    }))
+    match kind {
+        MouseEventKind::Down(MouseButton::Left) => MouseKind::LeftClick,
+        MouseEventKind::ScrollUp => MouseKind::ScrollUp,
+        MouseEventKind::ScrollDown => MouseKind::ScrollDown,
+        _ => MouseKind::Unknown,
+    }
+}
+
+/// Detect whether the terminal can show 24-bit colors from the `COLORTERM` variable that
+/// terminals set. Setting `VELM_TRUECOLOR` to `1` or `0` forces it on or off for terminals that
+/// get it wrong.
+fn detect_truecolor() -> bool {
+    truecolor_supported(
+        std::env::var("VELM_TRUECOLOR").ok().as_deref(),
+        std::env::var("COLORTERM").ok().as_deref(),
+    )
+}
+
+fn truecolor_supported(force: Option<&str>, colorterm: Option<&str>) -> bool {
+    match force.map(str::trim) {
+        Some("1" | "true" | "on") => true,
+        Some("0" | "false" | "off") => false,
+        _ => matches!(colorterm, Some("truecolor" | "24bit")),
+    }
 }
 
 /// Newtype to allow mapping VelmColor to CrosstermColor.
@@ -36,16 +82,41 @@ struct Key(VelmKey);
 /// Canvas implementation for crossterm.
 pub struct CrosstermCanvas<W: Write> {
     out: W,
+    /// Whether the terminal can show 24-bit colors, `Rgb` colors are drawn as the nearest of the
+    /// 256 colors when it can't.
+    truecolor: bool,
 }
 
 impl<W: Write> CrosstermCanvas<W> {
-    /// Creates a new CrosstermCanvas.
+    /// Creates a new CrosstermCanvas. Support for 24-bit colors is detected from the environment,
+    /// see `detect_truecolor`.
     pub fn new(mut out: W) -> Result<Self, IoError> {
         crossterm::terminal::enable_raw_mode()?;
         crossterm::execute!(out, EnterAlternateScreen)?;
         crossterm::execute!(out, EnableMouseCapture)?;
+        crossterm::execute!(out, EnableBracketedPaste)?;
+
+        Ok(Self {
+            out,
+            truecolor: detect_truecolor(),
+        })
+    }
+
+    /// Set whether the terminal can show 24-bit colors.
+    #[must_use]
+    pub fn with_truecolor(mut self, truecolor: bool) -> Self {
+        self.truecolor = truecolor;
+        self
+    }
 
-        Ok(Self { out })
+    /// The color to draw in place of the given color on this terminal.
+    fn supported_color(&self, color: VelmColor) -> Color {
+        match color {
+            VelmColor::Rgb(..) if !self.truecolor => {
+                Color(color.to_ansi256().map_or(color, VelmColor::AnsiValue))
+            }
+            _ => Color(color),
+        }
     }
 }
 
@@ -53,6 +124,8 @@ impl<W: Write> Drop for CrosstermCanvas<W> {
     /// Ensures that we LeaveAlternateScreen and disable_raw_mode before the application ends to
     /// return the user terminal back to normal.
     fn drop(&mut self) {
+        crossterm::execute!(self.out, DisableBracketedPaste)
+            .expect("unable to disable bracketed paste");
         crossterm::execute!(self.out, DisableMouseCapture)
             .expect("unable to disable mouse capture");
         crossterm::execute!(self.out, LeaveAlternateScreen)
@@ -73,23 +146,34 @@ impl<W: Write> Canvas for CrosstermCanvas<W> {
         let mut prev_background = Color(VelmColor::Reset);
         let mut prev_foreground = Color(VelmColor::Reset);
 
+        // Where the terminal cursor is left after printing the previous cell. Changed cells that
+        // are next to each other are printed as a run without moving the cursor between them.
+        let mut cursor: Option<(usize, usize)> = None;
+
         for cell in cells {
-            self.position_cursor(cell.position().row, cell.position().col)?;
+            // Spacers are covered by the wide character drawn before them.
+            if cell.is_spacer() {
+                continue;
+            }
+
+            let Position { row, col } = *cell.position();
+
+            if cursor != Some((row, col)) {
+                self.position_cursor(row, col)?;
+            }
+
+            cursor = Some((row, col + grapheme_width(cell.symbol())));
 
             if cell.background() != prev_background.0 {
-                crossterm::queue!(
-                    self.out,
-                    SetBackgroundColor(CrosstermColor::from(Color(cell.background())))
-                )?;
+                let bg = CrosstermColor::from(self.supported_color(cell.background()));
+                crossterm::queue!(self.out, SetBackgroundColor(bg))?;
 
                 prev_background = Color(cell.background());
             }
 
             if cell.foreground() != prev_foreground.0 {
-                crossterm::queue!(
-                    self.out,
-                    SetForegroundColor(CrosstermColor::from(Color(cell.foreground())))
-                )?;
+                let fg = CrosstermColor::from(self.supported_color(cell.foreground()));
+                crossterm::queue!(self.out, SetForegroundColor(fg))?;
 
                 prev_foreground = Color(cell.foreground());
             }
@@ -123,6 +207,16 @@ impl<W: Write> Canvas for CrosstermCanvas<W> {
         crossterm::queue!(self.out, MoveTo(x, y))
     }
 
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<(), IoError> {
+        let style = match shape {
+            CursorShape::Block => SetCursorStyle::SteadyBlock,
+            CursorShape::Bar => SetCursorStyle::SteadyBar,
+            CursorShape::Underline => SetCursorStyle::SteadyUnderScore,
+        };
+
+        crossterm::queue!(self.out, style)
+    }
+
     fn show_cursor(&mut self) -> Result<(), IoError> {
         crossterm::queue!(self.out, Show)
     }
@@ -163,79 +257,48 @@ impl From<crossterm::event::KeyEvent> for Key {
     fn from(event: crossterm::event::KeyEvent) -> Self {
         use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
-        match event {
-            KeyEvent {
-                modifiers: KeyModifiers::NONE,
-                code: KeyCode::Enter,
-            } => Key(VelmKey::Enter),
-            KeyEvent {
-                modifiers: KeyModifiers::NONE,
-                code: KeyCode::Tab,
-            } => Key(VelmKey::Tab),
-            KeyEvent {
-                modifiers: KeyModifiers::NONE,
-                code: KeyCode::Backspace,
-            } => Key(VelmKey::Backspace),
-            KeyEvent {
-                modifiers: KeyModifiers::NONE,
-                code: KeyCode::Esc,
-            } => Key(VelmKey::Esc),
-            KeyEvent {
-                modifiers: KeyModifiers::NONE,
-                code: KeyCode::Left,
-            } => Key(VelmKey::Left),
-            KeyEvent {
-                modifiers: KeyModifiers::NONE,
-                code: KeyCode::Right,
-            } => Key(VelmKey::Right),
-            KeyEvent {
-                modifiers: KeyModifiers::NONE,
-                code: KeyCode::Down,
-            } => Key(VelmKey::Down),
-            KeyEvent {
-                modifiers: KeyModifiers::NONE,
-                code: KeyCode::Up,
-            } => Key(VelmKey::Up),
-            KeyEvent {
-                modifiers: KeyModifiers::NONE,
-                code: KeyCode::Insert,
-            } => Key(VelmKey::Insert),
-            KeyEvent {
-                modifiers: KeyModifiers::NONE,
-                code: KeyCode::Delete,
-            } => Key(VelmKey::Delete),
-            KeyEvent {
-                modifiers: KeyModifiers::NONE,
-                code: KeyCode::Home,
-            } => Key(VelmKey::Home),
-            KeyEvent {
-                modifiers: KeyModifiers::NONE,
-                code: KeyCode::End,
-            } => Key(VelmKey::End),
-            KeyEvent {
-                modifiers: KeyModifiers::NONE,
-                code: KeyCode::PageUp,
-            } => Key(VelmKey::PageUp),
-            KeyEvent {
-                modifiers: KeyModifiers::NONE,
-                code: KeyCode::PageDown,
-            } => Key(VelmKey::PageDown),
-            KeyEvent {
-                modifiers: KeyModifiers::NONE,
-                code: KeyCode::Char(ch),
-            } => Key(VelmKey::Char(ch)),
-            KeyEvent {
-                modifiers: KeyModifiers::CONTROL,
-                code: KeyCode::Char(ch),
-            } => Key(VelmKey::Ctrl(ch)),
-            _ => Key(VelmKey::Unknown),
-        }
+        let KeyEvent {
+            code, modifiers, ..
+        } = event;
+
+        // Shift is already applied to the character so the modifier can be ignored, e.g. Shift+a
+        // arrives as `A`. Shift+Tab arrives as its own BackTab key code.
+        let modifiers = modifiers - KeyModifiers::SHIFT;
+
+        Key(match (modifiers, code) {
+            (KeyModifiers::NONE, KeyCode::Enter) => VelmKey::Enter,
+            (KeyModifiers::NONE, KeyCode::Tab) => VelmKey::Tab,
+            (KeyModifiers::NONE, KeyCode::BackTab) => VelmKey::BackTab,
+            (KeyModifiers::NONE, KeyCode::Backspace) => VelmKey::Backspace,
+            (KeyModifiers::NONE, KeyCode::Esc) => VelmKey::Esc,
+            (KeyModifiers::NONE, KeyCode::Left) => VelmKey::Left,
+            (KeyModifiers::NONE, KeyCode::Right) => VelmKey::Right,
+            (KeyModifiers::NONE, KeyCode::Down) => VelmKey::Down,
+            (KeyModifiers::NONE, KeyCode::Up) => VelmKey::Up,
+            (KeyModifiers::NONE, KeyCode::Insert) => VelmKey::Insert,
+            (KeyModifiers::NONE, KeyCode::Delete) => VelmKey::Delete,
+            (KeyModifiers::NONE, KeyCode::Home) => VelmKey::Home,
+            (KeyModifiers::NONE, KeyCode::End) => VelmKey::End,
+            (KeyModifiers::NONE, KeyCode::PageUp) => VelmKey::PageUp,
+            (KeyModifiers::NONE, KeyCode::PageDown) => VelmKey::PageDown,
+            (KeyModifiers::CONTROL, KeyCode::Left) => VelmKey::CtrlLeft,
+            (KeyModifiers::CONTROL, KeyCode::Right) => VelmKey::CtrlRight,
+            (KeyModifiers::CONTROL, KeyCode::Home) => VelmKey::CtrlHome,
+            (KeyModifiers::CONTROL, KeyCode::End) => VelmKey::CtrlEnd,
+            (KeyModifiers::NONE, KeyCode::F(n)) => VelmKey::Function(n),
+            (KeyModifiers::NONE, KeyCode::Char(ch)) => VelmKey::Char(ch),
+            (KeyModifiers::CONTROL, KeyCode::Char(ch)) => VelmKey::Ctrl(ch),
+            (KeyModifiers::ALT, KeyCode::Char(ch)) => VelmKey::Alt(ch),
+            _ => VelmKey::Unknown,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::CrosstermCanvas;
+    use super::{truecolor_supported, CrosstermCanvas, Key};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use velm_core::{ui::Color, Canvas, Cell, Key as VelmKey};
 
     #[test]
     fn crossterm_backend_enters_and_leaves_alternate_screen() {
@@ -245,8 +308,111 @@ mod tests {
         drop(backend);
 
         assert_eq!(
-            "\u{1b}[?1049h\u{1b}[?1049l",
+            concat!(
+                "\u{1b}[?1049h",
+                "\u{1b}[?1000h\u{1b}[?1002h\u{1b}[?1003h\u{1b}[?1015h\u{1b}[?1006h",
+                "\u{1b}[?2004h",
+                "\u{1b}[?2004l",
+                "\u{1b}[?1006l\u{1b}[?1015l\u{1b}[?1003l\u{1b}[?1002l\u{1b}[?1000l",
+                "\u{1b}[?1049l",
+            ),
             String::from_utf8(out).unwrap()
         );
     }
+
+    #[test]
+    fn shift_and_alt_modifiers_are_mapped() {
+        let tests = vec![
+            (KeyModifiers::SHIFT, KeyCode::Char('A'), VelmKey::Char('A')),
+            (KeyModifiers::SHIFT, KeyCode::BackTab, VelmKey::BackTab),
+            (KeyModifiers::ALT, KeyCode::Char('x'), VelmKey::Alt('x')),
+            (
+                KeyModifiers::CONTROL,
+                KeyCode::Char('r'),
+                VelmKey::Ctrl('r'),
+            ),
+            (KeyModifiers::ALT, KeyCode::Enter, VelmKey::Unknown),
+        ];
+
+        for (modifiers, code, expected) in tests {
+            assert_eq!(expected, Key::from(KeyEvent::new(code, modifiers)).0);
+        }
+    }
+
+    #[test]
+    fn ctrl_navigation_keys_are_mapped() {
+        let tests = vec![
+            (KeyCode::Left, VelmKey::CtrlLeft),
+            (KeyCode::Right, VelmKey::CtrlRight),
+            (KeyCode::Home, VelmKey::CtrlHome),
+            (KeyCode::End, VelmKey::CtrlEnd),
+            (KeyCode::Up, VelmKey::Unknown),
+        ];
+
+        for (code, expected) in tests {
+            assert_eq!(
+                expected,
+                Key::from(KeyEvent::new(code, KeyModifiers::CONTROL)).0
+            );
+        }
+    }
+
+    #[test]
+    fn function_keys_are_mapped() {
+        let event = KeyEvent::new(KeyCode::F(5), KeyModifiers::NONE);
+
+        assert_eq!(VelmKey::Function(5), Key::from(event).0);
+    }
+
+    #[test]
+    fn adjacent_cells_are_drawn_with_a_single_cursor_move() {
+        let mut canvas = CrosstermCanvas {
+            out: Vec::new(),
+            truecolor: true,
+        };
+
+        let mut cells: Vec<Cell> = (0..80)
+            .map(|col| Cell::new(col, 0, "x", Color::Reset, Color::Reset))
+            .collect();
+        cells.push(Cell::new(0, 1, "x", Color::Reset, Color::Reset));
+        cells.push(Cell::new(5, 1, "x", Color::Reset, Color::Reset));
+
+        canvas.draw(cells.iter()).unwrap();
+
+        // Each cursor move is written as `ESC [ row ; col H`.
+        let out = String::from_utf8(canvas.out.clone()).unwrap();
+        assert_eq!(3, out.matches('H').count());
+    }
+
+    #[test]
+    fn rgb_colors_are_downsampled_without_truecolor() {
+        let cells = [Cell::new(0, 0, "x", Color::Rgb(255, 0, 0), Color::Reset)];
+
+        let mut canvas = CrosstermCanvas {
+            out: Vec::new(),
+            truecolor: false,
+        };
+        canvas.draw(cells.iter()).unwrap();
+        let out = String::from_utf8(canvas.out.clone()).unwrap();
+        assert!(out.contains("38;5;196"));
+
+        let mut canvas = CrosstermCanvas {
+            out: Vec::new(),
+            truecolor: true,
+        };
+        canvas.draw(cells.iter()).unwrap();
+        let out = String::from_utf8(canvas.out.clone()).unwrap();
+        assert!(out.contains("38;2;255;0;0"));
+    }
+
+    #[test]
+    fn truecolor_is_detected_from_colorterm_unless_forced() {
+        assert!(truecolor_supported(None, Some("truecolor")));
+        assert!(truecolor_supported(None, Some("24bit")));
+        assert!(!truecolor_supported(None, Some("yes")));
+        assert!(!truecolor_supported(None, None));
+        assert!(truecolor_supported(Some("1"), None));
+        assert!(!truecolor_supported(Some("off"), Some("truecolor")));
+        assert!(truecolor_supported(Some("auto"), Some("truecolor")));
+    }
 }
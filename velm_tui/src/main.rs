@@ -5,7 +5,10 @@ use backtrace::Backtrace;
 use crossterm::{style::Print, terminal::LeaveAlternateScreen};
 use std::panic::{self, PanicInfo};
 use velm_core::Editor;
+#[cfg(not(feature = "termion"))]
 use velm_tui::{map_crossterm_event_stream, CrosstermCanvas};
+#[cfg(feature = "termion")]
+use velm_tui::{map_termion_event_stream, TermionCanvas};
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
@@ -15,12 +18,40 @@ async fn main() -> Result<(), Error> {
         panic_hook(info);
     }));
 
-    let mut canvas =
-        CrosstermCanvas::new(std::io::stdout()).context("unable to create CrosstermCanvas")?;
+    #[cfg(not(feature = "termion"))]
+    let (mut canvas, event_stream) = (
+        CrosstermCanvas::new(std::io::stdout()).context("unable to create CrosstermCanvas")?,
+        map_crossterm_event_stream(),
+    );
+    #[cfg(feature = "termion")]
+    let (mut canvas, event_stream) = (
+        TermionCanvas::new(std::io::stdout()).context("unable to create TermionCanvas")?,
+        map_termion_event_stream(),
+    );
 
-    Editor::new(&mut canvas)
-        .context("unable to initialise Editor")?
-        .consume(map_crossterm_event_stream())
+    let mut editor = Editor::new(&mut canvas).context("unable to initialise Editor")?;
+
+    // A file given on the command line is opened straight away, read-only when preceded by
+    // `-R` like vim, otherwise the welcome screen is shown.
+    let mut args = std::env::args().skip(1);
+    match args.next() {
+        Some(flag) if flag == "-R" => {
+            if let Some(file_name) = args.next() {
+                editor = editor
+                    .with_read_only_file(&file_name)
+                    .context("unable to open file")?;
+            }
+        }
+        Some(file_name) => {
+            editor = editor
+                .with_file(&file_name)
+                .context("unable to open file")?;
+        }
+        None => (),
+    }
+
+    editor
+        .consume(event_stream)
         .await
         .context("error during input event stream consumption")?;
 
@@ -0,0 +1,319 @@
+use std::convert::TryFrom;
+use std::io::{self, Error as IoError, Write};
+use termion::{
+    clear, color, cursor,
+    raw::{IntoRawMode, RawTerminal},
+    screen::{ToAlternateScreen, ToMainScreen},
+};
+use velm_core::{
+    ui::{grapheme_width, Color as VelmColor, Position, Rect},
+    Canvas, Cell, CursorShape, Event, EventStream, Key as VelmKey, MouseKind,
+};
+
+/// Turn on reporting of mouse presses, including the extended modes for large terminals.
+const ENABLE_MOUSE: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
+
+/// Turn off the mouse reporting enabled by `ENABLE_MOUSE`.
+const DISABLE_MOUSE: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l";
+
+/// Map the events read from stdin by termion into the events that are expected by the
+/// application. Termion reads input synchronously so events are read on their own thread, while
+/// resizes are picked up from the `SIGWINCH` signal as termion does not report them.
+pub fn map_termion_event_stream() -> EventStream {
+    use termion::input::TermRead;
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let (event_tx, event_rx) = tokio::sync::mpsc::unbounded_channel();
+
+    let input_tx = event_tx.clone();
+    std::thread::spawn(move || {
+        for possible_event in io::stdin().events() {
+            let event = match possible_event {
+                Ok(termion::event::Event::Key(key)) => Event::KeyPressed(Key::from(key).0),
+                Ok(termion::event::Event::Mouse(event)) => match mouse_event(event) {
+                    Some(event) => event,
+                    None => continue,
+                },
+                Ok(termion::event::Event::Unsupported(_)) => continue,
+                Err(e) => Event::ReadFailed(e),
+            };
+
+            // The Editor has stopped listening so there is no need to keep reading.
+            if input_tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut window_changes = match signal(SignalKind::window_change()) {
+            Ok(window_changes) => window_changes,
+            Err(e) => {
+                let _ = event_tx.send(Event::ReadFailed(e));
+                return;
+            }
+        };
+
+        while window_changes.recv().await.is_some() {
+            let event = match termion::terminal_size() {
+                Ok((width, height)) => Event::WindowResized(width, height),
+                Err(e) => Event::ReadFailed(e),
+            };
+
+            if event_tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    Box::pin(tokio_stream::wrappers::UnboundedReceiverStream::new(
+        event_rx,
+    ))
+}
+
+/// Map the termion mouse event into the mouse input understood by the application. Termion
+/// positions start at 1 rather than 0.
+fn mouse_event(event: termion::event::MouseEvent) -> Option<Event> {
+    use termion::event::{MouseButton, MouseEvent};
+
+    match event {
+        MouseEvent::Press(button, x, y) => Some(Event::MouseInput {
+            col: usize::from(x.saturating_sub(1)),
+            row: usize::from(y.saturating_sub(1)),
+            kind: match button {
+                MouseButton::Left => MouseKind::LeftClick,
+                MouseButton::WheelUp => MouseKind::ScrollUp,
+                MouseButton::WheelDown => MouseKind::ScrollDown,
+                _ => MouseKind::Unknown,
+            },
+        }),
+        _ => None,
+    }
+}
+
+/// Newtype to allow mapping termion::event::Key to VelmKey.
+struct Key(VelmKey);
+
+/// Canvas implementation for termion.
+pub struct TermionCanvas<W: Write> {
+    out: RawTerminal<W>,
+}
+
+impl<W: Write> TermionCanvas<W> {
+    /// Creates a new TermionCanvas.
+    pub fn new(out: W) -> Result<Self, IoError> {
+        let mut out = out.into_raw_mode()?;
+        write!(out, "{}{}", ToAlternateScreen, ENABLE_MOUSE)?;
+        out.flush()?;
+
+        Ok(Self { out })
+    }
+}
+
+impl<W: Write> Drop for TermionCanvas<W> {
+    /// Ensures that we leave the alternate screen before the application ends to return the user
+    /// terminal back to normal. Raw mode is disabled when the `RawTerminal` is dropped.
+    fn drop(&mut self) {
+        write!(self.out, "{}{}", DISABLE_MOUSE, ToMainScreen)
+            .expect("unable to leave alternate screen");
+        self.out.flush().expect("unable to leave alternate screen");
+    }
+}
+
+impl<W: Write> Canvas for TermionCanvas<W> {
+    fn clear(&mut self) -> Result<(), IoError> {
+        write!(self.out, "{}", clear::All)
+    }
+
+    fn draw<'a, I>(&mut self, cells: I) -> Result<(), IoError>
+    where
+        I: Iterator<Item = &'a Cell>,
+    {
+        let mut prev_background = VelmColor::Reset;
+        let mut prev_foreground = VelmColor::Reset;
+
+        // Where the terminal cursor is left after printing the previous cell. Changed cells that
+        // are next to each other are printed as a run without moving the cursor between them.
+        let mut cursor: Option<(usize, usize)> = None;
+
+        for cell in cells {
+            // Spacers are covered by the wide character drawn before them.
+            if cell.is_spacer() {
+                continue;
+            }
+
+            let Position { row, col } = *cell.position();
+
+            if cursor != Some((row, col)) {
+                self.position_cursor(row, col)?;
+            }
+
+            cursor = Some((row, col + grapheme_width(cell.symbol())));
+
+            if cell.background() != prev_background {
+                write_background(&mut self.out, cell.background())?;
+                prev_background = cell.background();
+            }
+
+            if cell.foreground() != prev_foreground {
+                write_foreground(&mut self.out, cell.foreground())?;
+                prev_foreground = cell.foreground();
+            }
+
+            write!(self.out, "{}", cell.symbol())?;
+        }
+
+        write!(
+            self.out,
+            "{}{}",
+            color::Bg(color::Reset),
+            color::Fg(color::Reset)
+        )
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.out.flush()
+    }
+
+    fn hide_cursor(&mut self) -> Result<(), IoError> {
+        write!(self.out, "{}", cursor::Hide)
+    }
+
+    fn position_cursor(&mut self, row: usize, col: usize) -> Result<(), IoError> {
+        // Termion positions start at 1 rather than 0.
+        let x = u16::try_from(col + 1)
+            .map_err(|e| IoError::new(io::ErrorKind::Other, format!("{}", e)))?;
+        let y = u16::try_from(row + 1)
+            .map_err(|e| IoError::new(io::ErrorKind::Other, format!("{}", e)))?;
+
+        write!(self.out, "{}", cursor::Goto(x, y))
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<(), IoError> {
+        match shape {
+            CursorShape::Block => write!(self.out, "{}", cursor::SteadyBlock),
+            CursorShape::Bar => write!(self.out, "{}", cursor::SteadyBar),
+            CursorShape::Underline => write!(self.out, "{}", cursor::SteadyUnderline),
+        }
+    }
+
+    fn show_cursor(&mut self) -> Result<(), IoError> {
+        write!(self.out, "{}", cursor::Show)
+    }
+
+    fn size(&self) -> Result<Rect, IoError> {
+        let (width, height) = termion::terminal_size()?;
+        Ok(Rect::new(usize::from(width), usize::from(height)))
+    }
+}
+
+fn write_foreground<W: Write>(out: &mut W, foreground: VelmColor) -> Result<(), IoError> {
+    match foreground {
+        VelmColor::Reset => write!(out, "{}", color::Fg(color::Reset)),
+        VelmColor::Rgb(r, g, b) => write!(out, "{}", color::Fg(color::Rgb(r, g, b))),
+        _ => write!(out, "{}", color::Fg(ansi_value(foreground))),
+    }
+}
+
+fn write_background<W: Write>(out: &mut W, background: VelmColor) -> Result<(), IoError> {
+    match background {
+        VelmColor::Reset => write!(out, "{}", color::Bg(color::Reset)),
+        VelmColor::Rgb(r, g, b) => write!(out, "{}", color::Bg(color::Rgb(r, g, b))),
+        _ => write!(out, "{}", color::Bg(ansi_value(background))),
+    }
+}
+
+/// Map a VelmColor to the termion ANSI color with the same meaning as crossterm gives it. Reset
+/// and RGB colors have their own termion types and are mapped to black here.
+fn ansi_value(velm_color: VelmColor) -> color::AnsiValue {
+    color::AnsiValue(match velm_color {
+        VelmColor::Reset | VelmColor::Black | VelmColor::Rgb(..) => 0,
+        VelmColor::Red => 1,
+        VelmColor::Green => 2,
+        VelmColor::Yellow => 3,
+        VelmColor::Blue => 4,
+        VelmColor::Magenta => 5,
+        VelmColor::Cyan => 6,
+        VelmColor::Gray => 7,
+        VelmColor::DarkGray => 8,
+        VelmColor::LightRed => 9,
+        VelmColor::LightGreen => 10,
+        VelmColor::LightYellow => 11,
+        VelmColor::LightBlue => 12,
+        VelmColor::LightMagenta => 13,
+        VelmColor::LightCyan => 14,
+        VelmColor::White => 15,
+        VelmColor::AnsiValue(v) => v,
+    })
+}
+
+impl From<termion::event::Key> for Key {
+    fn from(key: termion::event::Key) -> Self {
+        use termion::event::Key as TermionKey;
+
+        Key(match key {
+            // Termion reports Enter and Tab as the characters that they type.
+            TermionKey::Char('\n') => VelmKey::Enter,
+            TermionKey::Char('\t') => VelmKey::Tab,
+            TermionKey::BackTab => VelmKey::BackTab,
+            TermionKey::Backspace => VelmKey::Backspace,
+            TermionKey::Esc => VelmKey::Esc,
+            TermionKey::Left => VelmKey::Left,
+            TermionKey::Right => VelmKey::Right,
+            TermionKey::Up => VelmKey::Up,
+            TermionKey::Down => VelmKey::Down,
+            TermionKey::Insert => VelmKey::Insert,
+            TermionKey::Delete => VelmKey::Delete,
+            TermionKey::Home => VelmKey::Home,
+            TermionKey::End => VelmKey::End,
+            TermionKey::PageUp => VelmKey::PageUp,
+            TermionKey::PageDown => VelmKey::PageDown,
+            TermionKey::F(n) => VelmKey::Function(n),
+            TermionKey::Char(ch) => VelmKey::Char(ch),
+            TermionKey::Ctrl(ch) => VelmKey::Ctrl(ch),
+            TermionKey::Alt(ch) => VelmKey::Alt(ch),
+            _ => VelmKey::Unknown,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ansi_value, mouse_event, Key};
+    use termion::event::{Key as TermionKey, MouseButton, MouseEvent};
+    use velm_core::{ui::Color, Event, Key as VelmKey, MouseKind};
+
+    #[test]
+    fn keys_are_mapped() {
+        let tests = vec![
+            (TermionKey::Char('\n'), VelmKey::Enter),
+            (TermionKey::Char('\t'), VelmKey::Tab),
+            (TermionKey::Char('A'), VelmKey::Char('A')),
+            (TermionKey::Ctrl('r'), VelmKey::Ctrl('r')),
+            (TermionKey::Alt('x'), VelmKey::Alt('x')),
+            (TermionKey::F(5), VelmKey::Function(5)),
+            (TermionKey::Null, VelmKey::Unknown),
+        ];
+
+        for (key, expected) in tests {
+            assert_eq!(expected, Key::from(key).0);
+        }
+    }
+
+    #[test]
+    fn mouse_positions_start_at_zero() {
+        match mouse_event(MouseEvent::Press(MouseButton::Left, 1, 3)) {
+            Some(Event::MouseInput { col, row, kind }) => {
+                assert_eq!((0, 2, MouseKind::LeftClick), (col, row, kind));
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn colors_are_mapped_to_ansi_values() {
+        assert_eq!(1, ansi_value(Color::Red).0);
+        assert_eq!(9, ansi_value(Color::LightRed).0);
+        assert_eq!(236, ansi_value(Color::AnsiValue(236)).0);
+    }
+}